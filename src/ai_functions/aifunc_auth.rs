@@ -0,0 +1,18 @@
+use ai_functions_vasile::ai_function;
+
+#[ai_function]
+pub fn print_auth_backend_code(_code_input_and_public_routes: &str) {
+    /// INPUT: Takes in the Rust actix-web CODE_INPUT of a website backend, and the PUBLIC_ROUTES that must stay reachable without logging in
+    /// FUNCTION: Adds user authentication to the code:
+    ///   1. Users sign up via POST /signup and log in via POST /login with a JSON body { "username": string, "password": string }
+    ///   2. Passwords are NEVER stored in plain text. They are hashed and verified with the argon2 crate (Argon2::default(), random SaltString)
+    ///   3. POST /login returns a JSON body { "token": string } with a JWT issued by the jsonwebtoken crate. The secret is read from the JWT_SECRET environment variable, falling back to a random value generated at startup
+    ///   4. POST /logout revokes the token of the caller. It is not in PUBLIC_ROUTES: without a valid token it returns 401 Unauthorized
+    ///   5. Every route that is not in PUBLIC_ROUTES is protected by a middleware or extractor. Requests without a valid "Authorization: Bearer <token>" header get a 401 Unauthorized response
+    /// IMPORTANT: The following libraries are installed: reqwest, serde, serde_json, tokio, actix-web, async-trait, actix_cors, argon2, jsonwebtoken
+    /// IMPORTANT: Keep every existing route and feature of CODE_INPUT working
+    /// OUTPUT: Print ONLY the code, nothing else. This function ONLY prints code.
+    ///   CODE_INPUT is a set of files, each starting with a line like // FILE: src/main.rs
+    ///   Print every file that changes or is added, for example src/auth.rs, in full and in the same format. Files that are not printed stay as they are
+    println!(OUTPUT)
+}
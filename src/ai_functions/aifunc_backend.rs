@@ -9,42 +9,107 @@ pub fn print_backend_webserver_code(_project_description_and_template: &str) {
     /// IMPORTANT: The following libraries are already installed
     ///   reqwest, serde, serde_json, tokio, actix-web, async-trait, actix_cors
     /// No other external libraries should be used. Write functions that fit with the description from the PROJECT_DESCRIPTION
+    /// IMPORTANT: If a DATABASE_SCHEMA is given, rusqlite is installed too. Persist all data in the "app.db" SQLite file instead of memory or JSON files,
+    ///   and at startup apply the MIGRATION_FILE with conn.execute_batch(include_str!("../<MIGRATION_FILE>"))
+    /// IMPORTANT: Split the code into modules by concern, for example src/main.rs, src/models.rs, src/state.rs, src/handlers.rs and src/routes.rs.
+    ///   src/main.rs declares the other files as modules. Every path is relative to the project root and inside src/
     /// OUTPUT: Print ONLY the code, nothing else. This function ONLY prints code.
+    ///   Every file starts with a line naming it, like this:
+    /// // FILE: src/main.rs
+    /// the code of main.rs
+    /// // FILE: src/handlers.rs
+    /// the code of handlers.rs
     println!(OUTPUT)
 }
 
 #[ai_function]
-pub fn print_improved_webserver_code(_project_description_and_template: &str) {
-    /// INPUT: Takes in a PROJECT_DESCRIPTION and CODE_TEMPLATE for a website backend build
+pub fn print_improved_webserver_code(_code_input_and_code_review: &str) {
+    /// INPUT: Takes in the Rust actix-web CODE_INPUT of a website backend, its PROJECT_DESCRIPTION, the EXTERNAL_URLS it may call, its DATABASE_SCHEMA if any, and a CODE_REVIEW of it
     /// FUNCTION: Performs the following tasks:
-    ///   1. Removes any bugs in the code and adds minor additional functionality
-    ///   2. Makes sure everything requested in the spec from a backend standpoint was followed. If not, add the feature. No code should be implemented later. Everything should be written now.
-    ///   3. ONLY writes the code. No commentary.
-    /// IMPORTANT: The following libraries are already installed. Does not use ANY libraries other than what was provided in the template
-    ///   reqwest, serde, serde_json, tokio, actix-web, async-trait
+    ///   1. Implements every entry of CODE_REVIEW "missing_requirements". No code should be implemented later. Everything should be written now.
+    ///   2. Fixes every entry of CODE_REVIEW "error_handling_gaps", returning fitting HTTP status codes instead of panicking
+    ///   3. Uses every entry of CODE_REVIEW "unused_external_urls" where PROJECT_DESCRIPTION needs data from it
+    ///   4. Keeps every existing route working. ONLY writes the code. No commentary.
+    /// IMPORTANT: The following libraries are already installed. Does not use ANY libraries other than these
+    ///   reqwest, serde, serde_json, tokio, actix-web, async-trait, actix_cors
+    /// IMPORTANT: If a DATABASE_SCHEMA is given, rusqlite is installed too. Keeps persisting all data in the "app.db" SQLite file with rusqlite,
+    ///   and keeps applying the MIGRATION_FILE at startup
+    /// OUTPUT: CODE_INPUT is a set of files, each starting with a line like // FILE: src/handlers.rs
+    ///   Prints every file it changes or adds in full, in the same format. Files it does not print stay as they are
     println!(OUTPUT)
 }
 
 #[ai_function]
 pub fn print_fixed_code(_broken_code_with_bugs: &str) {
     /// INPUT: Takes in Rust BROKEN_CODE and the ERROR_BUGS found
-    /// FUNCTION: Removes bugs from code
+    /// ERROR_BUGS: Either compiler errors, or FAILING ENDPOINTS with the expected and actual status, a response body excerpt and a SERVER LOG TAIL
+    /// FUNCTION: Removes bugs from code so that it compiles and every failing endpoint returns the expected status
     /// IMPORTANT: Only prints out the new and improved code. No commentary or anything else
+    /// OUTPUT: BROKEN_CODE is a set of files, each starting with a line like // FILE: src/handlers.rs
+    ///   Prints every file it changes in full, in the same format. Files it does not print stay as they are
+    println!(OUTPUT)
+}
+
+#[ai_function]
+pub fn print_improved_webserver_code_edits(_code_input_and_code_review: &str) {
+    /// INPUT: Takes in the Rust actix-web CODE_INPUT of a website backend, its PROJECT_DESCRIPTION, the EXTERNAL_URLS it may call, its DATABASE_SCHEMA if any, and a CODE_REVIEW of it
+    /// FUNCTION: Changes CODE_INPUT so that it implements every CODE_REVIEW "missing_requirements", fixes every "error_handling_gaps"
+    ///   and uses every "unused_external_urls" where PROJECT_DESCRIPTION needs data from it, keeping every existing route working
+    /// OUTPUT: Prints ONLY the changes, as search/replace blocks under a line naming the file they change. It looks like this:
+    /// // FILE: src/handlers.rs
+    /// <<<<<<< SEARCH
+    /// lines copied exactly from that file of CODE_INPUT, enough of them to be unique
+    /// =======
+    /// the lines that replace them
+    /// >>>>>>> REPLACE
+    /// OR: Prints, under the same line naming the file, the hunks of a unified diff against that file of CODE_INPUT. It looks like this:
+    /// // FILE: src/handlers.rs
+    /// @@ -12,3 +12,3 @@
+    ///  an unchanged line
+    /// -a removed line
+    /// +an added line
+    /// IMPORTANT: Does not reprint unchanged code outside of the blocks or hunks. A new file is one block with nothing to search for. No commentary.
+    /// IMPORTANT: The following libraries are already installed. Does not use ANY libraries other than these
+    ///   reqwest, serde, serde_json, tokio, actix-web, async-trait, actix_cors
+    /// IMPORTANT: If a DATABASE_SCHEMA is given, rusqlite is installed too. Keeps persisting all data in the "app.db" SQLite file with rusqlite,
+    ///   and keeps applying the MIGRATION_FILE at startup
+    println!(OUTPUT)
+}
+
+#[ai_function]
+pub fn print_fixed_code_edits(_broken_code_with_bugs: &str) {
+    /// INPUT: Takes in Rust BROKEN_CODE and the ERROR_BUGS found
+    /// ERROR_BUGS: Either compiler errors, or FAILING ENDPOINTS with the expected and actual status, a response body excerpt and a SERVER LOG TAIL
+    /// FUNCTION: Removes bugs from code so that it compiles and every failing endpoint returns the expected status
+    /// OUTPUT: Prints ONLY the changes, as search/replace blocks under a line naming the file they change. It looks like this:
+    /// // FILE: src/handlers.rs
+    /// <<<<<<< SEARCH
+    /// lines copied exactly from that file of BROKEN_CODE, enough of them to be unique
+    /// =======
+    /// the lines that replace them
+    /// >>>>>>> REPLACE
+    /// OR: Prints, under the same line naming the file, the hunks of a unified diff against that file of BROKEN_CODE. It looks like this:
+    /// // FILE: src/handlers.rs
+    /// @@ -12,3 +12,3 @@
+    ///  an unchanged line
+    /// -a removed line
+    /// +an added line
+    /// IMPORTANT: Does not reprint unchanged code outside of the blocks or hunks. A new file is one block with nothing to search for. No commentary.
     println!(OUTPUT)
 }
 
 #[ai_function]
 pub fn print_rest_api_endpoints(_code_input: &str) {
-    /// INPUT: Takes in Rust webserver CODE_INPUT based on actix-web
+    /// INPUT: Takes in Rust webserver CODE_INPUT based on actix-web, its files each start with a line like // FILE: src/main.rs
     /// FUNCTION: Prints out the JSON schema for url endpoints and their respective types
     /// LOGIC: Script analyses all code and can categorize into the following object keys:
     ///   "route": This represents the url path of the endpoint
-    ///   "is_route_dynamic": if a route has curly braces in it such as {symbol} or {id} as an example, then this will be set to true
-    ///   "method": This represents the method being called
+    ///   "is_route_dynamic": if a route has curly braces in it such as {symbol} or {id} as an example, then this will be set to true, otherwise false
+    ///   "method": This represents the lowercase HTTP method being called: get, post, put, patch or delete
     ///   "request_body": This represents the body of a post method request
     ///   "response": This represents the output based upon the structs in the code and understanding the functions
     /// IMPORTANT: Only prints out the JSON schema. No commentary or anything else.
-    /// MUST READ: All keys are strings. Even bool should be wrapped in double quotes as "bool"
+    /// MUST READ: "is_route_dynamic" is a JSON boolean. Field types inside "request_body" and "response" are strings such as "number", "string" or "bool"
     /// EXAMPLE:
     /// INPUT_CODE:
     /// ...
@@ -72,7 +137,7 @@ pub fn print_rest_api_endpoints(_code_input: &str) {
     /// [
     ///   {
     ///     "route": "/item/{id}",
-    ///     "is_route_dynamic": "true",
+    ///     "is_route_dynamic": true,
     ///     "method": "get"
     ///     "request_body": "None",
     ///     "response": {
@@ -83,7 +148,7 @@ pub fn print_rest_api_endpoints(_code_input: &str) {
     ///   },
     ///   {
     ///     "route": "/item",
-    ///     "is_route_dynamic": "false",
+    ///     "is_route_dynamic": false,
     ///     "method": "post",
     ///     "request_body": {
     ///       "id": "number",
@@ -94,14 +159,14 @@ pub fn print_rest_api_endpoints(_code_input: &str) {
     ///   },
     ///   {
     ///     "route": "/item/{id}",
-    ///     "is_route_dynamic": "true",
+    ///     "is_route_dynamic": true,
     ///     "method": "delete",
     ///     "request_body": "None",
     ///     "response": "None"
     ///   },
     ///   {
     ///     "route": "/crypto",
-    ///     "is_route_dynamic": "false",
+    ///     "is_route_dynamic": false,
     ///     "method": "get",
     ///     "request_body": "None",
     ///     "response": "not_provided"
@@ -0,0 +1,23 @@
+use ai_functions_vasile::ai_function;
+
+#[ai_function]
+pub fn print_database_schema(_project_description: &str) {
+    /// INPUT: Takes in a PROJECT_DESCRIPTION of a website that needs CRUD functionality, and optionally the ERROR_BUGS of a previous schema attempt
+    /// FUNCTION: Designs the SQLite tables needed to persist the data of the website
+    /// IMPORTANT: Table and column names are snake_case and only use letters, digits and underscores
+    /// IMPORTANT: "sql_type" is one of INTEGER, TEXT, REAL, BLOB or NUMERIC. Every table has exactly one primary key column
+    /// OUTPUT: Prints ONLY a JSON object in the following format, no commentary:
+    ///   {
+    ///     "tables": [
+    ///       {
+    ///         "name": "item",
+    ///         "columns": [
+    ///           { "name": "id", "sql_type": "INTEGER", "is_primary_key": true, "is_nullable": false },
+    ///           { "name": "name", "sql_type": "TEXT", "is_primary_key": false, "is_nullable": false },
+    ///           { "name": "completed", "sql_type": "INTEGER", "is_primary_key": false, "is_nullable": false }
+    ///         ]
+    ///       }
+    ///     ]
+    ///   }
+    println!(OUTPUT)
+}
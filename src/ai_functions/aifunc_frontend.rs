@@ -0,0 +1,21 @@
+use ai_functions_vasile::ai_function;
+
+#[ai_function]
+pub fn print_frontend_code(_project_description_and_api_endpoints: &str) {
+    /// INPUT: Takes in a PROJECT_DESCRIPTION and the API_ENDPOINTS (JSON schema) of an already built website backend
+    /// FUNCTION: Writes a single page static frontend for the website, that lets users call every one of the API_ENDPOINTS
+    /// IMPORTANT: The backend is served from http://localhost:8080. Call it with fetch(), using the exact routes from API_ENDPOINTS, e.g. fetch(`http://localhost:8080/item/${id}`)
+    /// IMPORTANT: No build step and no external libraries. Only plain HTML, CSS and JavaScript inside one index.html file
+    /// IMPORTANT: Use request bodies and render responses in line with "request_body" and "response" of each endpoint
+    /// OUTPUT: Print ONLY the contents of index.html, starting with <!DOCTYPE html>. This function ONLY prints code.
+    println!(OUTPUT)
+}
+
+#[ai_function]
+pub fn print_fixed_frontend_code(_broken_frontend_with_bugs: &str) {
+    /// INPUT: Takes in a BROKEN_FRONTEND index.html, the API_ENDPOINTS it should be calling and the ERROR_BUGS found
+    /// ERROR_BUGS: Pages that did not load, or fetch() calls to a route and method that do not exist in API_ENDPOINTS
+    /// FUNCTION: Removes bugs from the frontend, so that every page loads and every fetch() call targets a route from API_ENDPOINTS with its method
+    /// IMPORTANT: Only prints out the new and improved index.html. No commentary or anything else
+    println!(OUTPUT)
+}
@@ -0,0 +1,25 @@
+use ai_functions_vasile::ai_function;
+
+#[ai_function]
+pub fn print_api_integration_tests(_code_input_and_api_endpoints: &str) {
+    /// INPUT: Takes in the Rust actix-web CODE_INPUT of a website backend and its API_ENDPOINTS (JSON schema)
+    /// FUNCTION: Writes integration tests for the backend, with at least one test per entry of API_ENDPOINTS
+    /// IMPORTANT: The tests are appended to a file that already starts with include!("../src/main.rs"), so every struct and handler of CODE_INPUT is in scope by its plain name
+    /// IMPORTANT: Use actix_web::test: build the app with test::init_service(App::new()...) registering the same routes as CODE_INPUT, call it with test::TestRequest and test::call_service
+    /// IMPORTANT: Every test uses #[actix_web::test]. Use the literal route path in every request, e.g. "/item/1" for "/item/{id}",
+    ///   in the same statement as its method, e.g. test::TestRequest::delete().uri("/item/1")
+    /// IMPORTANT: If the code has authentication, sign up and log in inside the test first and send the "Authorization: Bearer <token>" header
+    /// IMPORTANT: The following libraries are installed: reqwest, serde, serde_json, tokio, actix-web, async-trait, actix_cors
+    /// OUTPUT: Print ONLY the test code (no include! line, no main function), nothing else. This function ONLY prints code.
+    println!(OUTPUT)
+}
+
+#[ai_function]
+pub fn print_fixed_api_integration_tests(_broken_tests_with_bugs: &str) {
+    /// INPUT: Takes in Rust BROKEN_TESTS, the CODE_INPUT they are testing, the API_ENDPOINTS and the ERROR_BUGS found
+    /// ERROR_BUGS: Compiler errors in the tests, or API_ENDPOINTS (method and route) the tests do not call yet
+    /// FUNCTION: Fixes the tests so that they compile and call every one of the API_ENDPOINTS. Does not change what a passing server is expected to return
+    /// IMPORTANT: The tests are appended to a file that already starts with include!("../src/main.rs")
+    /// IMPORTANT: Only prints out the new test code. No include! line, no commentary or anything else
+    println!(OUTPUT)
+}
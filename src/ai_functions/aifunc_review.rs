@@ -0,0 +1,16 @@
+use ai_functions_vasile::ai_function;
+
+#[ai_function]
+pub fn print_code_review(_project_description_and_code_input: &str) {
+    /// INPUT: Takes in a PROJECT_DESCRIPTION and the Rust actix-web CODE_INPUT written for it
+    /// FUNCTION: Reviews the code like a senior engineer and lists concrete problems:
+    ///   "missing_requirements": features asked for in PROJECT_DESCRIPTION that CODE_INPUT does not implement
+    ///   "error_handling_gaps": places that panic (unwrap, expect, indexing) or return 200 on failure instead of a fitting status code
+    /// IMPORTANT: Every entry is one short sentence naming the route, handler or struct it is about. Do not list style issues
+    /// OUTPUT: Print ONLY a JSON object, nothing else. Use empty arrays when nothing is wrong. Example:
+    /// {
+    ///   "missing_requirements": ["No route to delete an item, the description asks for it"],
+    ///   "error_handling_gaps": ["read_item unwraps the lock of the item store and panics if it is poisoned"]
+    /// }
+    println!(OUTPUT)
+}
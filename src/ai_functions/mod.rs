@@ -1,3 +1,8 @@
 pub mod aifunc_architect;
+pub mod aifunc_auth;
 pub mod aifunc_backend;
+pub mod aifunc_database;
+pub mod aifunc_frontend;
 pub mod aifunc_managing;
+pub mod aifunc_qa;
+pub mod aifunc_review;
@@ -1,51 +1,120 @@
 use crate::apis::constants::*;
 use crate::models::general::llm::{APIResponse, ChatCompletion, Message};
+use async_trait::async_trait;
 use dotenv::dotenv;
 use reqwest::header::InvalidHeaderValue;
 use reqwest::{
     header::{HeaderMap, HeaderValue},
     Client,
 };
-use std::env;
+use std::{env, fmt};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum CallGptError {
+    #[error("{0} not found among environment variables")]
+    MissingEnvVar(&'static str),
     #[error("Invalid header: {0}")]
     InvalidHeader(#[from] InvalidHeaderValue),
     #[error("Reqwest error: {0}")]
     ReqwestError(#[from] reqwest::Error),
+    #[error("The response contains no message")]
+    EmptyResponse,
 }
 
-// using dynamically dispatched trait object that implements Error for flexibility
-// ownership of the type implementing Send can safely be transferred between threads
-pub async fn call_gpt(messages: Vec<Message>) -> Result<String, CallGptError> {
-    // enables us to get information from our envvars
-    dotenv().ok();
+// Where the OpenAI requests go and who pays for them
+#[derive(Debug, Clone)]
+pub struct OpenAiConfig {
+    pub api_key: String,
+    pub api_org: String,
+    pub model: String,
+    // randomness of the output: between 0 and 1. Where 0 is not random at all
+    pub temperature: f32,
+}
+
+impl OpenAiConfig {
+    pub fn new(api_key: String, api_org: String) -> Self {
+        Self {
+            api_key,
+            api_org,
+            model: "gpt-4".to_string(),
+            temperature: 0.1,
+        }
+    }
+
+    // Read the keys from the environment (or a .env file)
+    pub fn from_env() -> Result<Self, CallGptError> {
+        // enables us to get information from our envvars
+        dotenv().ok();
+
+        let api_key: String =
+            env::var(OPEN_AI_KEY).map_err(|_| CallGptError::MissingEnvVar(OPEN_AI_KEY))?;
+        let api_org: String =
+            env::var(OPEN_AI_ORG).map_err(|_| CallGptError::MissingEnvVar(OPEN_AI_ORG))?;
+        Ok(Self::new(api_key, api_org))
+    }
+}
+
+// Anything that can answer the prompts of the agents, e.g. OpenAI or a fake in tests
+#[async_trait]
+pub trait LlmProvider: fmt::Debug + Send + Sync {
+    async fn call(&self, messages: Vec<Message>) -> Result<String, CallGptError>;
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OpenAiProvider {
+    // None: read from the environment on every call
+    config: Option<OpenAiConfig>,
+}
 
-    let api_key: String =
-        env::var(OPEN_AI_KEY).expect("OPEN_AI_KEY not found among environment variables");
-    let api_org: String =
-        env::var(OPEN_AI_ORG).expect("OPEN_AI_ORG not found among environment variables");
+impl OpenAiProvider {
+    pub fn new(config: OpenAiConfig) -> Self {
+        Self {
+            config: Some(config),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        Self { config: None }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OpenAiProvider {
+    async fn call(&self, messages: Vec<Message>) -> Result<String, CallGptError> {
+        match &self.config {
+            Some(config) => call_gpt_with_config(config, messages).await,
+            None => call_gpt(messages).await,
+        }
+    }
+}
+
+pub async fn call_gpt(messages: Vec<Message>) -> Result<String, CallGptError> {
+    call_gpt_with_config(&OpenAiConfig::from_env()?, messages).await
+}
 
+pub async fn call_gpt_with_config(
+    config: &OpenAiConfig,
+    messages: Vec<Message>,
+) -> Result<String, CallGptError> {
     // Create API key header
     let mut headers = HeaderMap::new();
     headers.insert(
         "Authorization",
-        HeaderValue::from_str(&format!("Bearer {}", api_key))?,
+        HeaderValue::from_str(&format!("Bearer {}", config.api_key))?,
     );
     headers.insert(
         "OpenAI-Organization",
-        HeaderValue::from_str(api_org.as_str())?,
+        HeaderValue::from_str(config.api_org.as_str())?,
     );
 
     // Create client
     let client = Client::builder().default_headers(headers).build()?;
 
     let chat_completion = ChatCompletion {
-        model: "gpt-4".to_string(),
+        model: config.model.clone(),
         messages,
-        temperature: 0.1,
+        temperature: config.temperature,
     };
 
     let response: APIResponse = client
@@ -56,15 +125,19 @@ pub async fn call_gpt(messages: Vec<Message>) -> Result<String, CallGptError> {
         .json() // convert to APIResponse here
         .await?;
 
-    Ok(response.choices[0].api_message.content.clone())
+    response
+        .choices
+        .first()
+        .map(|choice| choice.api_message.content.clone())
+        .ok_or(CallGptError::EmptyResponse)
 }
 
-#[cfg(test)]
+// only calls OpenAI, see the openai-coverage feature
+#[cfg(all(test, feature = "openai-coverage"))]
 mod tests {
     use super::*;
 
     #[tokio::test]
-    #[cfg(feature = "openai-coverage")]
     async fn test_call_to_openai() {
         let message = Message {
             role: "user".to_string(),
@@ -0,0 +1,534 @@
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
+
+use quote::ToTokens;
+use syn::{
+    visit::{self, Visit},
+    Expr, ExprCall, ExprMethodCall, Fields, FnArg, GenericArgument, Item, ItemFn, ItemStruct, Lit,
+    LitStr, PathArguments, ReturnType, Type,
+};
+use thiserror::Error;
+
+use crate::{
+    helpers::source_files::SourceFiles,
+    models::{
+        agents::agent_traits::RouteObject,
+        general::api_route::{parse_path_params, HttpMethod, ScalarType, ShapeDescriptor},
+    },
+};
+
+// guards against structs that (indirectly) contain themselves
+const MAX_SHAPE_DEPTH: usize = 5;
+
+#[derive(Error, Debug)]
+pub enum CodeAnalysisError {
+    #[error("Failed to parse Rust code: {0}")]
+    Parse(#[from] syn::Error),
+    #[error("No actix-web routes found in the code")]
+    NoRoutes,
+}
+
+// Find the actix-web routes registered in the generated backend, without asking the LLM.
+// Handles `.route("/path", web::get().to(handler))`, `web::resource("/path").route(...)`,
+// `web::scope("/prefix")` and `#[get("/path")]` style handlers
+// The files are read as one, so that routes registered in main.rs find their handlers
+// and request structs in e.g. handlers.rs and models.rs
+pub fn extract_routes(files: &SourceFiles) -> Result<Vec<RouteObject>, CodeAnalysisError> {
+    let mut file = syn::parse_file("")?;
+    for (_, code) in files.iter() {
+        file.items.extend(syn::parse_file(code)?.items);
+    }
+
+    let mut collector = ItemCollector::default();
+    collector.visit_file(&file);
+
+    let mut route_finder = RouteFinder::new(&collector.attribute_routes);
+    route_finder.visit_file(&file);
+
+    // attribute handlers registered somewhere we could not follow (e.g. `configure`)
+    for (handler, (method, path)) in &collector.attribute_routes {
+        if !route_finder.registered_handlers.contains(handler) {
+            route_finder
+                .routes
+                .push((path.clone(), *method, handler.clone()));
+        }
+    }
+
+    let mut seen: HashSet<(String, HttpMethod)> = HashSet::new();
+    let routes: Vec<RouteObject> = route_finder
+        .routes
+        .into_iter()
+        .filter(|(path, method, _)| seen.insert((path.clone(), *method)))
+        .map(|(path, method, handler)| {
+            let handler_fn = collector.functions.get(&handler).copied();
+            RouteObject {
+                is_route_dynamic: !parse_path_params(&path).is_empty(),
+                method,
+                request_body: handler_fn
+                    .map(|item_fn| request_shape(item_fn, &collector.structs))
+                    .unwrap_or(ShapeDescriptor::NotProvided),
+                response: handler_fn
+                    .map(|item_fn| response_shape(item_fn, &collector.structs))
+                    .unwrap_or(ShapeDescriptor::NotProvided),
+                route: path,
+            }
+        })
+        .collect();
+
+    if routes.is_empty() {
+        return Err(CodeAnalysisError::NoRoutes);
+    }
+    Ok(routes)
+}
+
+// Structs, functions and attribute routed handlers of the file, by name
+#[derive(Default)]
+struct ItemCollector<'ast> {
+    structs: HashMap<String, &'ast ItemStruct>,
+    functions: HashMap<String, &'ast ItemFn>,
+    attribute_routes: HashMap<String, (HttpMethod, String)>,
+}
+
+impl<'ast> Visit<'ast> for ItemCollector<'ast> {
+    fn visit_item(&mut self, item: &'ast Item) {
+        match item {
+            Item::Struct(item_struct) => {
+                self.structs
+                    .insert(item_struct.ident.to_string(), item_struct);
+            }
+            Item::Fn(item_fn) => {
+                let name = item_fn.sig.ident.to_string();
+                if let Some(attribute_route) = attribute_route(item_fn) {
+                    self.attribute_routes.insert(name.clone(), attribute_route);
+                }
+                self.functions.insert(name, item_fn);
+            }
+            _ => {}
+        }
+        visit::visit_item(self, item);
+    }
+}
+
+// `#[get("/path")]` -> (Get, "/path")
+fn attribute_route(item_fn: &ItemFn) -> Option<(HttpMethod, String)> {
+    item_fn.attrs.iter().find_map(|attr| {
+        let name = attr.path().segments.last()?.ident.to_string();
+        let method = HttpMethod::from_str(&name).ok()?;
+        let path: LitStr = attr.parse_args().ok()?;
+        Some((method, path.value()))
+    })
+}
+
+struct RouteFinder<'a> {
+    attribute_routes: &'a HashMap<String, (HttpMethod, String)>,
+    // prefixes of the `web::scope`s we are currently inside of
+    scopes: Vec<String>,
+    // (path, method, handler name)
+    routes: Vec<(String, HttpMethod, String)>,
+    registered_handlers: HashSet<String>,
+}
+
+impl<'a> RouteFinder<'a> {
+    fn new(attribute_routes: &'a HashMap<String, (HttpMethod, String)>) -> Self {
+        Self {
+            attribute_routes,
+            scopes: vec![],
+            routes: vec![],
+            registered_handlers: HashSet::new(),
+        }
+    }
+
+    fn current_prefix(&self) -> String {
+        self.scopes.concat()
+    }
+
+    fn record(&mut self, node: &ExprMethodCall) {
+        let method_name = node.method.to_string();
+        let receiver_base = base_call(&node.receiver);
+        let receiver_scope = receiver_base.and_then(|call| string_arg_of(call, "scope"));
+
+        match (method_name.as_str(), node.args.len()) {
+            // .route("/path", web::get().to(handler))
+            ("route", 2) => {
+                let (Some(path), Some((method, handler))) =
+                    (string_literal(&node.args[0]), route_target(&node.args[1]))
+                else {
+                    return;
+                };
+                let prefix = format!(
+                    "{}{}",
+                    self.current_prefix(),
+                    receiver_scope.unwrap_or_default()
+                );
+                self.routes
+                    .push((format!("{}{}", prefix, path), method, handler));
+            }
+            // web::resource("/path").route(web::get().to(handler))
+            ("route", 1) => {
+                let (Some(path), Some((method, handler))) = (
+                    receiver_base.and_then(|call| string_arg_of(call, "resource")),
+                    route_target(&node.args[0]),
+                ) else {
+                    return;
+                };
+                self.routes.push((
+                    format!("{}{}", self.current_prefix(), path),
+                    method,
+                    handler,
+                ));
+            }
+            // .service(handler) where handler is annotated with #[get("/path")]
+            ("service", 1) => {
+                let Some(handler) = path_ident(&node.args[0]) else {
+                    return;
+                };
+                if let Some((method, path)) = self.attribute_routes.get(&handler) {
+                    let prefix = format!(
+                        "{}{}",
+                        self.current_prefix(),
+                        receiver_scope.unwrap_or_default()
+                    );
+                    self.routes
+                        .push((format!("{}{}", prefix, path), *method, handler.clone()));
+                    self.registered_handlers.insert(handler);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for RouteFinder<'_> {
+    fn visit_expr_method_call(&mut self, node: &'ast ExprMethodCall) {
+        self.record(node);
+
+        self.visit_expr(&node.receiver);
+        // whatever is registered on a scope lives under its prefix
+        let scope = base_call(&node.receiver).and_then(|call| string_arg_of(call, "scope"));
+        let is_scoped = scope.is_some();
+        if let Some(prefix) = scope {
+            self.scopes.push(prefix);
+        }
+        for arg in &node.args {
+            self.visit_expr(arg);
+        }
+        if is_scoped {
+            self.scopes.pop();
+        }
+    }
+}
+
+// Follow a builder chain such as `web::get().guard(..).to(handler)` down to `web::get()`
+fn base_call(expr: &Expr) -> Option<&ExprCall> {
+    match expr {
+        Expr::MethodCall(method_call) => base_call(&method_call.receiver),
+        Expr::Call(call) => Some(call),
+        Expr::Paren(paren) => base_call(&paren.expr),
+        _ => None,
+    }
+}
+
+// `web::scope("/api")` -> Some("/api") when `function_name` is "scope"
+fn string_arg_of(call: &ExprCall, function_name: &str) -> Option<String> {
+    if path_ident(&call.func)? != function_name {
+        return None;
+    }
+    string_literal(call.args.first()?)
+}
+
+// `web::get().to(handler)` -> (Get, "handler")
+fn route_target(expr: &Expr) -> Option<(HttpMethod, String)> {
+    let Expr::MethodCall(to_call) = expr else {
+        return None;
+    };
+    if to_call.method != "to" {
+        return None;
+    }
+    let handler = path_ident(to_call.args.first()?)?;
+    let method = HttpMethod::from_str(&path_ident(&base_call(&to_call.receiver)?.func)?).ok()?;
+    Some((method, handler))
+}
+
+fn string_literal(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Lit(expr_lit) => match &expr_lit.lit {
+            Lit::Str(lit_str) => Some(lit_str.value()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// Last segment of a path expression: `web::get` -> "get"
+fn path_ident(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Path(expr_path) => Some(expr_path.path.segments.last()?.ident.to_string()),
+        _ => None,
+    }
+}
+
+// The request body is whatever the handler extracts with web::Json or web::Form
+fn request_shape(item_fn: &ItemFn, structs: &HashMap<String, &ItemStruct>) -> ShapeDescriptor {
+    item_fn
+        .sig
+        .inputs
+        .iter()
+        .find_map(|input| match input {
+            FnArg::Typed(pat_type) => {
+                let (wrapper, inner) = split_generic(&pat_type.ty)?;
+                matches!(wrapper.as_str(), "Json" | "Form")
+                    .then(|| type_to_shape(inner, structs, 0))
+            }
+            FnArg::Receiver(_) => None,
+        })
+        .unwrap_or(ShapeDescriptor::None)
+}
+
+// The response is read from a `web::Json<T>` return type, or from `.json(T { .. })` in the body
+fn response_shape(item_fn: &ItemFn, structs: &HashMap<String, &ItemStruct>) -> ShapeDescriptor {
+    if let ReturnType::Type(_, return_type) = &item_fn.sig.output {
+        if let Some(shape) = json_return_shape(return_type, structs) {
+            return shape;
+        }
+    }
+
+    let mut json_finder = JsonBodyFinder::default();
+    json_finder.visit_block(&item_fn.block);
+    match json_finder.struct_name {
+        Some(struct_name) => named_type_shape(&struct_name, structs, 0),
+        None if json_finder.found_json_call => ShapeDescriptor::NotProvided,
+        None => ShapeDescriptor::None,
+    }
+}
+
+fn json_return_shape(
+    return_type: &Type,
+    structs: &HashMap<String, &ItemStruct>,
+) -> Option<ShapeDescriptor> {
+    let (wrapper, inner) = split_generic(return_type)?;
+    match wrapper.as_str() {
+        "Json" => Some(type_to_shape(inner, structs, 0)),
+        // Result<web::Json<T>, E>
+        "Result" | "Option" => json_return_shape(inner, structs),
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+struct JsonBodyFinder {
+    found_json_call: bool,
+    struct_name: Option<String>,
+}
+
+impl<'ast> Visit<'ast> for JsonBodyFinder {
+    fn visit_expr_method_call(&mut self, node: &'ast ExprMethodCall) {
+        if node.method == "json" {
+            self.found_json_call = true;
+            if let Some(Expr::Struct(expr_struct)) = node.args.first() {
+                if let Some(segment) = expr_struct.path.segments.last() {
+                    self.struct_name.get_or_insert(segment.ident.to_string());
+                }
+            }
+        }
+        visit::visit_expr_method_call(self, node);
+    }
+}
+
+// `web::Json<Item>` -> ("Json", Item)
+fn split_generic(ty: &Type) -> Option<(String, &Type)> {
+    let Type::Path(type_path) = ty else {
+        return None;
+    };
+    let segment = type_path.path.segments.last()?;
+    let PathArguments::AngleBracketed(generics) = &segment.arguments else {
+        return None;
+    };
+    let inner = generics.args.iter().find_map(|arg| match arg {
+        GenericArgument::Type(inner) => Some(inner),
+        _ => None,
+    })?;
+    Some((segment.ident.to_string(), inner))
+}
+
+fn type_to_shape(
+    ty: &Type,
+    structs: &HashMap<String, &ItemStruct>,
+    depth: usize,
+) -> ShapeDescriptor {
+    match ty {
+        Type::Reference(reference) => type_to_shape(&reference.elem, structs, depth),
+        Type::Slice(slice) => {
+            ShapeDescriptor::Array(Box::new(type_to_shape(&slice.elem, structs, depth)))
+        }
+        Type::Tuple(tuple) if tuple.elems.is_empty() => ShapeDescriptor::None,
+        Type::Path(type_path) => {
+            let Some(segment) = type_path.path.segments.last() else {
+                return ShapeDescriptor::NotProvided;
+            };
+            let name = segment.ident.to_string();
+            match (name.as_str(), split_generic(ty)) {
+                ("Vec" | "VecDeque" | "HashSet" | "BTreeSet", Some((_, inner))) => {
+                    ShapeDescriptor::Array(Box::new(type_to_shape(inner, structs, depth)))
+                }
+                ("Option" | "Box" | "Json" | "Form" | "Data" | "Mutex", Some((_, inner))) => {
+                    type_to_shape(inner, structs, depth)
+                }
+                _ => named_type_shape(&name, structs, depth),
+            }
+        }
+        other => ShapeDescriptor::Scalar(ScalarType::Other(other.to_token_stream().to_string())),
+    }
+}
+
+fn named_type_shape(
+    name: &str,
+    structs: &HashMap<String, &ItemStruct>,
+    depth: usize,
+) -> ShapeDescriptor {
+    match name {
+        "String" | "str" | "char" => ShapeDescriptor::Scalar(ScalarType::String),
+        "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "u8" | "u16" | "u32" | "u64" | "u128"
+        | "usize" | "f32" | "f64" => ShapeDescriptor::Scalar(ScalarType::Number),
+        "bool" => ShapeDescriptor::Scalar(ScalarType::Bool),
+        _ => match structs.get(name) {
+            Some(item_struct) if depth < MAX_SHAPE_DEPTH => match &item_struct.fields {
+                Fields::Named(fields) => ShapeDescriptor::Object(
+                    fields
+                        .named
+                        .iter()
+                        .filter_map(|field| {
+                            let field_name = field.ident.as_ref()?.to_string();
+                            Some((field_name, type_to_shape(&field.ty, structs, depth + 1)))
+                        })
+                        .collect(),
+                ),
+                _ => ShapeDescriptor::Scalar(ScalarType::Other(name.to_string())),
+            },
+            _ => ShapeDescriptor::Scalar(ScalarType::Other(name.to_string())),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WEBSERVER_CODE: &str = r#"
+        use actix_web::{get, web, App, HttpResponse, HttpServer, Responder};
+        use serde::{Deserialize, Serialize};
+
+        #[derive(Serialize, Deserialize, Clone)]
+        struct Item {
+            id: u64,
+            name: String,
+            completed: bool,
+            tags: Vec<String>,
+        }
+
+        async fn create_item(item: web::Json<Item>) -> impl Responder {
+            HttpResponse::Ok().finish()
+        }
+
+        async fn read_item(path: web::Path<u64>) -> impl Responder {
+            HttpResponse::Ok().json(Item { id: 1, name: "a".to_string(), completed: false, tags: vec![] })
+        }
+
+        async fn list_items() -> web::Json<Vec<Item>> {
+            web::Json(vec![])
+        }
+
+        #[get("/health")]
+        async fn health() -> impl Responder {
+            HttpResponse::Ok().body("ok")
+        }
+
+        #[actix_web::main]
+        async fn main() -> std::io::Result<()> {
+            HttpServer::new(move || {
+                App::new()
+                    .route("/item", web::post().to(create_item))
+                    .route("/item/{id}", web::get().to(read_item))
+                    .service(web::scope("/api").service(web::resource("/items").route(web::get().to(list_items))))
+                    .service(health)
+            })
+            .bind("127.0.0.1:8080")?
+            .run()
+            .await
+        }
+    "#;
+
+    fn find<'a>(routes: &'a [RouteObject], route: &str) -> &'a RouteObject {
+        routes
+            .iter()
+            .find(|route_object| route_object.route == route)
+            .unwrap_or_else(|| panic!("route {} not found in {:?}", route, routes))
+    }
+
+    #[test]
+    fn test_extract_routes() {
+        let routes = extract_routes(&SourceFiles::from_main(WEBSERVER_CODE)).unwrap();
+        assert_eq!(routes.len(), 4);
+
+        let create_item = find(&routes, "/item");
+        assert_eq!(create_item.method, HttpMethod::Post);
+        assert!(!create_item.is_route_dynamic);
+        let ShapeDescriptor::Object(fields) = &create_item.request_body else {
+            panic!("expected an object request body");
+        };
+        assert_eq!(fields["id"], ShapeDescriptor::Scalar(ScalarType::Number));
+        assert_eq!(
+            fields["tags"],
+            ShapeDescriptor::Array(Box::new(ShapeDescriptor::Scalar(ScalarType::String)))
+        );
+        assert_eq!(create_item.response, ShapeDescriptor::None);
+
+        let read_item = find(&routes, "/item/{id}");
+        assert_eq!(read_item.method, HttpMethod::Get);
+        assert!(read_item.is_route_dynamic);
+        assert_eq!(read_item.request_body, ShapeDescriptor::None);
+        assert!(matches!(read_item.response, ShapeDescriptor::Object(_)));
+
+        let list_items = find(&routes, "/api/items");
+        assert!(matches!(list_items.response, ShapeDescriptor::Array(_)));
+
+        let health = find(&routes, "/health");
+        assert_eq!(health.method, HttpMethod::Get);
+    }
+
+    #[test]
+    fn test_extract_routes_across_files() {
+        let files = SourceFiles::parse(
+            "// FILE: src/main.rs\n\
+             mod handlers;\n\
+             fn main() {\n    \
+                 App::new().route(\"/items\", web::get().to(handlers::list_items));\n\
+             }\n\
+             // FILE: src/handlers.rs\n\
+             pub struct Item { pub id: u64 }\n\
+             pub async fn list_items() -> web::Json<Vec<Item>> { todo!() }\n",
+            &SourceFiles::default(),
+        )
+        .unwrap();
+
+        let routes = extract_routes(&files).unwrap();
+        assert_eq!(routes.len(), 1);
+        let ShapeDescriptor::Array(item) = &routes[0].response else {
+            panic!("expected the items of handlers.rs as the response");
+        };
+        assert!(matches!(**item, ShapeDescriptor::Object(_)));
+    }
+
+    #[test]
+    fn test_extract_routes_errors() {
+        assert!(matches!(
+            extract_routes(&SourceFiles::from_main("fn main() {")),
+            Err(CodeAnalysisError::Parse(_))
+        ));
+        assert!(matches!(
+            extract_routes(&SourceFiles::from_main("fn main() {}")),
+            Err(CodeAnalysisError::NoRoutes)
+        ));
+    }
+}
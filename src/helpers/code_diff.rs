@@ -0,0 +1,246 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiffLine<'a> {
+    Context(&'a str),
+    Added(&'a str),
+    Removed(&'a str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DiffStats {
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+impl DiffStats {
+    pub fn is_unchanged(&self) -> bool {
+        self.insertions == 0 && self.deletions == 0
+    }
+}
+
+impl fmt::Display for DiffStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plural = |count: usize, word: &str| {
+            format!("{} {}{}", count, word, if count == 1 { "" } else { "s" })
+        };
+        write!(
+            f,
+            "{}(+), {}(-)",
+            plural(self.insertions, "insertion"),
+            plural(self.deletions, "deletion")
+        )
+    }
+}
+
+// The comparison table takes a cell per pair of changed lines, 32 MiB at most
+const MAX_DIFF_CELLS: usize = 4 * 1024 * 1024;
+
+// Line by line difference of two texts, based on their longest common subsequence of lines.
+// None when the changed lines are too many to compare
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Option<Vec<DiffLine<'a>>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    // the lines both ends have in common don't need the table
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_changed = &old[prefix..old.len() - suffix];
+    let new_changed = &new[prefix..new.len() - suffix];
+    if (old_changed.len() + 1).saturating_mul(new_changed.len() + 1) > MAX_DIFF_CELLS {
+        return None;
+    }
+
+    let mut diff: Vec<DiffLine> = old[..prefix]
+        .iter()
+        .map(|line| DiffLine::Context(line))
+        .collect();
+    diff.extend(changed_lines(old_changed, new_changed));
+    diff.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|line| DiffLine::Context(line)),
+    );
+    Some(diff)
+}
+
+fn changed_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<DiffLine<'a>> {
+    let width = new.len() + 1;
+
+    // common[i * width + j]: length of the longest common subsequence of old[i..] and new[j..]
+    let mut common = vec![0usize; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i * width + j] = if old[i] == new[j] {
+                common[(i + 1) * width + j + 1] + 1
+            } else {
+                common[(i + 1) * width + j].max(common[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut diff = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            diff.push(DiffLine::Context(old[i]));
+            i += 1;
+            j += 1;
+        } else if common[(i + 1) * width + j] >= common[i * width + j + 1] {
+            diff.push(DiffLine::Removed(old[i]));
+            i += 1;
+        } else {
+            diff.push(DiffLine::Added(new[j]));
+            j += 1;
+        }
+    }
+    diff.extend(old[i..].iter().map(|line| DiffLine::Removed(line)));
+    diff.extend(new[j..].iter().map(|line| DiffLine::Added(line)));
+    diff
+}
+
+pub fn diff_stats(diff: &[DiffLine]) -> DiffStats {
+    diff.iter().fold(DiffStats::default(), |mut stats, line| {
+        match line {
+            DiffLine::Added(_) => stats.insertions += 1,
+            DiffLine::Removed(_) => stats.deletions += 1,
+            DiffLine::Context(_) => {}
+        }
+        stats
+    })
+}
+
+// `-start,length` of a hunk, where an empty range starts at the line before it
+fn hunk_range(lines_before: usize, length: usize) -> String {
+    let start = if length == 0 {
+        lines_before
+    } else {
+        lines_before + 1
+    };
+    format!("{},{}", start, length)
+}
+
+// Unified diff with `context` unchanged lines around every change, empty when nothing changed
+pub fn unified_diff(diff: &[DiffLine], old_name: &str, new_name: &str, context: usize) -> String {
+    let changes: Vec<usize> = diff
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, DiffLine::Context(_)))
+        .map(|(index, _)| index)
+        .collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    // changes closer than two contexts apart share a hunk
+    let mut hunks: Vec<(usize, usize)> = vec![];
+    for &index in &changes {
+        let start = index.saturating_sub(context);
+        let end = (index + context + 1).min(diff.len());
+        match hunks.last_mut() {
+            Some(hunk) if start <= hunk.1 => hunk.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    let mut unified = format!("--- {}\n+++ {}\n", old_name, new_name);
+    for (start, end) in hunks {
+        let count = |lines: &[DiffLine], is_old: bool| {
+            lines
+                .iter()
+                .filter(|line| match line {
+                    DiffLine::Context(_) => true,
+                    DiffLine::Removed(_) => is_old,
+                    DiffLine::Added(_) => !is_old,
+                })
+                .count()
+        };
+        unified.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(count(&diff[..start], true), count(&diff[start..end], true)),
+            hunk_range(
+                count(&diff[..start], false),
+                count(&diff[start..end], false)
+            ),
+        ));
+        for line in &diff[start..end] {
+            let (prefix, text) = match line {
+                DiffLine::Context(text) => (' ', text),
+                DiffLine::Added(text) => ('+', text),
+                DiffLine::Removed(text) => ('-', text),
+            };
+            unified.push_str(&format!("{}{}\n", prefix, text));
+        }
+    }
+    unified
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_lines() {
+        let diff = diff_lines("a\nb\nc\n", "a\nc\nd\n").unwrap();
+
+        assert_eq!(
+            diff,
+            vec![
+                DiffLine::Context("a"),
+                DiffLine::Removed("b"),
+                DiffLine::Context("c"),
+                DiffLine::Added("d"),
+            ]
+        );
+        assert_eq!(
+            diff_stats(&diff).to_string(),
+            "1 insertion(+), 1 deletion(-)"
+        );
+        assert!(diff_stats(&diff_lines("a\n", "a\n").unwrap()).is_unchanged());
+    }
+
+    #[test]
+    fn test_diff_lines_too_many_changes() {
+        let lines =
+            |prefix: &str| -> String { (0..3000).map(|n| format!("{}{}\n", prefix, n)).collect() };
+        let (old, new) = (lines("old "), lines("new "));
+        assert_eq!(diff_lines(&old, &new), None);
+
+        // only the changed lines count
+        let edited = old.replacen("old 1500\n", "new 1500\n", 1);
+        assert_eq!(
+            diff_stats(&diff_lines(&old, &edited).unwrap()).to_string(),
+            "1 insertion(+), 1 deletion(-)"
+        );
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+        let new = "1\ntwo\n3\n4\n5\n6\n7\n8\n9\n10\n11\n";
+
+        assert_eq!(
+            unified_diff(
+                &diff_lines(old, new).unwrap(),
+                "main.rs",
+                "main.rs (generated)",
+                1
+            ),
+            "--- main.rs\n+++ main.rs (generated)\n\
+             @@ -1,3 +1,3 @@\n 1\n-2\n+two\n 3\n\
+             @@ -10,1 +10,2 @@\n 10\n+11\n"
+        );
+        assert_eq!(
+            unified_diff(&diff_lines("", "fn main() {}\n").unwrap(), "a", "b", 3),
+            "--- a\n+++ b\n@@ -0,0 +1,1 @@\n+fn main() {}\n"
+        );
+        assert_eq!(
+            unified_diff(&diff_lines(old, old).unwrap(), "a", "b", 3),
+            ""
+        );
+    }
+}
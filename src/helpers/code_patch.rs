@@ -0,0 +1,307 @@
+use thiserror::Error;
+
+const SEARCH_MARKER: &str = "<<<<<<< SEARCH";
+const DIVIDER_MARKER: &str = "=======";
+const REPLACE_MARKER: &str = ">>>>>>> REPLACE";
+
+#[derive(Error, Debug, PartialEq)]
+pub enum PatchError {
+    #[error("the response holds neither search/replace blocks nor a unified diff")]
+    Unrecognized,
+    #[error("edit {edit} is malformed: {reason}")]
+    Malformed { edit: usize, reason: String },
+    #[error("edit {edit} does not match the code, looking for '{snippet}'")]
+    NotFound { edit: usize, snippet: String },
+    #[error("edit {edit} matches the code in {matches} places")]
+    Ambiguous { edit: usize, matches: usize },
+}
+
+// These lines, found exactly once in the code, become those lines
+#[derive(Debug, PartialEq)]
+struct Edit {
+    search: Vec<String>,
+    replace: Vec<String>,
+    // 0-based line the edit claims to start at, unified diffs know it
+    line_hint: Option<usize>,
+}
+
+// Apply the edits an LLM printed, search/replace blocks or a unified diff, one after the other.
+// An edit that doesn't match exactly one place is a conflict, nothing is applied then
+pub fn apply_patch(code: &str, patch: &str) -> Result<String, PatchError> {
+    let edits = if patch.contains(SEARCH_MARKER) {
+        parse_search_replace(patch)?
+    } else if patch.lines().any(|line| line.starts_with("@@")) {
+        parse_unified_diff(patch)?
+    } else {
+        return Err(PatchError::Unrecognized);
+    };
+    if edits.is_empty() {
+        return Err(PatchError::Unrecognized);
+    }
+
+    let mut lines: Vec<String> = code.lines().map(String::from).collect();
+    // earlier edits move the lines the later hints point to
+    let mut line_shift: isize = 0;
+    for (index, edit) in edits.iter().enumerate() {
+        let line_hint = edit
+            .line_hint
+            .map(|line_hint| line_hint.saturating_add_signed(line_shift));
+        let start = find_edit(&lines, edit, line_hint, index + 1)?;
+        lines.splice(
+            start..start + edit.search.len(),
+            edit.replace.iter().cloned(),
+        );
+        line_shift += edit.replace.len() as isize - edit.search.len() as isize;
+    }
+
+    let mut patched = lines.join("\n");
+    if code.ends_with('\n') || code.is_empty() {
+        patched.push('\n');
+    }
+    Ok(patched)
+}
+
+// Where the edit starts, trailing whitespace doesn't count as a difference
+fn find_edit(
+    lines: &[String],
+    edit: &Edit,
+    line_hint: Option<usize>,
+    edit_number: usize,
+) -> Result<usize, PatchError> {
+    if edit.search.is_empty() {
+        // pure insertion, only a unified diff says where
+        return match line_hint {
+            Some(line_hint) => Ok(line_hint.min(lines.len())),
+            None if lines.is_empty() => Ok(0),
+            None => Err(PatchError::Malformed {
+                edit: edit_number,
+                reason: "nothing to search for".to_string(),
+            }),
+        };
+    }
+
+    let matches: Vec<usize> = (0..lines.len().saturating_sub(edit.search.len() - 1))
+        .filter(|&start| {
+            lines[start..start + edit.search.len()]
+                .iter()
+                .zip(&edit.search)
+                .all(|(line, search)| line.trim_end() == search.trim_end())
+        })
+        .collect();
+
+    match matches.as_slice() {
+        [] => Err(PatchError::NotFound {
+            edit: edit_number,
+            snippet: edit.search[0].trim().to_string(),
+        }),
+        [start] => Ok(*start),
+        _ => line_hint
+            .filter(|line_hint| matches.contains(line_hint))
+            .ok_or(PatchError::Ambiguous {
+                edit: edit_number,
+                matches: matches.len(),
+            }),
+    }
+}
+
+fn parse_search_replace(patch: &str) -> Result<Vec<Edit>, PatchError> {
+    enum Section {
+        Outside,
+        Search,
+        Replace,
+    }
+
+    let mut edits: Vec<Edit> = vec![];
+    let mut section = Section::Outside;
+    let (mut search, mut replace): (Vec<String>, Vec<String>) = (vec![], vec![]);
+
+    // anything outside of the blocks, like code fences or commentary, is skipped
+    for line in patch.lines() {
+        let marker = line.trim();
+        match section {
+            Section::Outside if marker == SEARCH_MARKER => section = Section::Search,
+            Section::Outside => {}
+            Section::Search if marker == DIVIDER_MARKER => section = Section::Replace,
+            Section::Search => search.push(line.to_string()),
+            Section::Replace if marker == REPLACE_MARKER => {
+                edits.push(Edit {
+                    search: std::mem::take(&mut search),
+                    replace: std::mem::take(&mut replace),
+                    line_hint: None,
+                });
+                section = Section::Outside;
+            }
+            Section::Replace => replace.push(line.to_string()),
+        }
+    }
+
+    match section {
+        Section::Outside => Ok(edits),
+        _ => Err(PatchError::Malformed {
+            edit: edits.len() + 1,
+            reason: format!("missing '{}'", REPLACE_MARKER),
+        }),
+    }
+}
+
+fn parse_unified_diff(patch: &str) -> Result<Vec<Edit>, PatchError> {
+    let mut edits: Vec<Edit> = vec![];
+    // trailing empty lines would read as empty context lines
+    let lines: Vec<&str> = patch.trim_end().lines().collect();
+
+    let mut index = 0;
+    while index < lines.len() {
+        let Some(header) = lines[index].strip_prefix("@@") else {
+            index += 1;
+            continue;
+        };
+        let line_hint = hunk_start(header).ok_or_else(|| PatchError::Malformed {
+            edit: edits.len() + 1,
+            reason: format!("unreadable hunk header '@@{}'", header),
+        })?;
+
+        let mut edit = Edit {
+            search: vec![],
+            replace: vec![],
+            line_hint: Some(line_hint),
+        };
+        index += 1;
+        while index < lines.len() {
+            let line = lines[index];
+            let is_file_header = line.starts_with("--- ")
+                && lines
+                    .get(index + 1)
+                    .is_some_and(|next| next.starts_with("+++ "));
+            if line.starts_with("@@") || line.starts_with("```") || is_file_header {
+                break;
+            }
+            match line.chars().next() {
+                Some('+') => edit.replace.push(line[1..].to_string()),
+                Some('-') => edit.search.push(line[1..].to_string()),
+                Some(' ') => {
+                    edit.search.push(line[1..].to_string());
+                    edit.replace.push(line[1..].to_string());
+                }
+                // "\ No newline at end of file"
+                Some('\\') => {}
+                // LLMs drop the space in front of empty context lines
+                None => {
+                    edit.search.push(String::new());
+                    edit.replace.push(String::new());
+                }
+                Some(_) => {
+                    return Err(PatchError::Malformed {
+                        edit: edits.len() + 1,
+                        reason: format!("unexpected line '{}'", line),
+                    })
+                }
+            }
+            index += 1;
+        }
+        edits.push(edit);
+    }
+
+    Ok(edits)
+}
+
+// 0-based start in the old code of a hunk header like " -12,7 +12,9 @@",
+// a hunk removing nothing ("-12,0") inserts after the line it names
+fn hunk_start(header: &str) -> Option<usize> {
+    let old_range = header.split_whitespace().next()?.strip_prefix('-')?;
+    let (start, length) = old_range.split_once(',').unwrap_or((old_range, "1"));
+    let start: usize = start.parse().ok()?;
+    match length.parse::<usize>().ok()? {
+        0 => Some(start),
+        _ => Some(start.saturating_sub(1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE: &str = "use actix_web::{web, App};\n\
+                        \n\
+                        async fn health() -> &'static str {\n    \
+                            \"ok\"\n\
+                        }\n\
+                        \n\
+                        async fn items() -> &'static str {\n    \
+                            \"[]\"\n\
+                        }\n";
+
+    #[test]
+    fn test_search_replace_blocks() {
+        let patch = "Here are the edits:\n\
+                     ```\n\
+                     <<<<<<< SEARCH\n\
+                     async fn items() -> &'static str {\n    \
+                         \"[]\"\n\
+                     =======\n\
+                     async fn items() -> &'static str {\n    \
+                         \"[1, 2]\"\n\
+                     >>>>>>> REPLACE\n\
+                     <<<<<<< SEARCH\n\
+                     use actix_web::{web, App};\n\
+                     =======\n\
+                     use actix_web::{web, App, HttpServer};\n\
+                     >>>>>>> REPLACE\n\
+                     ```\n";
+
+        let patched = apply_patch(CODE, patch).unwrap();
+        assert!(patched.starts_with("use actix_web::{web, App, HttpServer};\n"));
+        assert!(patched.contains("    \"[1, 2]\"\n}\n"));
+        assert!(patched.contains("    \"ok\"\n"));
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let patch = "--- main.rs\n\
+                     +++ main.rs\n\
+                     @@ -3,3 +3,3 @@\n \
+                     async fn health() -> &'static str {\n\
+                     -    \"ok\"\n\
+                     +    \"healthy\"\n \
+                     }\n\
+                     @@ -9,0 +10,4 @@\n\
+                     +\n\
+                     +async fn version() -> &'static str {\n\
+                     +    \"1.0\"\n\
+                     +}\n";
+
+        let patched = apply_patch(CODE, patch).unwrap();
+        assert!(patched.contains("    \"healthy\"\n"));
+        assert!(patched
+            .ends_with("    \"[]\"\n}\n\nasync fn version() -> &'static str {\n    \"1.0\"\n}\n"));
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let not_found = "<<<<<<< SEARCH\nasync fn users() {\n=======\n>>>>>>> REPLACE\n";
+        assert_eq!(
+            apply_patch(CODE, not_found),
+            Err(PatchError::NotFound {
+                edit: 1,
+                snippet: "async fn users() {".to_string()
+            })
+        );
+
+        let ambiguous = "<<<<<<< SEARCH\n}\n=======\n};\n>>>>>>> REPLACE\n";
+        assert_eq!(
+            apply_patch(CODE, ambiguous),
+            Err(PatchError::Ambiguous {
+                edit: 1,
+                matches: 2
+            })
+        );
+
+        let unterminated = "<<<<<<< SEARCH\n}\n=======\n";
+        assert!(matches!(
+            apply_patch(CODE, unterminated),
+            Err(PatchError::Malformed { edit: 1, .. })
+        ));
+
+        // a whole file instead of edits
+        assert_eq!(apply_patch(CODE, CODE), Err(PatchError::Unrecognized));
+    }
+}
@@ -1,17 +1,43 @@
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
 use std::{
-    fs,
-    io::{stdin, stdout, Stdout},
+    env,
+    fs::{self, OpenOptions},
+    io::{self, stdin, stdout, Stdout, Write},
+    path::{Path, PathBuf},
+    process::{self, Command, Stdio},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 use crossterm::{
-    style::{Color, ResetColor, SetForegroundColor},
-    ExecutableCommand,
+    style::{Color, ResetColor, SetForegroundColor, Stylize},
+    terminal, ExecutableCommand,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    helpers::{
+        code_diff::{diff_lines, diff_stats, unified_diff},
+        source_files::{remove_source_files, write_source_files, SourceFiles},
+    },
+    models::{
+        agents::agent_traits::{FactSheet, ProjectScope, SecurityReport, Severity},
+        general::{
+            agent_error::AgentError,
+            approval::{Approval, ApprovalRequest},
+        },
+    },
 };
 
 pub const CODE_TEMPLATE_PATH: &str =
     "/home/vasilegorcinschi/repos/web_template_autogpt/src/code_template.rs";
 pub const WS_PROJECT_PATH: &str = "/home/vasilegorcinschi/repos/web_template_autogpt";
-pub const EXEC_MAIN_PATH: &str = "/home/vasilegorcinschi/repos/web_template_autogpt/src/main.rs";
+pub const FRONTEND_PROJECT_PATH: &str =
+    "/home/vasilegorcinschi/repos/web_template_autogpt/frontend";
+pub const MIGRATIONS_PATH: &str = "/home/vasilegorcinschi/repos/web_template_autogpt/migrations";
+const WS_TESTS_PATH: &str = "/home/vasilegorcinschi/repos/web_template_autogpt/tests";
+const WS_CARGO_TOML_PATH: &str = "/home/vasilegorcinschi/repos/web_template_autogpt/Cargo.toml";
+const OPENAPI_SPEC_PATH: &str = "/home/vasilegorcinschi/repos/web_template_autogpt/openapi.json";
 const API_SCHEMA_PATH: &str =
     "/home/vasilegorcinschi/repos/auto_gippity_udemy/schemas/api_schema.json";
 
@@ -32,82 +58,523 @@ impl PrintCommand {
             Self::Issue => Color::Red,
         };
 
-        stdout.execute(SetForegroundColor(Color::Green)).unwrap();
+        let _ = stdout.execute(SetForegroundColor(Color::Green));
         print!("Agent {}:", agent_pos);
-        stdout.execute(SetForegroundColor(statement_color)).unwrap();
+        let _ = stdout.execute(SetForegroundColor(statement_color));
         println!("{}", agent_statement);
-        stdout.execute(ResetColor).unwrap();
+        let _ = stdout.execute(ResetColor);
     }
 }
 
-pub fn get_user_response(question: &str) -> String {
+pub fn get_user_response(question: &str) -> Result<String, AgentError> {
     let mut stdout: Stdout = stdout();
 
     // Print the question in a specific color
-    stdout.execute(SetForegroundColor(Color::Blue)).unwrap();
+    let _ = stdout.execute(SetForegroundColor(Color::Blue));
     println!("\n{}", question);
 
     // Reset the color
-    stdout.execute(ResetColor).unwrap();
+    let _ = stdout.execute(ResetColor);
 
     // Read user input
     let mut user_response = String::new();
     stdin()
         .read_line(&mut user_response)
-        .expect("Failed to read response");
-    user_response.trim().to_string()
+        .map_err(AgentError::io("read response"))?;
+    Ok(user_response.trim().to_string())
 }
 
 // Get code template and provide it as a single string to ChatGPT
-pub fn read_template_contents(template_path: &str) -> String {
-    fs::read_to_string(template_path).expect("Error reading code template")
+pub fn read_template_contents(template_path: &str) -> Result<String, AgentError> {
+    fs::read_to_string(template_path).map_err(AgentError::io(format!("read {}", template_path)))
 }
 
-// Save new backend code
-pub fn save_backend_code(contents: &String) {
-    fs::write(EXEC_MAIN_PATH, contents).expect("Error writing backend code")
+// Save new backend code, every file inside the src directory of the generated project
+pub fn save_backend_code(files: &SourceFiles) -> Result<(), AgentError> {
+    write_source_files(Path::new(WS_PROJECT_PATH), files)
+}
+// Put an earlier version of the backend code back, without the files it didn't have yet
+pub fn restore_backend_code(
+    current: &SourceFiles,
+    earlier: &SourceFiles,
+) -> Result<(), AgentError> {
+    remove_source_files(Path::new(WS_PROJECT_PATH), &current.missing_from(earlier))?;
+    save_backend_code(earlier)
 }
 // Save JSON API Endpoint Schema
-pub fn save_api_endpoints(api_endpoints: &String) {
-    fs::write(API_SCHEMA_PATH, api_endpoints).expect("Failed to write API endpoints to file")
+pub fn save_api_endpoints(api_endpoints: &String) -> Result<(), AgentError> {
+    fs::write(API_SCHEMA_PATH, api_endpoints).map_err(AgentError::io("write API endpoints to file"))
+}
+// Save the static frontend as the index page of the frontend directory
+pub fn save_frontend_code(contents: &String) -> Result<(), AgentError> {
+    fs::create_dir_all(FRONTEND_PROJECT_PATH)
+        .map_err(AgentError::io("create frontend directory"))?;
+    fs::write(format!("{}/index.html", FRONTEND_PROJECT_PATH), contents)
+        .map_err(AgentError::io("write frontend code"))
+}
+// Save a SQL migration into the migrations directory of the generated project
+pub fn save_migration(file_name: &str, contents: &String) -> Result<(), AgentError> {
+    fs::create_dir_all(MIGRATIONS_PATH).map_err(AgentError::io("create migrations directory"))?;
+    fs::write(format!("{}/{}", MIGRATIONS_PATH, file_name), contents)
+        .map_err(AgentError::io("write migration"))
+}
+// Save an integration test file into the tests directory of the generated project
+pub fn save_workspace_test(file_name: &str, contents: &String) -> Result<(), AgentError> {
+    fs::create_dir_all(WS_TESTS_PATH).map_err(AgentError::io("create tests directory"))?;
+    fs::write(format!("{}/{}", WS_TESTS_PATH, file_name), contents)
+        .map_err(AgentError::io("write integration tests"))
+}
+// Add a dependency to the generated project, unless it already has it
+pub fn add_workspace_dependency(name: &str, version_spec: &str) -> Result<(), AgentError> {
+    add_to_workspace_manifest("dependencies", name, version_spec)
+}
+// Same for the crates only its tests use
+pub fn add_workspace_dev_dependency(name: &str, version_spec: &str) -> Result<(), AgentError> {
+    add_to_workspace_manifest("dev-dependencies", name, version_spec)
+}
+
+fn add_to_workspace_manifest(
+    section: &str,
+    name: &str,
+    version_spec: &str,
+) -> Result<(), AgentError> {
+    let cargo_toml = read_template_contents(WS_CARGO_TOML_PATH)?;
+    let updated_cargo_toml = with_dependency(&cargo_toml, section, name, version_spec);
+    if updated_cargo_toml != cargo_toml {
+        fs::write(WS_CARGO_TOML_PATH, updated_cargo_toml)
+            .map_err(AgentError::io("write Cargo.toml"))?;
+    }
+    Ok(())
+}
+// Save a file into the root of the generated project, e.g. its Dockerfile
+pub fn save_workspace_file(file_name: &str, contents: &String) -> Result<(), AgentError> {
+    fs::write(format!("{}/{}", WS_PROJECT_PATH, file_name), contents)
+        .map_err(AgentError::io(format!("write {}", file_name)))
+}
+// Whether an earlier agent wrote this file, relative to the root of the generated project
+pub fn workspace_file_exists(file_name: &str) -> bool {
+    Path::new(WS_PROJECT_PATH).join(file_name).is_file()
+}
+// Name of the binary that the generated project builds
+pub fn read_workspace_package_name() -> Result<String, AgentError> {
+    package_name(&read_template_contents(WS_CARGO_TOML_PATH)?).ok_or_else(|| {
+        AgentError::Config(
+            "The Cargo.toml of the generated project has no package name".to_string(),
+        )
+    })
+}
+// Save OpenAPI document next to the generated code, so that clients can be generated from it
+pub fn save_openapi_spec(openapi_spec: &String) -> Result<(), AgentError> {
+    fs::write(OPENAPI_SPEC_PATH, openapi_spec).map_err(AgentError::io("write OpenAPI spec to file"))
+}
+
+fn package_name(cargo_toml: &str) -> Option<String> {
+    let package_section = cargo_toml.split("[package]").nth(1)?;
+    package_section
+        .lines()
+        .take_while(|line| !line.trim_start().starts_with('['))
+        .find_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            (key.trim() == "name").then(|| value.trim().trim_matches('"').to_string())
+        })
+}
+
+fn with_dependency(cargo_toml: &str, section: &str, name: &str, version_spec: &str) -> String {
+    let header = format!("[{}]", section);
+    let dependency_line = format!("{} = {}", name, version_spec);
+    let Some(section_start) = cargo_toml.find(&header) else {
+        return format!(
+            "{}\n\n{}\n{}\n",
+            cargo_toml.trim_end(),
+            header,
+            dependency_line
+        );
+    };
+
+    let insert_at = section_start + header.len();
+    let section_end = cargo_toml[insert_at..]
+        .find("\n[")
+        .map_or(cargo_toml.len(), |end| insert_at + end);
+    let already_present = cargo_toml[insert_at..section_end].lines().any(|line| {
+        line.trim_start()
+            .strip_prefix(name)
+            .is_some_and(|rest| rest.trim_start().starts_with('='))
+    });
+    if already_present {
+        return cargo_toml.to_string();
+    }
+    format!(
+        "{}\n{}{}",
+        &cargo_toml[..insert_at],
+        dependency_line,
+        &cargo_toml[insert_at..]
+    )
 }
 
 // Our flow involves allowing AI to execute code on our machine
 // This can potentially harmful for any host running this
 // As a safety measure we will want to review any code before allowing
 // to execute it
-pub fn is_code_safe() -> bool {
+pub fn review_generated_code(request: &ApprovalRequest) -> Result<Approval, AgentError> {
     let mut stdout = stdout();
 
+    print_security_report(&mut stdout, request.security_report);
+    show_code_diff(
+        &mut stdout,
+        request.previous_code.unwrap_or_default(),
+        request.code,
+    );
+
     loop {
-        stdout.execute(SetForegroundColor(Color::Blue)).unwrap();
+        let _ = stdout.execute(SetForegroundColor(Color::Blue));
         println!("\nWARNING: You are about to run code written entirely by AI");
         println!("Review the code and confirm that you wish to continue");
 
-        stdout.execute(SetForegroundColor(Color::Green)).unwrap();
+        let _ = stdout.execute(SetForegroundColor(Color::Green));
         println!("[1] All good!");
 
-        stdout.execute(SetForegroundColor(Color::DarkRed)).unwrap();
+        let _ = stdout.execute(SetForegroundColor(Color::DarkRed));
         println!("[2] Let's stop this project!");
 
-        stdout.execute(ResetColor).unwrap();
+        let _ = stdout.execute(SetForegroundColor(Color::Yellow));
+        println!("[3] Edit the code in $EDITOR first");
+        println!("[4] Show the diff again");
+
+        let _ = stdout.execute(ResetColor);
 
         let mut human_response: String = String::new();
         stdin()
             .read_line(&mut human_response)
-            .expect("Failed to read human response!");
+            .map_err(AgentError::io("read human response"))?;
 
         human_response = human_response.trim().to_lowercase();
         match human_response.as_str() {
-            "1" | "ok" | "y" => return true,
-            "2" | "no" | "n" => return false,
+            "1" | "ok" | "y" => return Ok(Approval::Approved),
+            "2" | "no" | "n" => {
+                return Ok(Approval::Denied {
+                    reason: "AI generated code was deemed not safe at the terminal".to_string(),
+                })
+            }
+            "3" | "e" => {
+                let Some(code) = edit_in_editor(request.code, "backend_code.rs")? else {
+                    continue;
+                };
+                if code == request.code {
+                    return Ok(Approval::Approved);
+                }
+                show_code_diff(&mut stdout, request.code, &code);
+                return Ok(Approval::Edited { code });
+            }
+            "4" | "d" => show_code_diff(
+                &mut stdout,
+                request.previous_code.unwrap_or_default(),
+                request.code,
+            ),
+            _ => {
+                println!("Invalid input, please select '1', '2', '3' or '4'")
+            }
+        }
+    }
+}
+
+// Colored unified diff with its stats, through the pager when it doesn't fit the terminal
+fn show_code_diff(stdout: &mut Stdout, previous_code: &str, code: &str) {
+    let Some(diff) = diff_lines(previous_code, code) else {
+        println!(
+            "\nCode changes: too many to compare, {} lines approved and {} lines generated",
+            previous_code.lines().count(),
+            code.lines().count()
+        );
+        return;
+    };
+    let stats = diff_stats(&diff);
+    if stats.is_unchanged() {
+        println!("\nCode changes: none since the last approval");
+        return;
+    }
+
+    let colored_diff: String = unified_diff(&diff, "backend (approved)", "backend (generated)", 3)
+        .lines()
+        .map(|line| {
+            let styled = match line.chars().next() {
+                _ if line.starts_with("---") || line.starts_with("+++") => line.bold(),
+                Some('@') => line.cyan(),
+                Some('+') => line.green(),
+                Some('-') => line.red(),
+                _ => line.reset(),
+            };
+            format!("{}\n", styled)
+        })
+        .collect();
+
+    let terminal_rows = terminal::size().map_or(usize::MAX, |(_, rows)| rows as usize);
+    if colored_diff.lines().count() < terminal_rows || !show_in_pager(&colored_diff) {
+        print!("\n{}", colored_diff);
+    }
+    let _ = stdout.execute(SetForegroundColor(Color::Yellow));
+    println!("Code changes: {}", stats);
+    let _ = stdout.execute(ResetColor);
+}
+
+// $PAGER, or less keeping the colors. False when no pager could be started
+fn show_in_pager(text: &str) -> bool {
+    let Some(mut pager) = command_from_env("PAGER", "less -R") else {
+        return false;
+    };
+    let Ok(mut child) = pager.stdin(Stdio::piped()).spawn() else {
+        return false;
+    };
+    if let Some(mut pager_stdin) = child.stdin.take() {
+        // the pager closes its input when the user quits early
+        let _ = pager_stdin.write_all(text.as_bytes());
+    }
+    child.wait().is_ok()
+}
+
+// Let the user change the contents in $EDITOR, None when the editor didn't exit cleanly
+fn edit_in_editor(contents: &str, file_name: &str) -> Result<Option<String>, AgentError> {
+    let Some(mut command) = command_from_env("EDITOR", "vi") else {
+        return Ok(None);
+    };
+    let editor = command.get_program().to_string_lossy().to_string();
+    let file_path = create_editor_file(contents, file_name)?;
+
+    let status = command
+        .arg(&file_path)
+        .status()
+        .map_err(AgentError::io(format!("run {}", editor)));
+    let edited_code = match status {
+        Ok(status) if status.success() => fs::read_to_string(&file_path)
+            .map(Some)
+            .map_err(AgentError::io("read edited code")),
+        Ok(status) => {
+            println!(
+                "{} exited with {}, keeping the generated code",
+                editor, status
+            );
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    };
+    let _ = fs::remove_file(&file_path);
+    edited_code
+}
+
+// A file nobody else could have created or read first: create_new refuses existing files and
+// symlinks planted at the path, the time in the name keeps other runs from guessing it
+fn create_editor_file(contents: &str, file_name: &str) -> Result<PathBuf, AgentError> {
+    let mut attempts = 0;
+    loop {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.subsec_nanos());
+        let file_path = env::temp_dir().join(format!(
+            "auto_gippity_{}_{}_{}",
+            process::id(),
+            nanos,
+            file_name
+        ));
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+
+        match options.open(&file_path) {
+            Ok(mut file) => {
+                file.write_all(contents.as_bytes())
+                    .map_err(AgentError::io("write file for the editor"))?;
+                return Ok(file_path);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempts < 10 => attempts += 1,
+            Err(e) => return Err(AgentError::io("create file for the editor")(e)),
+        }
+    }
+}
+
+// The parts of the fact sheet that can be corrected at a checkpoint
+#[derive(Serialize, Deserialize)]
+struct EditableFactSheet {
+    project_description: String,
+    project_scope: Option<ProjectScope>,
+    external_urls: Vec<String>,
+}
+
+// Checkpoint between agents: the plan so far, which the user can fix before more tokens are spent on it
+pub fn review_fact_sheet(
+    finished_agents: &[String],
+    fact_sheet: &FactSheet,
+) -> Result<FactSheet, AgentError> {
+    let mut stdout = stdout();
+    let mut reviewed = fact_sheet.clone();
+
+    loop {
+        print_fact_sheet(&mut stdout, finished_agents, &reviewed);
+
+        let _ = stdout.execute(SetForegroundColor(Color::Green));
+        println!("[1] Carry on");
+
+        let _ = stdout.execute(SetForegroundColor(Color::Yellow));
+        println!("[2] Edit the project description");
+        println!("[3] Edit the project scope");
+        println!("[4] Edit the external URLs");
+        println!("[5] Edit all of it in $EDITOR");
+
+        let _ = stdout.execute(ResetColor);
+
+        let mut human_response: String = String::new();
+        stdin()
+            .read_line(&mut human_response)
+            .map_err(AgentError::io("read human response"))?;
+
+        match human_response.trim() {
+            "" | "1" => return Ok(reviewed),
+            "2" => {
+                let project_description = get_user_response(
+                    "New project description (leave empty to keep the current one):",
+                )?;
+                if !project_description.is_empty() {
+                    reviewed.project_description = project_description;
+                }
+            }
+            "3" => {
+                let project_scope = reviewed.project_scope.unwrap_or(ProjectScope {
+                    is_crud_required: false,
+                    is_user_login_and_logout: false,
+                    is_external_urls_required: false,
+                });
+                reviewed.project_scope = Some(ProjectScope {
+                    is_crud_required: ask_yes_no(
+                        "Is CRUD required?",
+                        project_scope.is_crud_required,
+                    )?,
+                    is_user_login_and_logout: ask_yes_no(
+                        "Do users log in and out?",
+                        project_scope.is_user_login_and_logout,
+                    )?,
+                    is_external_urls_required: ask_yes_no(
+                        "Are external URLs required?",
+                        project_scope.is_external_urls_required,
+                    )?,
+                });
+            }
+            "4" => {
+                let external_urls = get_user_response(
+                    "External URLs separated by spaces ('none' removes them all, empty keeps them):",
+                )?;
+                match external_urls.as_str() {
+                    "" => {}
+                    "none" => reviewed.external_urls = vec![],
+                    _ => {
+                        reviewed.external_urls =
+                            external_urls.split_whitespace().map(String::from).collect()
+                    }
+                }
+            }
+            "5" => {
+                let editable = EditableFactSheet {
+                    project_description: reviewed.project_description.clone(),
+                    project_scope: reviewed.project_scope,
+                    external_urls: reviewed.external_urls.clone(),
+                };
+                let contents = serde_json::to_string_pretty(&editable)
+                    .map_err(AgentError::encode("fact sheet"))?;
+                let Some(edited) = edit_in_editor(&contents, "fact_sheet.json")? else {
+                    continue;
+                };
+                match serde_json::from_str::<EditableFactSheet>(&edited) {
+                    Ok(edited) => {
+                        reviewed.project_description = edited.project_description;
+                        reviewed.project_scope = edited.project_scope;
+                        reviewed.external_urls = edited.external_urls;
+                    }
+                    Err(e) => {
+                        println!("Could not read the edited fact sheet, discarding it: {}", e)
+                    }
+                }
+            }
             _ => {
-                println!("Invalid input, please select '1' or '2'")
+                println!("Invalid input, please select '1' to '5'")
             }
         }
     }
 }
 
+fn print_fact_sheet(stdout: &mut Stdout, finished_agents: &[String], fact_sheet: &FactSheet) {
+    let _ = stdout.execute(SetForegroundColor(Color::Blue));
+    println!("\nFact sheet after {}:", finished_agents.join(", "));
+    let _ = stdout.execute(ResetColor);
+
+    println!("Description: {}", fact_sheet.project_description);
+    match fact_sheet.project_scope {
+        Some(project_scope) => println!(
+            "Scope: CRUD {}, user login {}, external URLs {}",
+            yes_no(project_scope.is_crud_required),
+            yes_no(project_scope.is_user_login_and_logout),
+            yes_no(project_scope.is_external_urls_required)
+        ),
+        None => println!("Scope: not decided yet"),
+    }
+    if fact_sheet.external_urls.is_empty() {
+        println!("External URLs: none");
+    } else {
+        println!("External URLs:");
+        for external_url in &fact_sheet.external_urls {
+            println!("  {}", external_url);
+        }
+    }
+}
+
+fn yes_no(answer: bool) -> &'static str {
+    if answer {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+// An empty answer keeps the current value
+fn ask_yes_no(question: &str, current: bool) -> Result<bool, AgentError> {
+    let hint = if current { "[Y/n]" } else { "[y/N]" };
+    let answer = get_user_response(&format!("{} {}", question, hint))?.to_lowercase();
+    Ok(match answer.as_str() {
+        "" => current,
+        answer => matches!(answer, "y" | "yes"),
+    })
+}
+
+// A program with its arguments from an environment variable like EDITOR="code --wait"
+fn command_from_env(variable: &str, default: &str) -> Option<Command> {
+    let program_line = env::var(variable).unwrap_or_else(|_| default.to_string());
+    let mut words = program_line.split_whitespace();
+    let mut command = Command::new(words.next()?);
+    command.args(words);
+    Some(command)
+}
+
+fn print_security_report(stdout: &mut Stdout, security_report: &SecurityReport) {
+    if security_report.findings.is_empty() {
+        let _ = stdout.execute(SetForegroundColor(Color::Green));
+        println!("\nSecurity review: no findings");
+        let _ = stdout.execute(ResetColor);
+        return;
+    }
+
+    println!(
+        "\nSecurity review: {} finding(s)",
+        security_report.findings.len()
+    );
+    for finding in &security_report.findings {
+        let color = match finding.severity {
+            Severity::Critical | Severity::High => Color::Red,
+            Severity::Medium => Color::Yellow,
+            Severity::Low => Color::Grey,
+        };
+        let _ = stdout.execute(SetForegroundColor(color));
+        println!("{}", finding);
+    }
+    let _ = stdout.execute(ResetColor);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -116,4 +583,46 @@ mod tests {
     fn test_printing_agent_message() {
         PrintCommand::AICall.print_agent_message("agent_pos", "agent_statement");
     }
+
+    #[test]
+    fn test_with_dependency() {
+        let cargo_toml = "[package]\nname = \"web\"\n\n[dependencies]\nserde = \"1\"\n";
+
+        let updated = with_dependency(cargo_toml, "dependencies", "rusqlite", "\"0.29\"");
+        assert_eq!(
+            updated,
+            "[package]\nname = \"web\"\n\n[dependencies]\nrusqlite = \"0.29\"\nserde = \"1\"\n"
+        );
+        assert_eq!(
+            with_dependency(&updated, "dependencies", "rusqlite", "\"0.30\""),
+            updated
+        );
+        assert_eq!(
+            with_dependency(&updated, "dependencies", "serde", "\"2\""),
+            updated
+        );
+
+        // a crate the code uses is still missing from the tests' section, and the other way round
+        let updated = with_dependency(&updated, "dev-dependencies", "serde", "\"1\"");
+        assert_eq!(
+            updated,
+            "[package]\nname = \"web\"\n\n[dependencies]\nrusqlite = \"0.29\"\nserde = \"1\"\n\n\
+             [dev-dependencies]\nserde = \"1\"\n"
+        );
+        let updated = with_dependency(&updated, "dev-dependencies", "tokio", "\"1\"");
+        assert!(updated.ends_with("[dev-dependencies]\ntokio = \"1\"\nserde = \"1\"\n"));
+        assert_eq!(
+            with_dependency(&updated, "dependencies", "tokio", "\"1\""),
+            updated.replace("[dependencies]\n", "[dependencies]\ntokio = \"1\"\n")
+        );
+    }
+
+    #[test]
+    fn test_package_name() {
+        let cargo_toml =
+            "[package]\nversion = \"0.1.0\"\nname = \"web\"\n\n[dependencies]\nname = \"1\"\n";
+
+        assert_eq!(package_name(cargo_toml), Some("web".to_string()));
+        assert_eq!(package_name("[dependencies]\nname = \"1\"\n"), None);
+    }
 }
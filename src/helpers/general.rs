@@ -1,9 +1,27 @@
-use reqwest::Client;
+use std::time::Duration;
+
+use reqwest::{header, Client, Method};
 use serde::de::DeserializeOwned;
+use tokio::time;
 
-use crate::{apis::call_requests::call_gpt, models::general::llm::Message};
+use crate::{
+    apis::call_requests::LlmProvider,
+    models::{
+        agents::agent_traits::RouteObject,
+        general::{
+            agent_error::AgentError,
+            api_route::{concrete_path, HttpMethod},
+            llm::Message,
+        },
+    },
+};
 
-use super::command_line::PrintCommand;
+use super::{
+    command_line::PrintCommand,
+    sandbox::Sandbox,
+    source_files::{SourceFiles, SourceFilesError},
+    workspace::{run_workspace_server, stop_workspace_server},
+};
 
 // encourage certain specific output
 pub fn extend_ai_function(ai_func: fn(&str) -> &'static str, func_input: &str) -> Message {
@@ -27,11 +45,12 @@ pub fn extend_ai_function(ai_func: fn(&str) -> &'static str, func_input: &str) -
 // Perform call to LLM GPT
 
 pub async fn ai_task_request(
+    provider: &dyn LlmProvider,
     msg_context: String,
     agent_position: &str,
     agent_operation: &str,
     function_passed: for<'a> fn(&'a str) -> &'static str,
-) -> String {
+) -> Result<String, AgentError> {
     // Extend AI function
     let extended_message = extend_ai_function(function_passed, &msg_context);
 
@@ -39,34 +58,82 @@ pub async fn ai_task_request(
     PrintCommand::AICall.print_agent_message(agent_position, agent_operation);
 
     // Attempt first call
-    match call_gpt(vec![extended_message.clone()]).await {
-        Ok(response) => response,
+    match provider.call(vec![extended_message.clone()]).await {
+        Ok(response) => Ok(response),
         Err(_) => {
             // Retry if the first call fails
-            call_gpt(vec![extended_message])
-                .await
-                .expect("Failed to call OpenAI twice")
+            Ok(provider.call(vec![extended_message]).await?)
         }
     }
 }
 
 pub async fn ai_task_request_decoded<T: DeserializeOwned>(
+    provider: &dyn LlmProvider,
     msg_context: String,
     agent_position: &str,
     agent_operation: &str,
     function_passed: for<'a> fn(&'a str) -> &'static str,
-) -> T {
+) -> Result<T, AgentError> {
     let llm_response = ai_task_request(
+        provider,
         msg_context,
         agent_position,
         agent_operation,
         function_passed,
     )
-    .await;
+    .await?;
 
-    let decoded_response: T = serde_json::from_str(llm_response.as_str())
-        .expect("Failed to decode AI response from serde_json");
-    decoded_response
+    serde_json::from_str(llm_response.as_str()).map_err(|source| AgentError::Decode {
+        what: "AI response",
+        source,
+    })
+}
+
+// The source files an LLM prints when given the `sent` ones. A response that names no file,
+// where it could be any of them, is asked for once more
+pub async fn ai_task_request_files(
+    provider: &dyn LlmProvider,
+    msg_context: String,
+    sent: &SourceFiles,
+    agent_position: &str,
+    agent_operation: &str,
+    function_passed: for<'a> fn(&'a str) -> &'static str,
+) -> Result<SourceFiles, AgentError> {
+    let llm_response = ai_task_request(
+        provider,
+        msg_context.clone(),
+        agent_position,
+        agent_operation,
+        function_passed,
+    )
+    .await?;
+
+    match SourceFiles::parse(&llm_response, sent) {
+        Err(SourceFilesError::Unmarked) => {
+            PrintCommand::Issue.print_agent_message(
+                agent_position,
+                "The response names no file, asking again...",
+            );
+            let llm_response = ai_task_request(
+                provider,
+                msg_context,
+                agent_position,
+                agent_operation,
+                function_passed,
+            )
+            .await?;
+            Ok(SourceFiles::parse(&llm_response, sent)?)
+        }
+        parsed => Ok(parsed?),
+    }
+}
+
+// Client for calling the generated server and external urls
+pub fn http_client(timeout: Duration) -> Result<Client, AgentError> {
+    Client::builder()
+        .timeout(timeout)
+        .build()
+        .map_err(|e| AgentError::Config(format!("Failed to build HTTP client: {}", e)))
 }
 
 // Check whether request URL is valid
@@ -75,10 +142,98 @@ pub async fn check_status_code(client: &Client, url: &str) -> Result<u16, reqwes
     Ok(response.status().as_u16())
 }
 
+// Same as check_status_code, but also keeps the response body for bug reports
+pub async fn fetch_status_and_body(
+    client: &Client,
+    url: &str,
+) -> Result<(u16, String), reqwest::Error> {
+    let response = client.get(url).send().await?;
+    let status_code = response.status().as_u16();
+    let body = response.text().await?;
+    Ok((status_code, body))
+}
+
+// Run the backend and report the routes that don't allow requests from `origin`,
+// the browser refuses those responses
+pub async fn find_cors_bugs(
+    sandbox: &Sandbox,
+    agent_position: &str,
+    routes: &[&RouteObject],
+    origin: &str,
+) -> Result<Vec<String>, AgentError> {
+    if routes.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let client = http_client(Duration::from_secs(5))?;
+    let backend_server = run_workspace_server(sandbox).await?;
+    PrintCommand::UnitTest
+        .print_agent_message(agent_position, "Checking CORS headers in 5 seconds...");
+    time::sleep(Duration::from_secs(5)).await;
+
+    let mut bugs: Vec<String> = vec![];
+    for route_object in routes {
+        let url = format!(
+            "http://localhost:8080{}",
+            concrete_path(&route_object.route)
+        );
+        match cors_allow_origin(&client, &url, route_object.method, origin).await {
+            Ok(allow_origin) if cors_allows(allow_origin.as_deref(), origin) => {}
+            Ok(allow_origin) => bugs.push(format!(
+                "- {} {}: Access-Control-Allow-Origin is {}",
+                route_object.method.as_ref().to_uppercase(),
+                route_object.route,
+                allow_origin.map_or("missing".to_string(), |value| format!("'{}'", value))
+            )),
+            Err(e) => bugs.push(format!(
+                "- {} {}: no response ({})",
+                route_object.method.as_ref().to_uppercase(),
+                route_object.route,
+                e
+            )),
+        }
+    }
+    stop_workspace_server(sandbox, backend_server).await?;
+
+    Ok(bugs)
+}
+
+// The Access-Control-Allow-Origin the backend answers a request from `origin` with: of the
+// request itself for GET, of its preflight for the methods a browser checks first
+async fn cors_allow_origin(
+    client: &Client,
+    url: &str,
+    method: HttpMethod,
+    origin: &str,
+) -> Result<Option<String>, reqwest::Error> {
+    let request = match method {
+        HttpMethod::Get => client.get(url),
+        method => client
+            .request(Method::OPTIONS, url)
+            .header(
+                header::ACCESS_CONTROL_REQUEST_METHOD,
+                method.as_ref().to_uppercase(),
+            )
+            .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "content-type"),
+    };
+    let response = request.header(header::ORIGIN, origin).send().await?;
+    Ok(response
+        .headers()
+        .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from))
+}
+
+fn cors_allows(allow_origin: Option<&str>, origin: &str) -> bool {
+    matches!(allow_origin, Some(allowed) if allowed == "*" || allowed == origin)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::ai_functions::aifunc_managing::convert_user_input_to_goal;
+    #[cfg(feature = "openai-coverage")]
+    use crate::apis::call_requests::OpenAiProvider;
 
     #[test]
     fn test_extending_ai_function() {
@@ -86,18 +241,29 @@ mod tests {
         assert_eq!(extended_msg.role, "system".to_string());
     }
 
+    #[test]
+    fn test_cors_allows() {
+        let origin = "http://127.0.0.1:40123";
+        assert!(cors_allows(Some("*"), origin));
+        assert!(cors_allows(Some(origin), origin));
+        assert!(!cors_allows(Some("http://localhost:8080"), origin));
+        assert!(!cors_allows(None, origin));
+    }
+
     #[tokio::test]
     #[cfg(feature = "openai-coverage")]
     async fn test_ai_task_request() {
         let ai_func_param = "Build me a webserver for making stock price api requests".to_string();
 
         let result = ai_task_request(
+            &OpenAiProvider::from_env(),
             ai_func_param,
             "Managing agent",
             "Defining user requirements",
             convert_user_input_to_goal,
         )
-        .await;
+        .await
+        .unwrap();
 
         assert!(result.len() > 20);
     }
@@ -1,2 +1,13 @@
+pub mod code_analysis;
+pub mod code_diff;
+pub mod code_patch;
 pub mod command_line;
 pub mod general;
+pub mod openapi;
+pub mod port_forward;
+pub mod sandbox;
+pub mod source_files;
+pub mod static_server;
+pub mod supervisor;
+pub mod workspace;
+pub mod workspace_history;
@@ -0,0 +1,274 @@
+use serde_json::{json, Map, Value};
+
+use crate::models::{
+    agents::agent_traits::{FactSheet, RouteObject},
+    general::{
+        agent_error::AgentError,
+        api_route::{parse_path_params, path_template, ScalarType, ShapeDescriptor},
+    },
+};
+
+use super::command_line::{save_api_endpoints, save_openapi_spec};
+
+const OPENAPI_VERSION: &str = "3.1.0";
+const GENERATED_SERVER_URL: &str = "http://localhost:8080";
+
+// Write the endpoints of the fact sheet to the workspace, as they are and as an OpenAPI document
+pub fn export_api_schema(fact_sheet: &FactSheet) -> Result<(), AgentError> {
+    save_api_endpoints(
+        &serde_json::to_string_pretty(&fact_sheet.api_endpoint_schema)
+            .map_err(AgentError::encode("API endpoints"))?,
+    )?;
+    save_openapi_spec(
+        &serde_json::to_string_pretty(&build_openapi_document(fact_sheet))
+            .map_err(AgentError::encode("OpenAPI spec"))?,
+    )
+}
+
+// Turn the endpoints stored in the fact sheet into an OpenAPI 3.1 document
+pub fn build_openapi_document(fact_sheet: &FactSheet) -> Value {
+    let mut paths = Map::new();
+
+    for route_object in &fact_sheet.api_endpoint_schema {
+        // path keys name their params the way the parameters below do, without actix regexes
+        let path_item = paths
+            .entry(path_template(&route_object.route))
+            .or_insert_with(|| Value::Object(Map::new()));
+        if let Value::Object(operations) = path_item {
+            operations.insert(
+                route_object.method.to_string(),
+                build_operation(route_object),
+            );
+        }
+    }
+
+    json!({
+        "openapi": OPENAPI_VERSION,
+        "info": {
+            "title": "Auto-Gippity generated web server",
+            "description": fact_sheet.project_description,
+            "version": "0.1.0"
+        },
+        "servers": [{ "url": GENERATED_SERVER_URL }],
+        "paths": paths
+    })
+}
+
+fn build_operation(route_object: &RouteObject) -> Value {
+    let mut operation = Map::new();
+    operation.insert(
+        "operationId".to_string(),
+        Value::String(operation_id(route_object)),
+    );
+
+    let parameters: Vec<Value> = parse_path_params(&route_object.route)
+        .iter()
+        .map(|name| {
+            json!({
+                "name": name,
+                "in": "path",
+                "required": true,
+                "schema": path_param_schema(route_object, name)
+            })
+        })
+        .collect();
+    if !parameters.is_empty() {
+        operation.insert("parameters".to_string(), Value::Array(parameters));
+    }
+
+    if route_object.request_body != ShapeDescriptor::None {
+        operation.insert(
+            "requestBody".to_string(),
+            json!({
+                "required": true,
+                "content": { "application/json": { "schema": shape_to_schema(&route_object.request_body) } }
+            }),
+        );
+    }
+
+    let success_response = match &route_object.response {
+        ShapeDescriptor::None => json!({ "description": "Successful response" }),
+        shape => json!({
+            "description": "Successful response",
+            "content": { "application/json": { "schema": shape_to_schema(shape) } }
+        }),
+    };
+    operation.insert("responses".to_string(), json!({ "200": success_response }));
+
+    Value::Object(operation)
+}
+
+// e.g. GET /item/{id} -> get_item_id
+fn operation_id(route_object: &RouteObject) -> String {
+    let mut operation_id = route_object.method.to_string();
+    for word in route_object
+        .route
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        operation_id.push('_');
+        operation_id.push_str(&word.to_lowercase());
+    }
+    operation_id
+}
+
+// A path parameter takes the type of the body field with the same name, if there is one
+fn path_param_schema(route_object: &RouteObject, param_name: &str) -> Value {
+    [&route_object.request_body, &route_object.response]
+        .iter()
+        .find_map(|shape| match shape {
+            ShapeDescriptor::Object(fields) => fields.get(param_name),
+            _ => None,
+        })
+        .filter(|field| matches!(field, ShapeDescriptor::Scalar(_)))
+        .map(shape_to_schema)
+        .unwrap_or_else(|| json!({ "type": "string" }))
+}
+
+fn shape_to_schema(shape: &ShapeDescriptor) -> Value {
+    match shape {
+        ShapeDescriptor::None => json!({ "type": "null" }),
+        // an empty schema accepts anything
+        ShapeDescriptor::NotProvided => json!({}),
+        ShapeDescriptor::Scalar(ScalarType::String) => json!({ "type": "string" }),
+        ShapeDescriptor::Scalar(ScalarType::Number) => json!({ "type": "number" }),
+        ShapeDescriptor::Scalar(ScalarType::Bool) => json!({ "type": "boolean" }),
+        ShapeDescriptor::Scalar(ScalarType::Other(type_name)) => {
+            json!({ "description": format!("Rust type `{}`", type_name) })
+        }
+        ShapeDescriptor::Array(item) => json!({ "type": "array", "items": shape_to_schema(item) }),
+        ShapeDescriptor::Object(fields) => {
+            let properties: Map<String, Value> = fields
+                .iter()
+                .map(|(name, field)| (name.clone(), shape_to_schema(field)))
+                .collect();
+            json!({
+                "type": "object",
+                "properties": properties,
+                "required": fields.keys().collect::<Vec<&String>>()
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dummy_fact_sheet() -> FactSheet {
+        let api_endpoint_schema: Vec<RouteObject> = serde_json::from_value(json!([
+            {
+                "route": "/item",
+                "is_route_dynamic": false,
+                "method": "post",
+                "request_body": { "id": "number", "name": "string", "completed": "bool" },
+                "response": "None"
+            },
+            {
+                "route": "/item/{id}",
+                "is_route_dynamic": true,
+                "method": "get",
+                "request_body": "None",
+                "response": { "id": "number", "name": "string", "completed": "bool" }
+            },
+            {
+                "route": "/item/{id}",
+                "is_route_dynamic": true,
+                "method": "delete",
+                "request_body": "None",
+                "response": "None"
+            },
+            {
+                "route": "/files/{tail:.*}",
+                "is_route_dynamic": true,
+                "method": "get",
+                "request_body": "None",
+                "response": "not_provided"
+            }
+        ]))
+        .unwrap();
+
+        FactSheet {
+            project_description: "build a website that tracks todo items".to_string(),
+            project_scope: None,
+            external_urls: vec![],
+            backend_code: None,
+            api_endpoint_schema,
+            frontend_code: None,
+            database_schema: None,
+            security_report: None,
+            code_review: None,
+            deployment_files: vec![],
+        }
+    }
+
+    #[test]
+    fn test_build_openapi_document() {
+        let document = build_openapi_document(&dummy_fact_sheet());
+
+        assert_eq!(document["openapi"], "3.1.0");
+
+        let create_item = &document["paths"]["/item"]["post"];
+        assert_eq!(create_item["operationId"], "post_item");
+        assert_eq!(
+            create_item["requestBody"]["content"]["application/json"]["schema"]["properties"]
+                ["completed"],
+            json!({ "type": "boolean" })
+        );
+        assert!(create_item["responses"]["200"].get("content").is_none());
+
+        let read_item = &document["paths"]["/item/{id}"]["get"];
+        assert_eq!(
+            read_item["parameters"],
+            json!([{ "name": "id", "in": "path", "required": true, "schema": { "type": "number" } }])
+        );
+        assert!(read_item.get("requestBody").is_none());
+        assert_eq!(
+            read_item["responses"]["200"]["content"]["application/json"]["schema"]["type"],
+            "object"
+        );
+
+        let delete_item = &document["paths"]["/item/{id}"]["delete"];
+        assert_eq!(
+            delete_item["parameters"][0]["schema"],
+            json!({ "type": "string" })
+        );
+    }
+
+    #[test]
+    fn test_openapi_document_is_valid() {
+        let document = build_openapi_document(&dummy_fact_sheet());
+
+        // the document parses as OpenAPI and reads the same when written back out
+        let parsed: openapiv3::OpenAPI = serde_json::from_value(document.clone()).unwrap();
+        let reparsed: openapiv3::OpenAPI =
+            serde_json::from_value(serde_json::to_value(&parsed).unwrap()).unwrap();
+        assert_eq!(reparsed, parsed);
+        assert_eq!(parsed.paths.paths.len(), 3);
+
+        // every template param of a path, read as OpenAPI does without actix regexes, is one of
+        // its path parameters, and the other way round
+        for (path, operations) in document["paths"].as_object().unwrap() {
+            let mut template_params: Vec<String> = path
+                .split('{')
+                .skip(1)
+                .map(|param| param.split('}').next().unwrap().to_string())
+                .collect();
+            template_params.sort();
+            for operation in operations.as_object().unwrap().values() {
+                let mut parameters: Vec<String> = operation["parameters"]
+                    .as_array()
+                    .map(|parameters| {
+                        parameters
+                            .iter()
+                            .map(|parameter| parameter["name"].as_str().unwrap().to_string())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                parameters.sort();
+                assert_eq!(parameters, template_params, "parameters of {}", path);
+            }
+        }
+        assert!(document["paths"].get("/files/{tail}").is_some());
+    }
+}
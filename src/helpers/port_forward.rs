@@ -0,0 +1,267 @@
+use std::{io, net::Ipv4Addr};
+
+use tokio::{
+    io::copy_bidirectional,
+    net::{TcpListener, TcpStream},
+    task::{self, JoinHandle},
+};
+
+// Listens on the host's loopback and passes every connection on to the same port in the network
+// namespace of a sandboxed process. The sandbox has no other network, this port is the only way in
+#[derive(Debug)]
+pub struct PortForward {
+    accept_loop: JoinHandle<()>,
+}
+
+impl PortForward {
+    // `sandbox_pid` is the process that was spawned, e.g. bwrap, the namespace may be a child's
+    pub async fn start(port: u16, sandbox_pid: u32) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port)).await?;
+        let accept_loop = tokio::spawn(async move {
+            while let Ok((mut incoming, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    // a server that isn't listening yet refuses the connection, as it would on the host
+                    let outgoing =
+                        task::spawn_blocking(move || connect_in_sandbox(sandbox_pid, port))
+                            .await
+                            .map_err(io::Error::other)
+                            .and_then(|connected| connected)
+                            .and_then(|outgoing| {
+                                outgoing.set_nonblocking(true)?;
+                                TcpStream::from_std(outgoing)
+                            });
+                    if let Ok(mut outgoing) = outgoing {
+                        let _ = copy_bidirectional(&mut incoming, &mut outgoing).await;
+                    }
+                });
+            }
+        });
+        Ok(Self { accept_loop })
+    }
+}
+
+impl Drop for PortForward {
+    fn drop(&mut self) {
+        self.accept_loop.abort();
+    }
+}
+
+// A TCP connection to the sandbox's own loopback. A socket stays in the network namespace it was
+// created in, so a short-lived helper enters the sandbox's namespaces, connects and hands it back
+#[cfg(target_os = "linux")]
+fn connect_in_sandbox(sandbox_pid: u32, port: u16) -> io::Result<std::net::TcpStream> {
+    use std::{
+        fs::File,
+        os::{
+            fd::{AsRawFd, FromRawFd, OwnedFd},
+            unix::{net::UnixStream, process::CommandExt},
+        },
+        process::{Command, Stdio},
+    };
+
+    let pid = namespaced_process(sandbox_pid)?;
+    // joining the network namespace needs the capabilities of the user namespace that owns it
+    let user_namespace = File::open(format!("/proc/{}/ns/user", pid))?;
+    let net_namespace = File::open(format!("/proc/{}/ns/net", pid))?;
+    let (receiver, sender) = UnixStream::pair()?;
+    let (user_fd, net_fd, sender_fd) = (
+        user_namespace.as_raw_fd(),
+        net_namespace.as_raw_fd(),
+        sender.as_raw_fd(),
+    );
+
+    let mut helper = Command::new("true");
+    helper
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    // SAFETY: the closure runs between fork and exec, in a child with a single thread as setns of
+    // a user namespace requires. It only makes syscalls, which are async-signal-safe, and doesn't
+    // allocate. A failure comes back as the error of spawning the helper
+    unsafe {
+        helper.pre_exec(move || {
+            if libc::setns(user_fd, libc::CLONE_NEWUSER) != 0
+                || libc::setns(net_fd, libc::CLONE_NEWNET) != 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+            let socket = libc::socket(libc::AF_INET, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0);
+            if socket < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let address = libc::sockaddr_in {
+                sin_family: libc::AF_INET as libc::sa_family_t,
+                sin_port: port.to_be(),
+                sin_addr: libc::in_addr {
+                    s_addr: u32::from(Ipv4Addr::LOCALHOST).to_be(),
+                },
+                sin_zero: [0; 8],
+            };
+            if libc::connect(
+                socket,
+                (&address as *const libc::sockaddr_in).cast(),
+                std::mem::size_of::<libc::sockaddr_in>() as libc::socklen_t,
+            ) != 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+            send_fd(sender_fd, socket)
+        });
+    }
+    helper.status()?;
+    drop(sender);
+
+    let fd = receive_fd(receiver.as_raw_fd())?;
+    // SAFETY: the descriptor was just received and nothing else owns it
+    Ok(std::net::TcpStream::from(unsafe {
+        OwnedFd::from_raw_fd(fd)
+    }))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn connect_in_sandbox(_sandbox_pid: u32, _port: u16) -> io::Result<std::net::TcpStream> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "sandboxed servers can only be reached on Linux",
+    ))
+}
+
+// The process itself or the first descendant that has a network namespace other than ours,
+// bwrap leaves the namespaces to the child it starts
+#[cfg(target_os = "linux")]
+fn namespaced_process(pid: u32) -> io::Result<u32> {
+    use std::fs;
+
+    let own_namespace = fs::read_link("/proc/self/ns/net")?;
+    let mut candidates = vec![pid];
+    while let Some(candidate) = candidates.pop() {
+        if fs::read_link(format!("/proc/{}/ns/net", candidate))? != own_namespace {
+            return Ok(candidate);
+        }
+        for entry in fs::read_dir("/proc")?.flatten() {
+            let Some(child) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.parse::<u32>().ok())
+            else {
+                continue;
+            };
+            // "<pid> (<command>) <state> <parent pid> ...", the command may hold spaces
+            let is_child = fs::read_to_string(entry.path().join("stat"))
+                .ok()
+                .and_then(|stat| {
+                    let (_, fields) = stat.rsplit_once(')')?;
+                    fields.split_whitespace().nth(1)?.parse::<u32>().ok()
+                })
+                == Some(candidate);
+            if is_child {
+                candidates.push(child);
+            }
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        "the sandbox has no network namespace of its own yet",
+    ))
+}
+
+// The control message buffers are u64s to keep the cmsghdr in them aligned
+#[cfg(target_os = "linux")]
+const CONTROL_WORDS: usize = 4;
+
+// Async-signal-safe, it runs between fork and exec
+#[cfg(target_os = "linux")]
+fn send_fd(channel: libc::c_int, fd: libc::c_int) -> io::Result<()> {
+    let mut byte = [0u8; 1];
+    let mut iov = libc::iovec {
+        iov_base: byte.as_mut_ptr().cast(),
+        iov_len: byte.len(),
+    };
+    let mut control = [0u64; CONTROL_WORDS];
+    // SAFETY: every pointer is to a buffer on this stack frame that outlives the sendmsg call,
+    // and the control buffer has room for one descriptor
+    unsafe {
+        let mut message: libc::msghdr = std::mem::zeroed();
+        message.msg_iov = &mut iov;
+        message.msg_iovlen = 1;
+        message.msg_control = control.as_mut_ptr().cast();
+        message.msg_controllen = libc::CMSG_SPACE(std::mem::size_of::<libc::c_int>() as u32) as _;
+        let header = libc::CMSG_FIRSTHDR(&message);
+        (*header).cmsg_level = libc::SOL_SOCKET;
+        (*header).cmsg_type = libc::SCM_RIGHTS;
+        (*header).cmsg_len = libc::CMSG_LEN(std::mem::size_of::<libc::c_int>() as u32) as _;
+        std::ptr::write_unaligned(libc::CMSG_DATA(header).cast::<libc::c_int>(), fd);
+        if libc::sendmsg(channel, &message, 0) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn receive_fd(channel: libc::c_int) -> io::Result<libc::c_int> {
+    let mut byte = [0u8; 1];
+    let mut iov = libc::iovec {
+        iov_base: byte.as_mut_ptr().cast(),
+        iov_len: byte.len(),
+    };
+    let mut control = [0u64; CONTROL_WORDS];
+    // SAFETY: as in send_fd, and the descriptor is only read when the kernel wrote a header for it
+    unsafe {
+        let mut message: libc::msghdr = std::mem::zeroed();
+        message.msg_iov = &mut iov;
+        message.msg_iovlen = 1;
+        message.msg_control = control.as_mut_ptr().cast();
+        message.msg_controllen = std::mem::size_of_val(&control) as _;
+        if libc::recvmsg(channel, &mut message, libc::MSG_CMSG_CLOEXEC) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let header = libc::CMSG_FIRSTHDR(&message);
+        if header.is_null()
+            || (*header).cmsg_level != libc::SOL_SOCKET
+            || (*header).cmsg_type != libc::SCM_RIGHTS
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the helper didn't hand over a connection",
+            ));
+        }
+        Ok(std::ptr::read_unaligned(
+            libc::CMSG_DATA(header).cast::<libc::c_int>(),
+        ))
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::{
+        process::{Command, Stdio},
+        thread,
+        time::Duration,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_connect_in_sandbox() {
+        // a network namespace of its own, with its loopback interface still down
+        let Ok(mut sandbox) = Command::new("unshare")
+            .args(["--user", "--map-root-user", "--net", "sleep", "10"])
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            return;
+        };
+        thread::sleep(Duration::from_millis(200));
+        if matches!(sandbox.try_wait(), Ok(Some(_))) {
+            // namespaces are not available here
+            return;
+        }
+
+        // the host would refuse the connection, only the sandbox has no route to 127.0.0.1
+        let error = connect_in_sandbox(sandbox.id(), 1).unwrap_err();
+        let _ = sandbox.kill();
+        let _ = sandbox.wait();
+        assert_eq!(error.kind(), io::ErrorKind::NetworkUnreachable);
+    }
+}
@@ -0,0 +1,382 @@
+use std::{
+    env, fs,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+};
+
+use crate::models::general::agent_error::AgentError;
+
+use super::{
+    command_line::WS_PROJECT_PATH,
+    supervisor::{self, ResourceLimits},
+};
+
+// on the sandbox's own /tmp
+const SANDBOX_CARGO_HOME: &str = "/tmp/cargo";
+// what the sandbox sees of the host besides the toolchain and the workspace, all read-only.
+// /etc has the dynamic linker's cache, and the resolver and certificates for fetching
+const SYSTEM_PATHS: [&str; 6] = ["/usr", "/bin", "/sbin", "/lib", "/lib64", "/etc"];
+
+// How the generated project is kept away from the host
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Isolation {
+    // bubblewrap, fails when it isn't installed or can't create namespaces
+    Auto,
+    // run directly on the host, resource limits still apply
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Isolator {
+    // read-only system and toolchain, writable workspace, private /tmp, cargo home, network and
+    // environment
+    Bubblewrap,
+    Host,
+}
+
+// Only fetching the dependencies gets a network, and a cargo home it can write to
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Network {
+    Offline,
+    Fetch,
+}
+
+// Where the host's cargo comes from, bound read-only into the sandbox
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Toolchain {
+    // the directory of the cargo on the PATH, e.g. ~/.cargo/bin with rustup's proxies
+    cargo_bin: Option<PathBuf>,
+    rustup_home: Option<PathBuf>,
+    // set by rustup when the agents themselves run through cargo
+    rustup_toolchain: Option<String>,
+}
+
+impl Toolchain {
+    fn detect() -> Self {
+        let cargo_bin = env::var_os("PATH").and_then(|path| {
+            env::split_paths(&path).find(|directory| directory.join("cargo").is_file())
+        });
+        let rustup_home = env::var_os("RUSTUP_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".rustup")))
+            .filter(|rustup_home| rustup_home.is_dir());
+        Self {
+            cargo_bin,
+            rustup_home,
+            rustup_toolchain: env::var("RUSTUP_TOOLCHAIN").ok(),
+        }
+    }
+}
+
+// Runs the cargo commands on AI generated code without network and without access to the host
+// filesystem, the web server is reached through a forwarded port. The limits are for the supervisor
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sandbox {
+    pub isolation: Isolation,
+    pub limits: ResourceLimits,
+}
+
+impl Default for Sandbox {
+    fn default() -> Self {
+        Self {
+            isolation: Isolation::Auto,
+            limits: ResourceLimits::default(),
+        }
+    }
+}
+
+impl Sandbox {
+    // Resource limits without namespaces, for hosts that can't create them
+    pub fn unisolated() -> Self {
+        Self {
+            isolation: Isolation::None,
+            ..Self::default()
+        }
+    }
+
+    // `cargo <args>` in the generated project, wrapped in the sandbox
+    pub fn cargo(&self, args: &[&str]) -> Result<Command, AgentError> {
+        let isolator = match self.isolation {
+            Isolation::None => Isolator::Host,
+            Isolation::Auto => detect_isolator().ok_or_else(|| {
+                AgentError::Sandbox(
+                    "bubblewrap can't create namespaces on this host, \
+                     install bubblewrap or opt out of isolation"
+                        .to_string(),
+                )
+            })?,
+        };
+        let mut cargo_args: Vec<&str> = args.to_vec();
+        if isolator != Isolator::Host {
+            self.fetch_workspace_dependencies(isolator)?;
+            cargo_args.push("--offline");
+        }
+        Ok(cargo_command(isolator, Network::Offline, &cargo_args))
+    }
+
+    // Why a sandboxed process failed, when it was the sandbox rather than the code
+    pub fn violation(&self, output: &Output) -> Option<String> {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let is_sandbox_error = stderr.starts_with("bwrap:");
+
+        (!output.status.success() && is_sandbox_error).then(|| {
+            format!(
+                "could not start the sandbox: {}",
+                stderr.lines().next().unwrap_or_default()
+            )
+        })
+    }
+
+    // The one sandboxed step with a network, the others then build offline. A hanging registry or
+    // git server is stopped by the same limits as the build
+    fn fetch_workspace_dependencies(&self, isolator: Isolator) -> Result<(), AgentError> {
+        fs::create_dir_all(cargo_cache()).map_err(AgentError::io("create the cargo cache"))?;
+        let mut command = cargo_command(isolator, Network::Fetch, &["fetch"]);
+        let supervised = supervisor::run(&mut command, self.limits)
+            .map_err(AgentError::io("fetch backend dependencies"))?;
+        if let Some(limit) = supervised.limit {
+            return Err(AgentError::LimitExceeded {
+                step: "dependency fetch",
+                limit,
+            });
+        }
+        if let Some(violation) = self.violation(&supervised.output) {
+            return Err(AgentError::Sandbox(violation));
+        }
+        if supervised.output.status.success() {
+            Ok(())
+        } else {
+            Err(AgentError::Build(
+                String::from_utf8_lossy(&supervised.output.stderr).to_string(),
+            ))
+        }
+    }
+}
+
+fn cargo_command(isolator: Isolator, network: Network, cargo_args: &[&str]) -> Command {
+    let (program, wrapper_args) = wrap(
+        isolator,
+        network,
+        &Toolchain::detect(),
+        &cargo_cache(),
+        cargo_args,
+    );
+    let mut command = Command::new(program);
+    command.args(wrapper_args).current_dir(WS_PROJECT_PATH);
+    command
+}
+
+// Program and arguments running `cargo <cargo_args>` through the isolator
+fn wrap(
+    isolator: Isolator,
+    network: Network,
+    toolchain: &Toolchain,
+    cargo_cache: &str,
+    cargo_args: &[&str],
+) -> (String, Vec<String>) {
+    let mut args: Vec<String> = vec![];
+    let program = match isolator {
+        Isolator::Bubblewrap => {
+            args.extend(
+                [
+                    "--die-with-parent",
+                    "--new-session",
+                    "--unshare-user",
+                    "--unshare-pid",
+                    "--unshare-ipc",
+                    "--unshare-uts",
+                ]
+                .map(String::from),
+            );
+            if network == Network::Offline {
+                args.push("--unshare-net".to_string());
+            }
+            for system_path in SYSTEM_PATHS {
+                args.extend(["--ro-bind-try", system_path, system_path].map(String::from));
+            }
+            // the rest of the host, e.g. ~/.ssh or the agents' .env, stays out of reach
+            for toolchain_path in [&toolchain.cargo_bin, &toolchain.rustup_home]
+                .into_iter()
+                .flatten()
+            {
+                let toolchain_path = toolchain_path.display().to_string();
+                args.extend([
+                    "--ro-bind".to_string(),
+                    toolchain_path.clone(),
+                    toolchain_path,
+                ]);
+            }
+            args.extend(
+                [
+                    "--dev",
+                    "/dev",
+                    "--proc",
+                    "/proc",
+                    "--tmpfs",
+                    "/tmp",
+                    "--bind",
+                    WS_PROJECT_PATH,
+                    WS_PROJECT_PATH,
+                ]
+                .map(String::from),
+            );
+            // the crates are downloaded to a cargo home of the sandbox's own, not the host's. Fetching
+            // runs generated code too, e.g. a rustc wrapper in .cargo/config.toml, and whatever it
+            // leaves in there is only ever used by the sandbox
+            let cargo_cache_bind = match network {
+                Network::Offline => "--ro-bind",
+                Network::Fetch => "--bind",
+            };
+            args.extend([cargo_cache_bind, cargo_cache, SANDBOX_CARGO_HOME].map(String::from));
+            // nothing of the agents' environment, e.g. the OpenAI key, gets to the generated code
+            let path = toolchain
+                .cargo_bin
+                .iter()
+                .map(|cargo_bin| cargo_bin.display().to_string())
+                .chain(["/usr/local/bin", "/usr/bin", "/bin"].map(String::from))
+                .collect::<Vec<String>>()
+                .join(":");
+            args.extend(["--clearenv", "--setenv", "PATH", &path].map(String::from));
+            args.extend(["--setenv", "HOME", "/tmp"].map(String::from));
+            if let Some(rustup_home) = &toolchain.rustup_home {
+                args.extend([
+                    "--setenv".to_string(),
+                    "RUSTUP_HOME".to_string(),
+                    rustup_home.display().to_string(),
+                ]);
+            }
+            if let Some(rustup_toolchain) = &toolchain.rustup_toolchain {
+                args.extend(["--setenv", "RUSTUP_TOOLCHAIN", rustup_toolchain].map(String::from));
+            }
+            args.extend(
+                [
+                    "--setenv",
+                    "CARGO_HOME",
+                    SANDBOX_CARGO_HOME,
+                    "--chdir",
+                    WS_PROJECT_PATH,
+                    "--",
+                    "cargo",
+                ]
+                .map(String::from),
+            );
+            "bwrap"
+        }
+        Isolator::Host => "cargo",
+    };
+    args.extend(cargo_args.iter().map(|arg| arg.to_string()));
+    (program.to_string(), args)
+}
+
+// The first isolator that manages to create namespaces here
+fn detect_isolator() -> Option<Isolator> {
+    let works = |program: &str, args: &[&str]| {
+        Command::new(program)
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    };
+
+    works(
+        "bwrap",
+        &["--unshare-user", "--ro-bind", "/", "/", "--", "true"],
+    )
+    .then_some(Isolator::Bubblewrap)
+}
+
+// Next to the workspace, e.g. web_template_autogpt.cargo, and kept between runs
+fn cargo_cache() -> String {
+    format!("{}.cargo", WS_PROJECT_PATH)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_cargo_command() {
+        let toolchain = Toolchain {
+            cargo_bin: Some(PathBuf::from("/home/user/.cargo/bin")),
+            rustup_home: Some(PathBuf::from("/home/user/.rustup")),
+            rustup_toolchain: None,
+        };
+        let cargo_cache = "/home/user/web_template_autogpt.cargo";
+
+        let (program, args) = wrap(
+            Isolator::Bubblewrap,
+            Network::Offline,
+            &toolchain,
+            cargo_cache,
+            &["run", "--offline"],
+        );
+        assert_eq!(program, "bwrap");
+        let args = args.join(" ");
+        assert!(args.contains("--unshare-net"));
+        // the toolchain, not the home directory around it
+        assert!(!args.contains("--ro-bind / /"));
+        assert!(!args.contains("/home/user "));
+        assert!(args.contains(
+            "--ro-bind-try /etc /etc \
+             --ro-bind /home/user/.cargo/bin /home/user/.cargo/bin \
+             --ro-bind /home/user/.rustup /home/user/.rustup "
+        ));
+        assert!(args.ends_with(
+            "--ro-bind /home/user/web_template_autogpt.cargo /tmp/cargo \
+             --clearenv --setenv PATH /home/user/.cargo/bin:/usr/local/bin:/usr/bin:/bin \
+             --setenv HOME /tmp --setenv RUSTUP_HOME /home/user/.rustup \
+             --setenv CARGO_HOME /tmp/cargo \
+             --chdir /home/vasilegorcinschi/repos/web_template_autogpt -- cargo run --offline"
+        ));
+
+        // fetching gets the network, and writes to the cargo cache
+        let (_, args) = wrap(
+            Isolator::Bubblewrap,
+            Network::Fetch,
+            &toolchain,
+            cargo_cache,
+            &["fetch"],
+        );
+        let args = args.join(" ");
+        assert!(!args.contains("--unshare-net"));
+        assert!(args.contains(" --bind /home/user/web_template_autogpt.cargo /tmp/cargo "));
+
+        let (program, args) = wrap(
+            Isolator::Host,
+            Network::Offline,
+            &toolchain,
+            cargo_cache,
+            &["test"],
+        );
+        assert_eq!(
+            (program.as_str(), args),
+            ("cargo", vec!["test".to_string()])
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_violation() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let sandbox = Sandbox::default();
+        let failed = |stderr: &str| Output {
+            status: ExitStatusExt::from_raw(101 << 8),
+            stdout: vec![],
+            stderr: stderr.as_bytes().to_vec(),
+        };
+
+        assert_eq!(
+            sandbox.violation(&failed("bwrap: No permissions to creating new namespace")),
+            Some(
+                "could not start the sandbox: bwrap: No permissions to creating new namespace"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            sandbox.violation(&failed("error[E0425]: cannot find value `x`")),
+            None
+        );
+    }
+}
@@ -0,0 +1,437 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::models::general::agent_error::AgentError;
+
+use super::code_patch::{apply_patch, PatchError};
+
+// The one file every generated project has, a response without file markers can only be all of it
+pub const MAIN_FILE: &str = "src/main.rs";
+// Starts every file of a response, e.g. "// FILE: src/routes.rs"
+const FILE_MARKER: &str = "// FILE:";
+
+#[derive(Error, Debug, PartialEq)]
+pub enum SourceFilesError {
+    #[error("'{path}' is not a Rust file inside src/: {reason}")]
+    UnsafePath { path: String, reason: &'static str },
+    #[error("'{0}' is printed more than once")]
+    Duplicate(String),
+    #[error("the edits of '{path}' don't apply: {source}")]
+    Patch { path: String, source: PatchError },
+    #[error("the response names no file, it could be any of the files it was given")]
+    Unmarked,
+}
+
+// The generated backend: contents by path relative to the project root, e.g. "src/routes.rs"
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(from = "RawSourceFiles")]
+pub struct SourceFiles(BTreeMap<String, String>);
+
+// Fact sheets from before the backend was split into files hold main.rs as a plain string
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawSourceFiles {
+    Main(String),
+    Files(BTreeMap<String, String>),
+}
+
+impl From<RawSourceFiles> for SourceFiles {
+    fn from(raw: RawSourceFiles) -> Self {
+        match raw {
+            RawSourceFiles::Main(code) => Self::from_main(code),
+            RawSourceFiles::Files(files) => Self(files),
+        }
+    }
+}
+
+impl SourceFiles {
+    pub fn from_main(code: impl Into<String>) -> Self {
+        Self(BTreeMap::from([(MAIN_FILE.to_string(), code.into())]))
+    }
+
+    // The files an LLM printed when given the `sent` ones, every path is checked before anything
+    // can be written
+    pub fn parse(response: &str, sent: &SourceFiles) -> Result<Self, SourceFilesError> {
+        let mut files: BTreeMap<String, String> = BTreeMap::new();
+        for (path, contents) in split_files(response, sent)? {
+            check_path(&path)?;
+            if files.contains_key(&path) {
+                return Err(SourceFilesError::Duplicate(path));
+            }
+            files.insert(path, contents);
+        }
+        Ok(Self(files))
+    }
+
+    // These files with the edits an LLM printed for some of them, see `apply_patch`.
+    // A file the edits name that doesn't exist yet starts out empty
+    pub fn patched(&self, response: &str, sent: &SourceFiles) -> Result<Self, SourceFilesError> {
+        let mut patched = self.clone();
+        for (path, patch) in split_files(response, sent)? {
+            check_path(&path)?;
+            let code = patched.get(&path).unwrap_or_default();
+            let code = apply_patch(code, &patch).map_err(|source| SourceFilesError::Patch {
+                path: path.clone(),
+                source,
+            })?;
+            patched.0.insert(path, code);
+        }
+        Ok(patched)
+    }
+
+    // Add the files of the other set, replacing the ones with the same path
+    pub fn merge(&mut self, other: SourceFiles) {
+        self.0.extend(other.0);
+    }
+
+    // Only the files that errors point to, like "--> src/routes.rs:12:5", all of them when none is named
+    pub fn referenced_by(&self, errors: &str) -> Self {
+        let referenced: BTreeMap<String, String> = self
+            .0
+            .iter()
+            .filter(|(path, _)| errors.contains(&format!("{}:", path)))
+            .map(|(path, contents)| (path.clone(), contents.clone()))
+            .collect();
+        if referenced.is_empty() {
+            self.clone()
+        } else {
+            Self(referenced)
+        }
+    }
+
+    // All files in the response format, the way the LLM and the person approving them read the code
+    pub fn render(&self) -> String {
+        self.0
+            .iter()
+            .map(|(path, contents)| {
+                let newline = if contents.ends_with('\n') { "" } else { "\n" };
+                format!("{} {}\n{}{}", FILE_MARKER, path, contents, newline)
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    // Paths of the files the other set doesn't have
+    pub fn missing_from(&self, other: &SourceFiles) -> Vec<&str> {
+        self.0
+            .keys()
+            .filter(|path| !other.0.contains_key(*path))
+            .map(String::as_str)
+            .collect()
+    }
+
+    pub fn get(&self, path: &str) -> Option<&str> {
+        self.0.get(path).map(String::as_str)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .iter()
+            .map(|(path, contents)| (path.as_str(), contents.as_str()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+// Generated files stay Rust files inside src/, whatever path the LLM printed
+pub fn check_path(path: &str) -> Result<(), SourceFilesError> {
+    let unsafe_path = |reason| {
+        Err(SourceFilesError::UnsafePath {
+            path: path.to_string(),
+            reason,
+        })
+    };
+
+    if !path
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "_-./".contains(c))
+    {
+        return unsafe_path("only letters, digits, '_', '-', '.' and '/' are allowed");
+    }
+    let segments: Vec<&str> = path.split('/').collect();
+    if segments
+        .iter()
+        .any(|segment| segment.is_empty() || *segment == "." || *segment == "..")
+    {
+        return unsafe_path("it must be relative, without '.' or '..'");
+    }
+    if segments.len() < 2 || segments[0] != "src" {
+        return unsafe_path("it must be inside src/");
+    }
+    if !path.ends_with(".rs") {
+        return unsafe_path("it must end in .rs");
+    }
+    Ok(())
+}
+
+// Write the files into the project at `root`. Symlinks left in src/ could point anywhere,
+// so every file has to resolve to a place inside of it
+pub fn write_source_files(root: &Path, files: &SourceFiles) -> Result<(), AgentError> {
+    let source_dir = source_dir(root)?;
+    for (path, contents) in files.iter() {
+        let target = checked_target(root, &source_dir, path)?;
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(AgentError::io(format!("create directory of {}", path)))?;
+        }
+        fs::write(&target, contents).map_err(AgentError::io(format!("write {}", path)))?;
+    }
+    Ok(())
+}
+
+// Remove files from the project at `root`, e.g. modules an earlier version of the code didn't have
+pub fn remove_source_files(root: &Path, paths: &[&str]) -> Result<(), AgentError> {
+    let source_dir = source_dir(root)?;
+    for path in paths {
+        let target = checked_target(root, &source_dir, path)?;
+        match fs::remove_file(&target) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(AgentError::io(format!("remove {}", path))(e))
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn source_dir(root: &Path) -> Result<PathBuf, AgentError> {
+    let source_dir = root.join("src");
+    fs::create_dir_all(&source_dir).map_err(AgentError::io("create src directory"))?;
+    fs::canonicalize(&source_dir).map_err(AgentError::io("resolve src directory"))
+}
+
+// Where a checked path ends up, as long as that is inside of `source_dir`
+fn checked_target(root: &Path, source_dir: &Path, path: &str) -> Result<PathBuf, AgentError> {
+    check_path(path)?;
+    let target = root.join(path);
+
+    // the directories that already exist decide where new ones end up
+    let is_inside = target
+        .ancestors()
+        .skip(1)
+        .find(|ancestor| ancestor.exists())
+        .and_then(|ancestor| fs::canonicalize(ancestor).ok())
+        .is_some_and(|ancestor| ancestor.starts_with(source_dir));
+    let is_symlink =
+        fs::symlink_metadata(&target).is_ok_and(|metadata| metadata.file_type().is_symlink());
+    if !is_inside || is_symlink {
+        return Err(SourceFilesError::UnsafePath {
+            path: path.to_string(),
+            reason: "it resolves to a place outside of src/",
+        }
+        .into());
+    }
+    Ok(target)
+}
+
+// (path, contents) of every file in a response, commentary before the first file is dropped
+// Without any marker it's main.rs, as long as main.rs is all the LLM was sent. Otherwise it may
+// well be another file reprinted without its marker, and taking it for main.rs would overwrite it
+fn split_files(
+    response: &str,
+    sent: &SourceFiles,
+) -> Result<Vec<(String, String)>, SourceFilesError> {
+    let is_marker = |line: &str| line.trim_start().starts_with(FILE_MARKER);
+    if !response.lines().any(is_marker) {
+        let is_main_only = sent.0.len() == 1 && sent.0.contains_key(MAIN_FILE);
+        if !is_main_only {
+            return Err(SourceFilesError::Unmarked);
+        }
+        return Ok(vec![(MAIN_FILE.to_string(), response.to_string())]);
+    }
+
+    let mut files: Vec<(String, Vec<&str>)> = vec![];
+    for line in response.lines() {
+        match line.trim_start().strip_prefix(FILE_MARKER) {
+            Some(path) => files.push((path.trim().to_string(), vec![])),
+            None => {
+                if let Some((_, lines)) = files.last_mut() {
+                    lines.push(line);
+                }
+            }
+        }
+    }
+    Ok(files
+        .into_iter()
+        .map(|(path, lines)| (path, file_contents(&lines)))
+        .collect())
+}
+
+// Code fences and blank lines around a file aren't part of it
+fn file_contents(lines: &[&str]) -> String {
+    let is_padding = |line: &&str| {
+        let line = line.trim();
+        line.is_empty() || line.starts_with("```")
+    };
+    let start = lines
+        .iter()
+        .position(|line| !is_padding(line))
+        .unwrap_or(lines.len());
+    let end = lines
+        .iter()
+        .rposition(|line| !is_padding(line))
+        .map_or(start, |index| index + 1);
+
+    lines[start..end]
+        .iter()
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESPONSE: &str = "Here is the project:\n\
+                            ```rust\n\
+                            // FILE: src/main.rs\n\
+                            mod routes;\n\
+                            \n\
+                            fn main() {}\n\
+                            ```\n\
+                            \n\
+                            ```rust\n\
+                            // FILE: src/routes.rs\n\
+                            pub async fn health() -> &'static str {\n    \
+                                \"ok\"\n\
+                            }\n\
+                            ```\n";
+
+    #[test]
+    fn test_parse_and_render() {
+        let files = SourceFiles::parse(RESPONSE, &SourceFiles::default()).unwrap();
+
+        assert_eq!(files.get(MAIN_FILE), Some("mod routes;\n\nfn main() {}\n"));
+        assert_eq!(
+            files.get("src/routes.rs"),
+            Some("pub async fn health() -> &'static str {\n    \"ok\"\n}\n")
+        );
+        assert_eq!(SourceFiles::parse(&files.render(), &files).unwrap(), files);
+
+        // a single file response is main.rs, when that is what the LLM was given
+        assert_eq!(
+            SourceFiles::parse("fn main() {}", &SourceFiles::from_main("")).unwrap(),
+            SourceFiles::from_main("fn main() {}")
+        );
+        // otherwise it could be routes.rs just as well
+        assert_eq!(
+            SourceFiles::parse("pub fn health() {}", &files),
+            Err(SourceFilesError::Unmarked)
+        );
+        assert_eq!(
+            files.patched("@@ -1,1 +1,1 @@\n-mod routes;\n+mod api;\n", &files),
+            Err(SourceFilesError::Unmarked)
+        );
+        // and so are fact sheets from before the split
+        let files: SourceFiles = serde_json::from_str("\"fn main() {}\"").unwrap();
+        assert_eq!(files, SourceFiles::from_main("fn main() {}"));
+    }
+
+    #[test]
+    fn test_unsafe_paths() {
+        for path in [
+            "../src/main.rs",
+            "src/../../.bashrc.rs",
+            "/etc/cron.d/job.rs",
+            "src/./main.rs",
+            "main.rs",
+            "src/build.sh",
+            "src\\..\\main.rs",
+        ] {
+            let response = format!("// FILE: {}\nfn main() {{}}\n", path);
+            assert!(
+                matches!(
+                    SourceFiles::parse(&response, &SourceFiles::default()),
+                    Err(SourceFilesError::UnsafePath { .. })
+                ),
+                "{} was accepted",
+                path
+            );
+        }
+        assert!(check_path("src/handlers/items.rs").is_ok());
+
+        let duplicate = "// FILE: src/main.rs\nfn main() {}\n// FILE: src/main.rs\n";
+        assert_eq!(
+            SourceFiles::parse(duplicate, &SourceFiles::default()),
+            Err(SourceFilesError::Duplicate(MAIN_FILE.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_patched_and_referenced_files() {
+        let files = SourceFiles::parse(RESPONSE, &SourceFiles::default()).unwrap();
+        let edits = "// FILE: src/routes.rs\n\
+                     <<<<<<< SEARCH\n    \
+                         \"ok\"\n\
+                     =======\n    \
+                         \"healthy\"\n\
+                     >>>>>>> REPLACE\n\
+                     // FILE: src/state.rs\n\
+                     <<<<<<< SEARCH\n\
+                     =======\n\
+                     pub struct AppState;\n\
+                     >>>>>>> REPLACE\n";
+
+        let patched = files.patched(edits, &files).unwrap();
+        assert!(patched
+            .get("src/routes.rs")
+            .unwrap()
+            .contains("\"healthy\""));
+        assert_eq!(patched.get("src/state.rs"), Some("pub struct AppState;\n"));
+        assert_eq!(patched.get(MAIN_FILE), files.get(MAIN_FILE));
+
+        let errors = "error[E0308]: mismatched types\n  --> src/routes.rs:2:5\n";
+        let referenced = patched.referenced_by(errors);
+        assert_eq!(
+            referenced
+                .iter()
+                .map(|(path, _)| path)
+                .collect::<Vec<&str>>(),
+            vec!["src/routes.rs"]
+        );
+        assert_eq!(patched.referenced_by("linker `cc` not found"), patched);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_write_source_files() {
+        let root = std::env::temp_dir().join(format!("auto_gippity_{}_files", std::process::id()));
+        let outside = root.join("outside");
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("src/linked")).unwrap();
+
+        let files = SourceFiles::parse(RESPONSE, &SourceFiles::default()).unwrap();
+        write_source_files(&root, &files).unwrap();
+        assert_eq!(
+            fs::read_to_string(root.join("src/routes.rs")).unwrap(),
+            files.get("src/routes.rs").unwrap()
+        );
+
+        let escaping = SourceFiles::parse(
+            "// FILE: src/linked/evil.rs\nfn main() {}\n",
+            &SourceFiles::default(),
+        )
+        .unwrap();
+        let result = write_source_files(&root, &escaping);
+        assert!(matches!(result, Err(AgentError::Files(_))));
+        assert!(!outside.join("evil.rs").exists());
+
+        let earlier = SourceFiles::from_main("fn main() {}\n");
+        let removed = files.missing_from(&earlier);
+        remove_source_files(&root, &removed).unwrap();
+        assert!(!root.join("src/routes.rs").exists());
+        assert!(root.join("src/main.rs").exists());
+        assert!(remove_source_files(&root, &["src/linked/evil.rs"]).is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,109 @@
+use std::{
+    io,
+    net::SocketAddr,
+    path::{Component, Path, PathBuf},
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+
+// Serve the files of `root` over HTTP on a random localhost port, until the handle is aborted.
+// Only meant for checking generated static frontends, not as a production server
+pub async fn serve_directory(root: PathBuf) -> io::Result<(SocketAddr, JoinHandle<()>)> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+
+    let handle = tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let root = root.clone();
+            tokio::spawn(async move {
+                // a broken connection only affects that one request
+                let _ = handle_connection(stream, &root).await;
+            });
+        }
+    });
+
+    Ok((address, handle))
+}
+
+async fn handle_connection(mut stream: TcpStream, root: &Path) -> io::Result<()> {
+    let mut buffer = [0u8; 4096];
+    let read = stream.read(&mut buffer).await?;
+    let request = String::from_utf8_lossy(&buffer[..read]);
+
+    // "GET /index.html HTTP/1.1"
+    let request_path = request.split_whitespace().nth(1).unwrap_or("/");
+
+    let (status_line, content_type, body) = match resolve_path(root, request_path) {
+        Some(file_path) => match tokio::fs::read(&file_path).await {
+            Ok(contents) => ("200 OK", content_type_of(&file_path), contents),
+            Err(_) => ("404 Not Found", "text/plain", b"Not Found".to_vec()),
+        },
+        None => ("400 Bad Request", "text/plain", b"Bad Request".to_vec()),
+    };
+
+    let header = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status_line,
+        content_type,
+        body.len()
+    );
+    stream.write_all(header.as_bytes()).await?;
+    stream.write_all(&body).await?;
+    stream.shutdown().await
+}
+
+// Map a request path onto a file below `root`, refusing anything that escapes it
+fn resolve_path(root: &Path, request_path: &str) -> Option<PathBuf> {
+    let path = request_path.split(['?', '#']).next().unwrap_or("/");
+    let mut file_path = root.to_path_buf();
+
+    for component in Path::new(path.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(part) => file_path.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+
+    if path.ends_with('/') || file_path == root {
+        file_path.push("index.html");
+    }
+    Some(file_path)
+}
+
+fn content_type_of(file_path: &Path) -> &'static str {
+    match file_path
+        .extension()
+        .and_then(|extension| extension.to_str())
+    {
+        Some("html") => "text/html; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("json") => "application/json",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_path() {
+        let root = Path::new("/srv/frontend");
+
+        assert_eq!(
+            resolve_path(root, "/"),
+            Some(PathBuf::from("/srv/frontend/index.html"))
+        );
+        assert_eq!(
+            resolve_path(root, "/js/app.js?v=2"),
+            Some(PathBuf::from("/srv/frontend/js/app.js"))
+        );
+        assert_eq!(resolve_path(root, "/../../etc/passwd"), None);
+    }
+}
@@ -0,0 +1,455 @@
+use std::{
+    fmt, io,
+    io::Read,
+    process::{Child, Command, ExitStatus, Output, Stdio},
+    sync::{Arc, Mutex, MutexGuard},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+const MIB: u64 = 1024 * 1024;
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// How much a supervised process may take before it's stopped
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResourceLimits {
+    pub wall_clock: Duration,
+    // per output stream, anything beyond is dropped and the process stopped
+    pub max_output_bytes: usize,
+    pub cpu_seconds: u64,
+    pub address_space_bytes: u64,
+}
+
+impl Default for ResourceLimits {
+    // rustc reserves far more address space than it touches, hence the generous default
+    fn default() -> Self {
+        Self {
+            wall_clock: Duration::from_secs(600),
+            max_output_bytes: MIB as usize,
+            cpu_seconds: 600,
+            address_space_bytes: 16 * 1024 * MIB,
+        }
+    }
+}
+
+// The limit that stopped a process
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Limit {
+    WallClock(Duration),
+    Output(usize),
+    CpuTime(u64),
+    AddressSpace(u64),
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WallClock(duration) => write!(f, "wall-clock timeout of {:?}", duration),
+            Self::Output(bytes) => write!(f, "output cap of {} bytes", bytes),
+            Self::CpuTime(seconds) => write!(f, "CPU time limit of {} seconds", seconds),
+            Self::AddressSpace(bytes) => {
+                write!(f, "address space limit of {} MiB", bytes / MIB)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SupervisedOutput {
+    pub output: Output,
+    // None when the process ended, or was killed, on its own terms
+    pub limit: Option<Limit>,
+}
+
+// What the watchdog, the output readers and the owner share
+#[derive(Debug)]
+struct Supervised {
+    child: Mutex<Child>,
+    limit: Mutex<Option<Limit>>,
+}
+
+impl Supervised {
+    fn child(&self) -> MutexGuard<'_, Child> {
+        self.child
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    // Kill the process with everything it started, recording the first limit that was hit
+    fn stop(&self, limit: Option<Limit>) {
+        if let Some(limit) = limit {
+            self.limit
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .get_or_insert(limit);
+        }
+        kill_tree(&mut self.child());
+    }
+
+    fn leader(&self) -> io::Result<Leader> {
+        leader(&mut self.child())
+    }
+}
+
+// Where the process leading the group is. Until it's reaped, its pid, and with it the group id,
+// can't be given to another process
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Leader {
+    Running,
+    Exited,
+    Reaped,
+}
+
+// A process running within limits, e.g. the generated web server while the agents test it
+#[derive(Debug)]
+pub struct SupervisedChild {
+    supervised: Arc<Supervised>,
+    limits: ResourceLimits,
+    stdout: JoinHandle<Vec<u8>>,
+    stderr: JoinHandle<Vec<u8>>,
+    watchdog: JoinHandle<()>,
+}
+
+impl SupervisedChild {
+    pub fn spawn(command: &mut Command, limits: ResourceLimits) -> io::Result<Self> {
+        apply_rlimits(command, limits);
+        let mut child = command
+            // return information back to us
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+        let supervised = Arc::new(Supervised {
+            child: Mutex::new(child),
+            limit: Mutex::new(None),
+        });
+
+        let spawn_reader = |pipe: Option<Box<dyn Read + Send>>| {
+            let supervised = supervised.clone();
+            thread::spawn(move || {
+                pipe.map(|pipe| read_capped(pipe, limits.max_output_bytes, &supervised))
+                    .unwrap_or_default()
+            })
+        };
+        let stdout = spawn_reader(stdout.map(|pipe| Box::new(pipe) as Box<dyn Read + Send>));
+        let stderr = spawn_reader(stderr.map(|pipe| Box::new(pipe) as Box<dyn Read + Send>));
+
+        let watchdog = {
+            let supervised = supervised.clone();
+            let deadline = Instant::now() + limits.wall_clock;
+            thread::spawn(move || {
+                while matches!(supervised.leader(), Ok(Leader::Running)) {
+                    if Instant::now() >= deadline {
+                        supervised.stop(Some(Limit::WallClock(limits.wall_clock)));
+                        return;
+                    }
+                    thread::sleep(POLL_INTERVAL);
+                }
+            })
+        };
+
+        Ok(Self {
+            supervised,
+            limits,
+            stdout,
+            stderr,
+            watchdog,
+        })
+    }
+
+    pub fn id(&self) -> u32 {
+        self.supervised.child().id()
+    }
+
+    // Wait for the process to end, or for a limit to end it
+    pub fn wait(self) -> io::Result<SupervisedOutput> {
+        while self.supervised.leader()? == Leader::Running {
+            thread::sleep(POLL_INTERVAL);
+        }
+        // leftovers of the process tree would keep the output pipes open, the group is killed
+        // before reaping the leader, while its id is still taken
+        let status = {
+            let mut child = self.supervised.child();
+            kill_tree(&mut child);
+            child.wait()?
+        };
+
+        let output = Output {
+            status,
+            stdout: self.stdout.join().unwrap_or_default(),
+            stderr: self.stderr.join().unwrap_or_default(),
+        };
+        let _ = self.watchdog.join();
+        let recorded_limit = *self
+            .supervised
+            .limit
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let limit = recorded_limit.or_else(|| rlimit_hit(&output, self.limits));
+        Ok(SupervisedOutput { output, limit })
+    }
+
+    // Stop the process tree and collect what it wrote so far
+    pub fn kill(self) -> io::Result<SupervisedOutput> {
+        self.supervised.stop(None);
+        self.wait()
+    }
+}
+
+// Run to completion within the limits
+pub fn run(command: &mut Command, limits: ResourceLimits) -> io::Result<SupervisedOutput> {
+    SupervisedChild::spawn(command, limits)?.wait()
+}
+
+fn read_capped(mut pipe: impl Read, cap: usize, supervised: &Supervised) -> Vec<u8> {
+    let mut buffer: Vec<u8> = vec![];
+    let mut chunk = [0u8; 8192];
+    loop {
+        match pipe.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(read) if buffer.len() + read > cap => {
+                buffer.extend_from_slice(&chunk[..cap - buffer.len()]);
+                supervised.stop(Some(Limit::Output(cap)));
+                break;
+            }
+            Ok(read) => buffer.extend_from_slice(&chunk[..read]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => break,
+        }
+    }
+    buffer
+}
+
+// Looks without reaping: WNOWAIT leaves an exited process for Child::wait
+#[cfg(target_os = "linux")]
+fn leader(child: &mut Child) -> io::Result<Leader> {
+    // SAFETY: waitid only writes to the siginfo it is given, which starts zeroed
+    unsafe {
+        let mut info: libc::siginfo_t = std::mem::zeroed();
+        let result = libc::waitid(
+            libc::P_PID,
+            child.id() as libc::id_t,
+            &mut info,
+            libc::WEXITED | libc::WNOHANG | libc::WNOWAIT,
+        );
+        if result != 0 {
+            let error = io::Error::last_os_error();
+            return match error.raw_os_error() {
+                Some(libc::ECHILD) => Ok(Leader::Reaped),
+                _ => Err(error),
+            };
+        }
+        // no pid when nothing has exited yet
+        Ok(if info.si_pid() == 0 {
+            Leader::Running
+        } else {
+            Leader::Exited
+        })
+    }
+}
+
+// Elsewhere the exit is only seen by reaping, so a finished group isn't signalled anymore
+#[cfg(not(target_os = "linux"))]
+fn leader(child: &mut Child) -> io::Result<Leader> {
+    Ok(match child.try_wait()? {
+        Some(_) => Leader::Reaped,
+        None => Leader::Running,
+    })
+}
+
+// rlimits end the process themselves, they can only be told apart afterwards.
+// Cargo reports a rustc killed by SIGXCPU, Rust programs report failed allocations
+fn rlimit_hit(output: &Output, limits: ResourceLimits) -> Option<Limit> {
+    if output.status.success() {
+        return None;
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    if stderr.contains("SIGXCPU") || killed_by_cpu_limit(&output.status) {
+        Some(Limit::CpuTime(limits.cpu_seconds))
+    } else if stderr.contains("memory allocation of") {
+        Some(Limit::AddressSpace(limits.address_space_bytes))
+    } else {
+        None
+    }
+}
+
+#[cfg(unix)]
+fn killed_by_cpu_limit(status: &ExitStatus) -> bool {
+    use std::os::unix::process::ExitStatusExt;
+
+    status.signal() == Some(libc::SIGXCPU)
+}
+
+#[cfg(not(unix))]
+fn killed_by_cpu_limit(_status: &ExitStatus) -> bool {
+    false
+}
+
+// The child leads its own process group, so that this reaches everything it started.
+// Once the leader is reaped, the group id may belong to somebody else
+#[cfg(unix)]
+fn kill_tree(child: &mut Child) {
+    let is_unreaped = matches!(leader(child), Ok(Leader::Running | Leader::Exited));
+    if let (true, Ok(process_group)) = (is_unreaped, i32::try_from(child.id())) {
+        // SAFETY: kill only sends a signal, a group that is already gone is not an error here
+        unsafe {
+            libc::kill(-process_group, libc::SIGKILL);
+        }
+    }
+    let _ = child.kill();
+}
+
+#[cfg(not(unix))]
+fn kill_tree(child: &mut Child) {
+    let _ = child.kill();
+}
+
+// The limits are inherited by everything the command starts, e.g. every rustc of a build.
+// Going over the CPU time sends SIGXCPU, with a few seconds of grace before SIGKILL
+#[cfg(unix)]
+fn apply_rlimits(command: &mut Command, limits: ResourceLimits) {
+    use std::os::unix::process::CommandExt;
+
+    let set_limit = |resource, soft: u64, hard: u64| {
+        let limit = libc::rlimit {
+            rlim_cur: soft as libc::rlim_t,
+            rlim_max: hard as libc::rlim_t,
+        };
+        // SAFETY: setrlimit only reads the struct it is given
+        if unsafe { libc::setrlimit(resource, &limit) } == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    };
+
+    command.process_group(0);
+    // SAFETY: the closure runs between fork and exec and only calls setrlimit,
+    // which is async-signal-safe, it doesn't allocate
+    unsafe {
+        command.pre_exec(move || {
+            set_limit(libc::RLIMIT_CPU, limits.cpu_seconds, limits.cpu_seconds + 5)?;
+            set_limit(
+                libc::RLIMIT_AS,
+                limits.address_space_bytes,
+                limits.address_space_bytes,
+            )
+        });
+    }
+}
+
+#[cfg(not(unix))]
+fn apply_rlimits(_command: &mut Command, _limits: ResourceLimits) {}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    fn shell(script: &str) -> Command {
+        let mut command = Command::new("sh");
+        command.args(["-c", script]);
+        command
+    }
+
+    #[test]
+    fn test_within_limits() {
+        let supervised = run(
+            &mut shell("ulimit -t; echo done >&2"),
+            ResourceLimits {
+                cpu_seconds: 42,
+                ..ResourceLimits::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(supervised.limit, None);
+        assert_eq!(String::from_utf8_lossy(&supervised.output.stdout), "42\n");
+        assert_eq!(String::from_utf8_lossy(&supervised.output.stderr), "done\n");
+    }
+
+    #[test]
+    fn test_wall_clock_kills_the_process_tree() {
+        let started = Instant::now();
+        // the background sleep keeps stdout open, only killing the whole group ends the run
+        let supervised = run(
+            &mut shell("sleep 30 & sleep 30"),
+            ResourceLimits {
+                wall_clock: Duration::from_millis(200),
+                ..ResourceLimits::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            supervised.limit,
+            Some(Limit::WallClock(Duration::from_millis(200)))
+        );
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(
+            supervised.limit.unwrap().to_string(),
+            "wall-clock timeout of 200ms"
+        );
+    }
+
+    #[test]
+    fn test_output_cap() {
+        let supervised = run(
+            &mut shell("yes"),
+            ResourceLimits {
+                max_output_bytes: 1000,
+                ..ResourceLimits::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(supervised.limit, Some(Limit::Output(1000)));
+        assert_eq!(supervised.output.stdout.len(), 1000);
+    }
+
+    #[test]
+    fn test_cpu_time_limit() {
+        let supervised = run(
+            &mut shell("while :; do :; done"),
+            ResourceLimits {
+                cpu_seconds: 1,
+                ..ResourceLimits::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(supervised.limit, Some(Limit::CpuTime(1)));
+    }
+
+    #[test]
+    fn test_kill() {
+        let server = SupervisedChild::spawn(
+            &mut shell("echo listening; sleep 30"),
+            ResourceLimits::default(),
+        )
+        .unwrap();
+        thread::sleep(Duration::from_millis(200));
+
+        let supervised = server.kill().unwrap();
+        assert_eq!(supervised.limit, None);
+        assert_eq!(
+            String::from_utf8_lossy(&supervised.output.stdout),
+            "listening\n"
+        );
+    }
+
+    #[test]
+    fn test_leader_is_reaped_last() {
+        let mut child = shell("exit 3").spawn().unwrap();
+        while leader(&mut child).unwrap() == Leader::Running {
+            thread::sleep(POLL_INTERVAL);
+        }
+
+        // looking doesn't reap, the group id stays taken until the wait
+        assert_eq!(leader(&mut child).unwrap(), Leader::Exited);
+        kill_tree(&mut child);
+        assert_eq!(child.wait().unwrap().code(), Some(3));
+        assert_eq!(leader(&mut child).unwrap(), Leader::Reaped);
+    }
+}
@@ -0,0 +1,157 @@
+use std::process::{Command, Output, Stdio};
+
+use tokio::task;
+
+use crate::models::general::agent_error::AgentError;
+
+use super::{
+    command_line::WS_PROJECT_PATH,
+    port_forward::PortForward,
+    sandbox::{Isolation, Sandbox},
+    supervisor::{self, SupervisedChild, SupervisedOutput},
+};
+
+// the port the generated web server listens on, and the agents test it on
+const SERVER_PORT: u16 = 8080;
+
+// The generated web server. Sandboxed, it has no network, and only its port is forwarded from the
+// host's loopback
+#[derive(Debug)]
+pub struct WorkspaceServer {
+    process: SupervisedChild,
+    port_forward: Option<PortForward>,
+}
+
+// Agents share the async executor: processes are started, polled and waited for on a
+// blocking thread instead, so that the other agents of a wave keep running
+pub async fn run_blocking<T: Send + 'static>(
+    work: impl FnOnce() -> Result<T, AgentError> + Send + 'static,
+) -> Result<T, AgentError> {
+    task::spawn_blocking(work)
+        .await
+        .map_err(|e| AgentError::Panicked(e.to_string()))?
+}
+
+// Build the generated project, capturing the compiler output.
+// Build scripts and proc macros are generated code too, so the build has no network
+pub async fn build_workspace(sandbox: &Sandbox) -> Result<Output, AgentError> {
+    let sandbox = sandbox.clone();
+    run_blocking(move || {
+        let mut command = sandbox.cargo(&["build"])?;
+        let supervised = supervisor::run(&mut command, sandbox.limits)
+            .map_err(AgentError::io("build backend application"))?;
+        check_run("backend build", &sandbox, supervised)
+    })
+    .await
+}
+
+// Start the generated web server in the background, stop it with stop_workspace_server
+pub async fn run_workspace_server(sandbox: &Sandbox) -> Result<WorkspaceServer, AgentError> {
+    let isolation = sandbox.isolation;
+    let sandbox = sandbox.clone();
+    let process = run_blocking(move || {
+        let mut command = sandbox.cargo(&["run"])?;
+        SupervisedChild::spawn(&mut command, sandbox.limits)
+            .map_err(AgentError::io("run backend application"))
+    })
+    .await?;
+    if isolation == Isolation::None {
+        return Ok(WorkspaceServer {
+            process,
+            port_forward: None,
+        });
+    }
+
+    match PortForward::start(SERVER_PORT, process.id()).await {
+        Ok(port_forward) => Ok(WorkspaceServer {
+            process,
+            port_forward: Some(port_forward),
+        }),
+        Err(e) => {
+            let _ = run_blocking(move || {
+                process
+                    .kill()
+                    .map_err(AgentError::io("stop backend web server"))
+            })
+            .await;
+            Err(AgentError::io(format!(
+                "forward port {} to the web server",
+                SERVER_PORT
+            ))(e))
+        }
+    }
+}
+
+// Kill the web server with everything it started, and collect what it logged
+pub async fn stop_workspace_server(
+    sandbox: &Sandbox,
+    server: WorkspaceServer,
+) -> Result<Output, AgentError> {
+    drop(server.port_forward);
+    let sandbox = sandbox.clone();
+    run_blocking(move || {
+        let supervised = server
+            .process
+            .kill()
+            .map_err(AgentError::io("stop backend web server"))?;
+        check_run("backend web server", &sandbox, supervised)
+    })
+    .await
+}
+
+// Run one integration test file of the generated project, e.g. tests/api.rs
+pub async fn test_workspace(sandbox: &Sandbox, test_name: &str) -> Result<Output, AgentError> {
+    let sandbox = sandbox.clone();
+    let test_name = test_name.to_string();
+    run_blocking(move || {
+        let mut command = sandbox.cargo(&["test", "--test", &test_name])?;
+        let supervised = supervisor::run(&mut command, sandbox.limits)
+            .map_err(AgentError::io("test backend application"))?;
+        check_run("integration tests", &sandbox, supervised)
+    })
+    .await
+}
+
+// A run that was stopped by a limit or by the sandbox is a failure of its own,
+// not a bug for the LLM to fix
+fn check_run(
+    step: &'static str,
+    sandbox: &Sandbox,
+    supervised: SupervisedOutput,
+) -> Result<Output, AgentError> {
+    if let Some(limit) = supervised.limit {
+        return Err(AgentError::LimitExceeded { step, limit });
+    }
+    match sandbox.violation(&supervised.output) {
+        Some(violation) => Err(AgentError::Sandbox(violation)),
+        None => Ok(supervised.output),
+    }
+}
+
+// Build the container image of the generated project, None when there is no container runtime
+pub async fn build_workspace_image(image_tag: &str) -> Result<Option<Output>, AgentError> {
+    let image_tag = image_tag.to_string();
+    run_blocking(move || build_image(&image_tag)).await
+}
+
+fn build_image(image_tag: &str) -> Result<Option<Output>, AgentError> {
+    let has_docker = Command::new("docker")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    if !has_docker {
+        return Ok(None);
+    }
+
+    let output = Command::new("docker")
+        .args(["build", "--tag", image_tag, "."])
+        .current_dir(WS_PROJECT_PATH)
+        // return information back to us
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(AgentError::io("build container image"))?;
+    Ok(Some(output))
+}
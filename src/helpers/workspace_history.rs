@@ -0,0 +1,359 @@
+use std::{
+    ffi::OsString,
+    fs,
+    path::PathBuf,
+    process::{Command, Output},
+};
+
+use crate::models::general::agent_error::AgentError;
+
+use super::{
+    command_line::{PrintCommand, WS_PROJECT_PATH},
+    workspace::run_blocking,
+};
+
+// build output and the database of test runs aren't versions of the code
+const GITIGNORE: &str = "/target\n*.db\n";
+// commits don't depend on, or change, the git config of whoever runs the agents, and run no hooks
+const GIT_CONFIG: [&str; 8] = [
+    "-c",
+    "user.name=Auto-Gippity",
+    "-c",
+    "user.email=auto-gippity@localhost",
+    "-c",
+    "commit.gpgsign=false",
+    "-c",
+    "core.hooksPath=/dev/null",
+];
+
+// A git repository of the generated project with a commit for every version of the code,
+// to bisect regressions the LLM introduced and to go back to a version that built
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkspaceHistory {
+    root: PathBuf,
+    git_dir: PathBuf,
+}
+
+impl WorkspaceHistory {
+    // The repository is kept next to the project, e.g. web_template_autogpt.git. The sandbox can
+    // write to the project, hooks or filters planted in a .git there would run on the host
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        let root: PathBuf = root.into();
+        let mut git_dir = OsString::from(&root);
+        git_dir.push(".git");
+        Self {
+            root,
+            git_dir: git_dir.into(),
+        }
+    }
+
+    pub fn workspace() -> Self {
+        Self::new(WS_PROJECT_PATH)
+    }
+
+    // Commit everything that changed in the project, None when nothing did
+    pub fn commit(&self, message: &str) -> Result<Option<String>, AgentError> {
+        self.init()?;
+        self.git(&["add", "--all"])?;
+        if self.git_succeeds(&["diff", "--cached", "--quiet"])? {
+            return Ok(None);
+        }
+
+        self.git(&["commit", "--quiet", "--no-verify", "-m", message])?;
+        Ok(Some(self.git(&["rev-parse", "HEAD"])?.trim().to_string()))
+    }
+
+    // Commit the project as it is, to put parts of it back later
+    pub fn snapshot(&self, message: &str) -> Result<Snapshot, AgentError> {
+        self.commit(message)?;
+        Ok(Snapshot {
+            history: self.clone(),
+            commit: self.git(&["rev-parse", "HEAD"])?.trim().to_string(),
+        })
+    }
+
+    fn init(&self) -> Result<(), AgentError> {
+        if self.git_dir.exists() {
+            return Ok(());
+        }
+        self.git(&["init", "--quiet"])?;
+        let gitignore = self.root.join(".gitignore");
+        if !gitignore.exists() {
+            fs::write(gitignore, GITIGNORE).map_err(AgentError::io("write .gitignore"))?;
+        }
+        Ok(())
+    }
+
+    fn git_output(&self, args: &[&str]) -> Result<Output, AgentError> {
+        Command::new("git")
+            .arg("--git-dir")
+            .arg(&self.git_dir)
+            .arg("--work-tree")
+            .arg(&self.root)
+            .args(GIT_CONFIG)
+            .args(args)
+            .current_dir(&self.root)
+            .output()
+            .map_err(AgentError::io("run git"))
+    }
+
+    fn git(&self, args: &[&str]) -> Result<String, AgentError> {
+        let output = self.git_output(args)?;
+        if !output.status.success() {
+            return Err(AgentError::History(format!(
+                "git {}: {}",
+                args[0],
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    // For the commands that answer with their exit code
+    fn git_succeeds(&self, args: &[&str]) -> Result<bool, AgentError> {
+        Ok(self.git_output(args)?.status.success())
+    }
+}
+
+// The project as a wave of agents found it, to take back what a failed agent wrote
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    history: WorkspaceHistory,
+    commit: String,
+}
+
+impl Snapshot {
+    // Put the paths, relative to the project, back as they were, without the files added since
+    pub fn restore(&self, paths: &[&str]) -> Result<(), AgentError> {
+        for path in paths {
+            let current = self.history.root.join(path);
+            if current.is_dir() {
+                fs::remove_dir_all(&current)
+            } else if current.exists() {
+                fs::remove_file(&current)
+            } else {
+                Ok(())
+            }
+            .map_err(AgentError::io(format!("remove {}", path)))?;
+
+            let earlier = format!("{}:{}", self.commit, path);
+            if self.history.git_succeeds(&["cat-file", "-e", &earlier])? {
+                self.history.git(&["checkout", &self.commit, "--", path])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Commit the version of the code that was just built. The history is only there to look back,
+// failing to write it is shown but never stops the agent
+pub async fn record_iteration(
+    agent_position: &str,
+    ai_function: &str,
+    build: &Output,
+) -> Option<String> {
+    record(
+        agent_position,
+        iteration_message(agent_position, ai_function, build),
+    )
+    .await
+}
+
+// Passes `result` through, committing the code that was written when it is the error that stops
+// the agent before the build, e.g. a denied approval
+pub async fn record_unbuilt<T>(
+    agent_position: &str,
+    ai_function: &str,
+    result: Result<T, AgentError>,
+) -> Result<T, AgentError> {
+    if let Err(e) = &result {
+        record(
+            agent_position,
+            format!(
+                "{}: not built\n\nWritten by the {}\n\n{}",
+                ai_function, agent_position, e
+            ),
+        )
+        .await;
+    }
+    result
+}
+
+async fn record(agent_position: &str, message: String) -> Option<String> {
+    run_blocking(move || WorkspaceHistory::workspace().commit(&message))
+        .await
+        .unwrap_or_else(|e| {
+            PrintCommand::Issue.print_agent_message(
+                agent_position,
+                format!("Could not record this version of the code: {}", e).as_str(),
+            );
+            None
+        })
+}
+
+// "print_fixed_code: does not compile (2 errors)", with the agent and the first error in the body
+pub fn iteration_message(agent_position: &str, ai_function: &str, build: &Output) -> String {
+    let stderr = String::from_utf8_lossy(&build.stderr);
+    let errors: Vec<&str> = stderr
+        .lines()
+        .filter(|line| {
+            line.starts_with("error[")
+                || (line.starts_with("error:")
+                    && !line.starts_with("error: could not compile")
+                    && !line.starts_with("error: aborting"))
+        })
+        .collect();
+
+    let compile_result = match (build.status.success(), errors.len()) {
+        (true, _) => "compiles".to_string(),
+        (false, 0) => "does not compile".to_string(),
+        (false, 1) => "does not compile (1 error)".to_string(),
+        (false, count) => format!("does not compile ({} errors)", count),
+    };
+    let mut message = format!(
+        "{}: {}\n\nWritten by the {}",
+        ai_function, compile_result, agent_position
+    );
+    if let Some(first_error) = errors.first() {
+        message.push_str(&format!("\n\n{}", first_error));
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Subjects of the commits, the latest first
+    fn subjects(history: &WorkspaceHistory) -> Vec<String> {
+        history
+            .git(&["log", "--format=%s"])
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_iteration_message() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let build = |code: i32, stderr: &str| Output {
+            status: ExitStatusExt::from_raw(code << 8),
+            stdout: vec![],
+            stderr: stderr.as_bytes().to_vec(),
+        };
+
+        assert_eq!(
+            iteration_message(
+                "Backend Developer",
+                "print_backend_webserver_code",
+                &build(0, "   Compiling web_template v0.1.0\n    Finished dev")
+            ),
+            "print_backend_webserver_code: compiles\n\nWritten by the Backend Developer"
+        );
+        assert_eq!(
+            iteration_message(
+                "Backend Developer",
+                "print_fixed_code",
+                &build(
+                    101,
+                    "error[E0425]: cannot find value `x` in this scope\n --> src/routes.rs:3:5\n\
+                     error[E0308]: mismatched types\n\
+                     error: could not compile `web_template` (bin) due to 2 previous errors"
+                )
+            ),
+            "print_fixed_code: does not compile (2 errors)\n\nWritten by the Backend Developer\n\n\
+             error[E0425]: cannot find value `x` in this scope"
+        );
+    }
+
+    #[test]
+    fn test_commit_history() {
+        let root =
+            std::env::temp_dir().join(format!("auto_gippity_{}_history", std::process::id()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        let history = WorkspaceHistory::new(&root);
+        // generated code can write a .git of its own, git leaves it alone
+        fs::create_dir_all(root.join(".git/hooks")).unwrap();
+        fs::write(
+            root.join(".git/hooks/post-commit"),
+            "#!/bin/sh\ntouch hooked\n",
+        )
+        .unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(
+                root.join(".git/hooks/post-commit"),
+                fs::Permissions::from_mode(0o755),
+            )
+            .unwrap();
+        }
+
+        fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+        let first = history
+            .commit("print_backend_webserver_code: compiles")
+            .unwrap();
+        assert!(first.is_some());
+        assert_eq!(
+            fs::read_to_string(root.join(".gitignore")).unwrap(),
+            GITIGNORE
+        );
+        assert!(history.git_dir.join("HEAD").is_file());
+
+        // nothing changed, nothing to record
+        assert_eq!(history.commit("print_fixed_code: compiles").unwrap(), None);
+
+        fs::write(root.join("src/main.rs"), "fn main() {\n").unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join("target/build.log"), "ignored").unwrap();
+        history
+            .commit("print_improved_webserver_code: does not compile (1 error)")
+            .unwrap();
+
+        assert_eq!(
+            subjects(&history),
+            vec![
+                "print_improved_webserver_code: does not compile (1 error)",
+                "print_backend_webserver_code: compiles",
+            ]
+        );
+        assert!(!root.join("hooked").exists());
+        fs::remove_dir_all(&root).unwrap();
+        fs::remove_dir_all(&history.git_dir).unwrap();
+    }
+    #[test]
+    fn test_restore_snapshot() {
+        let root =
+            std::env::temp_dir().join(format!("auto_gippity_{}_snapshot", std::process::id()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(root.join("Cargo.toml"), "[package]\n").unwrap();
+        let history = WorkspaceHistory::new(&root);
+        let snapshot = history.snapshot("Before the Backend Developer").unwrap();
+
+        fs::write(root.join("src/main.rs"), "fn main() {\n").unwrap();
+        fs::write(root.join("src/routes.rs"), "").unwrap();
+        fs::create_dir_all(root.join("frontend")).unwrap();
+        fs::write(root.join("frontend/index.html"), "<html>").unwrap();
+        fs::write(root.join("Cargo.toml"), "[package]\nname = \"web\"\n").unwrap();
+        history.commit("print_fixed_code: compiles").unwrap();
+
+        snapshot.restore(&["src", "frontend"]).unwrap();
+        assert_eq!(
+            fs::read_to_string(root.join("src/main.rs")).unwrap(),
+            "fn main() {}\n"
+        );
+        assert!(!root.join("src/routes.rs").exists());
+        assert!(!root.join("frontend").exists());
+        // paths that weren't asked for keep what was written since
+        assert_eq!(
+            fs::read_to_string(root.join("Cargo.toml")).unwrap(),
+            "[package]\nname = \"web\"\n"
+        );
+        fs::remove_dir_all(&root).unwrap();
+        fs::remove_dir_all(&history.git_dir).unwrap();
+    }
+}
@@ -0,0 +1,46 @@
+//! Agents that turn a website request into a working Rust backend, frontend and deployment files.
+//!
+//! The [`ManagingAgent`] runs the whole team and keeps the [`FactSheet`] they share. Prompts go
+//! through an [`LlmProvider`] and generated code only runs once an [`ApprovalPolicy`] agrees, both
+//! can be swapped through [`ManagingAgent::builder`]. Everything else stays internal.
+
+// Name of an ai_function, for the prompts and logs. Defined before the modules that use it
+macro_rules! get_function_string {
+    ($func: ident) => {{
+        stringify!($func)
+    }};
+}
+
+#[macro_use]
+mod ai_functions;
+mod apis;
+mod helpers;
+mod models;
+
+pub use apis::call_requests::{CallGptError, LlmProvider, OpenAiConfig, OpenAiProvider};
+pub use helpers::{
+    command_line::get_user_response,
+    sandbox::{Isolation, Sandbox},
+    source_files::{SourceFiles, SourceFilesError},
+    supervisor::{Limit, ResourceLimits},
+};
+pub use models::{
+    agents::{
+        agent_context::{AgentContext, CodeEditMode},
+        agent_security::SecurityPolicy,
+        agent_traits::{FactSheet, FactSheetField, Severity, SpecialFunctions},
+    },
+    agents_manager::{
+        agent_run::{AgentRunReport, AgentRunResult, FailurePolicy, RunSummary},
+        managing_agent::{ManagingAgent, ManagingAgentBuilder},
+    },
+    general::{
+        agent_error::AgentError,
+        approval::{
+            AlwaysAllow, AlwaysDeny, Approval, ApprovalPolicy, ApprovalRequest, ApprovalRule,
+            InteractiveApproval, RuleBasedApproval,
+        },
+        checkpoint::{FactSheetCheckpoint, InteractiveCheckpoint},
+        llm::Message,
+    },
+};
@@ -1,25 +1,85 @@
-#[macro_export]
-macro_rules! get_function_string {
-    ($func: ident) => {{
-        stringify!($func)
-    }};
-}
+use std::{env, process};
+
+use auto_gipitty_udemy::{
+    get_user_response, AlwaysAllow, AlwaysDeny, ApprovalRule, CodeEditMode, InteractiveCheckpoint,
+    ManagingAgent, ManagingAgentBuilder, RuleBasedApproval, Sandbox, SecurityPolicy, Severity,
+};
 
-#[macro_use]
-mod ai_functions;
-mod apis;
-mod helpers;
-mod models;
+const USAGE: &str =
+    "usage: auto_gipitty_udemy [--approval ask|allow|deny|clean] [--block-at low|medium|high|critical] [--checkpoint] [--no-isolation] [--patch-edits]";
 
-use helpers::command_line::get_user_response;
-use models::agents_manager::managing_agent::ManagingAgent;
+// `--approval` picks who approves running generated code, asking at the terminal by default.
+// `--block-at` picks the lowest severity of security findings that stops the project, critical by default.
+// `--checkpoint` lets the user review and edit the fact sheet between agents.
+// `--no-isolation` runs generated code without namespaces, only within resource limits.
+// `--patch-edits` has the backend improved and fixed through edits instead of whole files
+fn builder_from_args(args: &[String]) -> Result<ManagingAgentBuilder, String> {
+    let mut builder = ManagingAgent::builder();
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        builder = match flag.as_str() {
+            "--approval" => match args.next().map(String::as_str) {
+                Some("ask") => builder,
+                Some("allow") => builder.approval(AlwaysAllow),
+                Some("deny") => builder.approval(AlwaysDeny),
+                // for unattended runs: only code without security findings may run
+                Some("clean") => {
+                    builder.approval(RuleBasedApproval::new(vec![ApprovalRule::CleanScan]))
+                }
+                _ => return Err(USAGE.to_string()),
+            },
+            "--block-at" => {
+                let block_at = match args.next().map(String::as_str) {
+                    Some("low") => Severity::Low,
+                    Some("medium") => Severity::Medium,
+                    Some("high") => Severity::High,
+                    Some("critical") => Severity::Critical,
+                    _ => return Err(USAGE.to_string()),
+                };
+                builder.security_policy(SecurityPolicy { block_at })
+            }
+            "--checkpoint" => builder.checkpoint(InteractiveCheckpoint),
+            "--no-isolation" => builder.sandbox(Sandbox::unisolated()),
+            "--patch-edits" => builder.code_edits(CodeEditMode::Patch),
+            _ => return Err(USAGE.to_string()),
+        };
+    }
+    Ok(builder)
+}
 
 #[tokio::main]
 async fn main() {
-    let user_request = get_user_response("What website are we building today?");
-    let mut managing_agent = ManagingAgent::new(user_request)
-        .await
-        .expect("Error creating agent");
+    let args: Vec<String> = env::args().skip(1).collect();
+    let builder = match builder_from_args(&args) {
+        Ok(builder) => builder,
+        Err(usage) => {
+            eprintln!("{}", usage);
+            process::exit(2);
+        }
+    };
+
+    let managing_agent = match get_user_response("What website are we building today?") {
+        Ok(user_request) => builder.build(user_request).await,
+        Err(e) => Err(e),
+    };
+    let mut managing_agent = match managing_agent {
+        Ok(managing_agent) => managing_agent,
+        Err(e) => {
+            eprintln!("Error creating agent: {}", e);
+            process::exit(1);
+        }
+    };
 
-    managing_agent.execute_project().await;
+    match managing_agent.execute_project().await {
+        Ok(run_summary) => {
+            println!("\n{}", run_summary);
+            if run_summary.is_failed() {
+                process::exit(1);
+            }
+        }
+        Err(e) => {
+            eprintln!("Error executing project: {}", e);
+            process::exit(1);
+        }
+    }
 }
@@ -2,7 +2,7 @@ use crate::models::general::llm::Message;
 
 use super::basic_traits::BasicTraits;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum AgentState {
     Discovering,
     Working,
@@ -2,6 +2,7 @@ use crate::models::general::llm::Message;
 
 use super::basic_agent::AgentState;
 
+#[allow(dead_code)]
 pub trait BasicTraits {
     fn new(objective: String, position: String) -> Self;
     fn update_state(&mut self, new_state: AgentState);
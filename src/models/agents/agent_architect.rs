@@ -4,23 +4,29 @@ use crate::{
     ai_functions::aifunc_architect::{print_project_scope, print_site_urls},
     helpers::{
         command_line::PrintCommand,
-        general::{ai_task_request_decoded, check_status_code},
+        general::{ai_task_request_decoded, check_status_code, http_client},
+    },
+    models::{
+        agent_basic::basic_agent::{AgentState, BasicAgent},
+        general::agent_error::AgentError,
     },
-    models::agent_basic::basic_agent::{AgentState, BasicAgent},
 };
 use async_trait::async_trait;
-use reqwest::Client;
 
-use super::agent_traits::{FactSheet, ProjectScope, SpecialFunctions};
+use super::{
+    agent_context::AgentContext,
+    agent_traits::{FactSheet, FactSheetField, ProjectScope, SpecialFunctions},
+};
 
 #[derive(Debug)]
 pub struct AgentSolutionArchitect {
     attributes: BasicAgent,
+    context: AgentContext,
 }
 
 impl AgentSolutionArchitect {
     #[allow(dead_code)]
-    pub fn new() -> Self {
+    pub fn new(context: AgentContext) -> Self {
         let attributes = BasicAgent {
             // go to previous location g; go to next location g,
             // Ctr + o takes you to the previous locaiton in the jump list
@@ -32,41 +38,50 @@ impl AgentSolutionArchitect {
             memory: vec![],
         };
 
-        Self { attributes }
+        Self {
+            attributes,
+            context,
+        }
     }
 
     // Retrieve project scope
-    async fn call_project_scope(&mut self, fact_sheet: &mut FactSheet) -> ProjectScope {
+    async fn call_project_scope(
+        &mut self,
+        fact_sheet: &mut FactSheet,
+    ) -> Result<ProjectScope, AgentError> {
         let msg_context = fact_sheet.project_description.to_string();
 
         let ai_response = ai_task_request_decoded::<ProjectScope>(
+            self.context.provider.as_ref(),
             msg_context,
             &self.attributes.position,
             get_function_string!(print_project_scope),
             print_project_scope,
         )
-        .await;
+        .await?;
 
         fact_sheet.project_scope = Some(ai_response);
         self.attributes.state = AgentState::Finished;
-        ai_response
+        Ok(ai_response)
     }
 
     async fn call_determine_external_urls(
         &mut self,
         fact_sheet: &mut FactSheet,
         msg_context: String,
-    ) {
+    ) -> Result<(), AgentError> {
         let ai_response: Vec<String> = ai_task_request_decoded::<Vec<String>>(
+            self.context.provider.as_ref(),
             msg_context,
             &self.attributes.position,
             get_function_string!(print_site_urls),
             print_site_urls,
         )
-        .await;
+        .await?;
 
         fact_sheet.external_urls = ai_response;
         self.attributes.state = AgentState::Validation;
+        Ok(())
     }
 }
 
@@ -76,29 +91,31 @@ impl SpecialFunctions for AgentSolutionArchitect {
         &self.attributes
     }
 
-    async fn execute(
-        &mut self,
-        fact_sheet: &mut FactSheet,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    fn reads(&self) -> Vec<FactSheetField> {
+        vec![FactSheetField::ProjectDescription]
+    }
+
+    fn writes(&self) -> Vec<FactSheetField> {
+        vec![FactSheetField::ProjectScope, FactSheetField::ExternalUrls]
+    }
+
+    async fn execute(&mut self, fact_sheet: &mut FactSheet) -> Result<(), AgentError> {
         while self.attributes.state != AgentState::Finished {
             match self.attributes.state {
                 AgentState::Discovering => {
-                    let project_scope = self.call_project_scope(fact_sheet).await;
+                    let project_scope = self.call_project_scope(fact_sheet).await?;
                     if project_scope.is_external_urls_required {
                         self.call_determine_external_urls(
                             fact_sheet,
                             fact_sheet.project_description.clone(),
                         )
-                        .await;
+                        .await?;
                         self.attributes.state = AgentState::Validation;
                     }
                 }
                 AgentState::Validation => {
                     let mut exclude_urls: Vec<String> = vec![];
-                    let client = Client::builder()
-                        .timeout(Duration::from_secs(5))
-                        .build()
-                        .unwrap();
+                    let client = http_client(Duration::from_secs(5))?;
 
                     // Find faulty urls
                     let urls: &Vec<String> = fact_sheet.external_urls.as_ref();
@@ -141,14 +158,14 @@ impl SpecialFunctions for AgentSolutionArchitect {
     }
 }
 
-#[cfg(test)]
+// only calls OpenAI, see the openai-coverage feature
+#[cfg(all(test, feature = "openai-coverage"))]
 mod tests {
     use super::*;
 
     #[tokio::test]
-    #[cfg(feature = "openai-coverage")]
     async fn test_soltuion_architect() {
-        let mut agent = AgentSolutionArchitect::new();
+        let mut agent = AgentSolutionArchitect::new(AgentContext::default());
 
         let mut dummy_factsheet = FactSheet {
             project_description:
@@ -158,6 +175,11 @@ mod tests {
             external_urls: vec![],
             backend_code: None,
             api_endpoint_schema: vec![],
+            frontend_code: None,
+            database_schema: None,
+            security_report: None,
+            code_review: None,
+            deployment_files: vec![],
         };
 
         agent
@@ -0,0 +1,721 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use reqwest::{Client, Method, StatusCode};
+use tokio::time;
+
+use crate::{
+    ai_functions::{aifunc_auth::print_auth_backend_code, aifunc_backend::print_fixed_code},
+    helpers::{
+        code_analysis::extract_routes,
+        command_line::{
+            add_workspace_dependency, add_workspace_dev_dependency, read_workspace_package_name,
+            save_backend_code, save_workspace_test, PrintCommand,
+        },
+        general::{ai_task_request_files, http_client},
+        source_files::SourceFiles,
+        workspace::{build_workspace, run_workspace_server, stop_workspace_server},
+        workspace_history::{record_iteration, record_unbuilt},
+    },
+    models::{
+        agent_basic::basic_agent::{AgentState, BasicAgent},
+        general::{
+            agent_error::AgentError,
+            api_route::{concrete_path, HttpMethod},
+            approval::{require_approval, ApprovalRequest},
+        },
+    },
+};
+
+use super::{
+    agent_context::AgentContext,
+    agent_security::AgentSecurityReviewer,
+    agent_traits::{FactSheet, FactSheetField, RouteObject, SpecialFunctions},
+};
+
+const MAX_AUTH_BUG_RETRIES: i8 = 2;
+const PUBLIC_ROUTES: [&str; 2] = ["/signup", "/login"];
+// logging out takes the session it ends, so it is protected like any other route
+const LOGOUT_ROUTE: &str = "/logout";
+pub const AUTH_TEST_FILE: &str = "auth.rs";
+const BASE_URL: &str = "http://localhost:8080";
+const TEST_PASSWORD: &str = "correct horse battery staple";
+// the generated test starts the server itself, so it runs with a plain cargo test
+const AUTH_TEST_TEMPLATE: &str = r#"// Generated by Auto-Gippity: protected routes refuse requests without a token, signing up and
+// logging in opens them, logging out closes them again.
+// Run with: cargo test --test auth, the test starts the server itself
+use std::{
+    process::{Child, Command},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use reqwest::{Client, Method, StatusCode};
+
+const BASE_URL: &str = "http://localhost:8080";
+const PROTECTED_ROUTES: [(&str, &str); {route_count}] = [{protected_routes}];
+
+// The server of this project, stopped when the test is done
+struct Server(Child);
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+async fn start_server(client: &Client) -> Server {
+    let server = Server(
+        Command::new(env!("CARGO_BIN_EXE_{package_name}"))
+            .spawn()
+            .expect("Could not start the server"),
+    );
+    for _ in 0..50 {
+        if client.get(BASE_URL).send().await.is_ok() {
+            return server;
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    panic!("The server is not listening on {}", BASE_URL);
+}
+
+async fn status_of(client: &Client, (method, path): (&str, &str), token: Option<&str>) -> StatusCode {
+    let mut request = client.request(
+        Method::from_bytes(method.as_bytes()).unwrap(),
+        format!("{}{}", BASE_URL, path),
+    );
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    request.send().await.expect("The server went away").status()
+}
+
+async fn post_credentials(client: &Client, path: &str, credentials: &str) -> (StatusCode, String) {
+    let response = client
+        .post(format!("{}{}", BASE_URL, path))
+        .header("Content-Type", "application/json")
+        .body(credentials.to_string())
+        .send()
+        .await
+        .expect("The server went away");
+    let status = response.status();
+    (status, response.text().await.unwrap_or_default())
+}
+
+#[tokio::test]
+async fn signup_login_and_logout() {
+    let client = Client::new();
+    let _server = start_server(&client).await;
+
+    for route in PROTECTED_ROUTES {
+        let status = status_of(&client, route, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{} {} without a token", route.0, route.1);
+    }
+
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let credentials = serde_json::json!({ "username": format!("user{}", nanos), "password": "{password}" }).to_string();
+    let (status, body) = post_credentials(&client, "/signup", &credentials).await;
+    assert!(status.is_success(), "POST /signup returned {}: {}", status, body);
+    let (status, body) = post_credentials(&client, "/login", &credentials).await;
+    assert!(status.is_success(), "POST /login returned {}: {}", status, body);
+    let body: serde_json::Value = serde_json::from_str(&body).expect("POST /login returns JSON");
+    let token = body["token"].as_str().expect("POST /login returns a token");
+
+    let routes = PROTECTED_ROUTES.into_iter().filter(|&(_, path)| path != "/logout");
+    for route in routes.clone() {
+        let status = status_of(&client, route, Some(token)).await;
+        assert_ne!(status, StatusCode::UNAUTHORIZED, "{} {} with the token of POST /login", route.0, route.1);
+    }
+
+    let status = status_of(&client, ("POST", "/logout"), Some(token)).await;
+    assert!(status.is_success(), "POST /logout returned {}", status);
+    for route in routes {
+        let status = status_of(&client, route, Some(token)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{} {} after POST /logout", route.0, route.1);
+    }
+}
+"#;
+
+#[derive(Debug)]
+pub struct AgentAuthEngineer {
+    attributes: BasicAgent,
+    context: AgentContext,
+    // what the approval policy saw last, the next approval shows the changes since
+    approved_code: Option<SourceFiles>,
+    // the ai_function that wrote the current code, for the workspace history
+    changed_by: String,
+    bug_errors: Option<String>,
+    bug_count: i8,
+}
+
+impl AgentAuthEngineer {
+    pub fn new(context: AgentContext) -> Self {
+        let attributes = BasicAgent {
+            objective:
+                "Adds password hashing, token issuance and protected routes to the webserver"
+                    .to_string(),
+            position: "Auth Engineer".to_string(),
+            state: AgentState::Discovering,
+            memory: vec![],
+        };
+
+        Self {
+            attributes,
+            context,
+            approved_code: None,
+            changed_by: String::new(),
+            bug_errors: None,
+            bug_count: 0,
+        }
+    }
+
+    async fn call_auth_backend_code(
+        &mut self,
+        fact_sheet: &mut FactSheet,
+    ) -> Result<(), AgentError> {
+        let mut backend_code = fact_sheet.backend_code.clone().unwrap_or_default();
+        let msg_context = format!(
+            "CODE_INPUT: {}\n PUBLIC_ROUTES: {:?}\n",
+            backend_code.render(),
+            PUBLIC_ROUTES
+        );
+
+        let auth_code = ai_task_request_files(
+            self.context.provider.as_ref(),
+            msg_context,
+            &backend_code,
+            &self.attributes.position,
+            get_function_string!(print_auth_backend_code),
+            print_auth_backend_code,
+        )
+        .await?;
+        backend_code.merge(auth_code);
+
+        add_workspace_dependency("argon2", r#""0.5""#)?;
+        add_workspace_dependency("jsonwebtoken", r#""9""#)?;
+        save_backend_code(&backend_code)?;
+        fact_sheet.backend_code = Some(backend_code);
+        self.changed_by = get_function_string!(print_auth_backend_code).to_string();
+        Ok(())
+    }
+
+    async fn call_fix_code_bugs(&mut self, fact_sheet: &mut FactSheet) -> Result<(), AgentError> {
+        let mut backend_code = fact_sheet.backend_code.clone().unwrap_or_default();
+        let bug_errors = self.bug_errors.clone().unwrap_or_default();
+        let broken_code = backend_code.referenced_by(&bug_errors);
+        let msg_context = format!(
+            "BROKEN_CODE: {}\n ERROR_BUGS: {}\n
+            THIS FUNCTION ONLY OUTPUTS CODE. JUST OUTPUT THE CODE.",
+            broken_code.render(),
+            bug_errors
+        );
+
+        let fixed_code = ai_task_request_files(
+            self.context.provider.as_ref(),
+            msg_context,
+            &broken_code,
+            &self.attributes.position,
+            get_function_string!(print_fixed_code),
+            print_fixed_code,
+        )
+        .await?;
+        backend_code.merge(fixed_code);
+
+        save_backend_code(&backend_code)?;
+        fact_sheet.backend_code = Some(backend_code);
+        self.changed_by = get_function_string!(print_fixed_code).to_string();
+        Ok(())
+    }
+
+    // Run the backend and walk through what its users do, see auth_flow_bugs
+    async fn find_auth_bugs(
+        &self,
+        protected_routes: &[RouteObject],
+    ) -> Result<Vec<String>, AgentError> {
+        let client = http_client(Duration::from_secs(5))?;
+        let run_backend_server = run_workspace_server(&self.context.sandbox).await?;
+        PrintCommand::UnitTest.print_agent_message(
+            self.attributes.position.as_str(),
+            "Auth Unit Testing: Launching server in 5 seconds...",
+        );
+        time::sleep(Duration::from_secs(5)).await;
+
+        PrintCommand::UnitTest.print_agent_message(
+            self.attributes.position.as_str(),
+            "Testing sign up, log in and log out against the protected routes...",
+        );
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos());
+        let bugs = auth_flow_bugs(
+            &client,
+            BASE_URL,
+            protected_routes,
+            &format!("user{}", nanos),
+        )
+        .await;
+
+        stop_workspace_server(&self.context.sandbox, run_backend_server).await?;
+
+        Ok(bugs)
+    }
+}
+
+pub fn is_public_route(route: &str) -> bool {
+    PUBLIC_ROUTES.contains(&route.trim_end_matches('/'))
+}
+
+fn is_logout_route(route_object: &RouteObject) -> bool {
+    route_object.method == HttpMethod::Post
+        && route_object.route.trim_end_matches('/') == LOGOUT_ROUTE
+}
+
+fn has_logout_route(protected_routes: &[RouteObject]) -> bool {
+    protected_routes.iter().any(is_logout_route)
+}
+
+// Protected routes refuse requests without a token, signing up and logging in as `username`
+// opens them, logging out closes them again. Every step that goes otherwise is a bug
+async fn auth_flow_bugs(
+    client: &Client,
+    base_url: &str,
+    protected_routes: &[RouteObject],
+    username: &str,
+) -> Vec<String> {
+    let mut bugs: Vec<String> = vec![];
+    for route_object in protected_routes {
+        match status_of(client, base_url, route_object, None).await {
+            Ok(StatusCode::UNAUTHORIZED) => {}
+            Ok(status) => bugs.push(format!(
+                "Unauthenticated {} returned status {} instead of 401",
+                request_line(route_object),
+                status.as_u16()
+            )),
+            Err(bug) => bugs.push(bug),
+        }
+    }
+
+    let token = match log_in(client, base_url, username).await {
+        Ok(token) => token,
+        Err(bug) => {
+            bugs.push(bug);
+            return bugs;
+        }
+    };
+    let (logout_routes, routes): (Vec<&RouteObject>, Vec<&RouteObject>) = protected_routes
+        .iter()
+        .partition(|route_object| is_logout_route(route_object));
+    for route_object in &routes {
+        match status_of(client, base_url, route_object, Some(&token)).await {
+            Ok(StatusCode::UNAUTHORIZED) => bugs.push(format!(
+                "{} returned 401 with the token of POST /login",
+                request_line(route_object)
+            )),
+            Ok(_) => {}
+            Err(bug) => bugs.push(bug),
+        }
+    }
+
+    let Some(logout_route) = logout_routes.first() else {
+        return bugs;
+    };
+    match status_of(client, base_url, logout_route, Some(&token)).await {
+        Ok(status) if status.is_success() => {
+            for route_object in &routes {
+                match status_of(client, base_url, route_object, Some(&token)).await {
+                    Ok(StatusCode::UNAUTHORIZED) => {}
+                    Ok(status) => bugs.push(format!(
+                        "{} returned status {} after POST {}, the token was not revoked",
+                        request_line(route_object),
+                        status.as_u16(),
+                        LOGOUT_ROUTE
+                    )),
+                    Err(bug) => bugs.push(bug),
+                }
+            }
+        }
+        Ok(status) => bugs.push(format!(
+            "POST {} with the token of POST /login returned status {}",
+            LOGOUT_ROUTE,
+            status.as_u16()
+        )),
+        Err(bug) => bugs.push(bug),
+    }
+    bugs
+}
+
+// Sign up and log in, the token POST /login answers with
+async fn log_in(client: &Client, base_url: &str, username: &str) -> Result<String, String> {
+    let credentials = serde_json::json!({ "username": username, "password": TEST_PASSWORD });
+    let mut body = String::new();
+    for route in PUBLIC_ROUTES {
+        let response = client
+            .post(format!("{}{}", base_url, route))
+            .json(&credentials)
+            .send()
+            .await
+            .map_err(|e| format!("POST {} failed: {}", route, e))?;
+        let status = response.status();
+        body = response.text().await.unwrap_or_default();
+        if !status.is_success() {
+            return Err(format!(
+                "POST {} returned status {}: {}",
+                route,
+                status.as_u16(),
+                body
+            ));
+        }
+    }
+
+    serde_json::from_str::<serde_json::Value>(&body)
+        .ok()
+        .and_then(|login| login["token"].as_str().map(String::from))
+        .ok_or_else(|| format!("POST /login returned no token: {}", body))
+}
+
+async fn status_of(
+    client: &Client,
+    base_url: &str,
+    route_object: &RouteObject,
+    token: Option<&str>,
+) -> Result<StatusCode, String> {
+    let mut request = client.request(
+        Method::from(route_object.method),
+        format!("{}{}", base_url, concrete_path(&route_object.route)),
+    );
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    request
+        .send()
+        .await
+        .map(|response| response.status())
+        .map_err(|e| format!("{} failed: {}", request_line(route_object), e))
+}
+
+// "DELETE /item/1"
+fn request_line(route_object: &RouteObject) -> String {
+    format!(
+        "{} {}",
+        Method::from(route_object.method),
+        concrete_path(&route_object.route)
+    )
+}
+
+// Integration test shipped with the generated project, the same walk through as auth_flow_bugs
+fn auth_test_file(package_name: &str, protected_routes: &[RouteObject]) -> String {
+    let routes: Vec<String> = protected_routes
+        .iter()
+        .map(|route_object| {
+            format!(
+                "(\"{}\", \"{}\")",
+                Method::from(route_object.method),
+                concrete_path(&route_object.route)
+            )
+        })
+        .collect();
+
+    AUTH_TEST_TEMPLATE
+        .replace("{route_count}", &routes.len().to_string())
+        .replace("{protected_routes}", &routes.join(", "))
+        .replace("{package_name}", package_name)
+        .replace("{password}", TEST_PASSWORD)
+}
+
+#[async_trait]
+impl SpecialFunctions for AgentAuthEngineer {
+    fn get_attributes_from_agent(&self) -> &BasicAgent {
+        &self.attributes
+    }
+
+    fn reads(&self) -> Vec<FactSheetField> {
+        vec![
+            FactSheetField::ProjectScope,
+            FactSheetField::ExternalUrls,
+            FactSheetField::BackendCode,
+        ]
+    }
+
+    fn writes(&self) -> Vec<FactSheetField> {
+        vec![
+            FactSheetField::BackendCode,
+            FactSheetField::ApiEndpointSchema,
+            FactSheetField::SecurityReport,
+        ]
+    }
+
+    async fn execute(&mut self, fact_sheet: &mut FactSheet) -> Result<(), AgentError> {
+        if self.approved_code.is_none() {
+            self.approved_code = fact_sheet.backend_code.clone();
+        }
+
+        while self.attributes.state != AgentState::Finished {
+            match self.attributes.state {
+                AgentState::Discovering => {
+                    let is_login_required = fact_sheet
+                        .project_scope
+                        .is_some_and(|project_scope| project_scope.is_user_login_and_logout);
+                    if !is_login_required {
+                        PrintCommand::AICall.print_agent_message(
+                            self.attributes.position.as_str(),
+                            "No user login required, skipping authentication",
+                        );
+                        self.attributes.state = AgentState::Finished;
+                        continue;
+                    }
+                    self.call_auth_backend_code(fact_sheet).await?;
+                    self.attributes.state = AgentState::Validation;
+                }
+                AgentState::Working => {
+                    self.call_fix_code_bugs(fact_sheet).await?;
+                    self.attributes.state = AgentState::Validation;
+                }
+                AgentState::Validation => {
+                    // Guard: static review first, a blocking finding stops the project
+                    record_unbuilt(
+                        &self.attributes.position,
+                        &self.changed_by,
+                        AgentSecurityReviewer::with_policy(self.context.security)
+                            .execute(fact_sheet)
+                            .await,
+                    )
+                    .await?;
+                    let security_report = fact_sheet.security_report.clone().unwrap_or_default();
+
+                    // Guard: ensure AI safety
+                    PrintCommand::UnitTest.print_agent_message(
+                        self.attributes.position.as_str(),
+                        "Auth Code Unit Testing: Requesting approval",
+                    );
+                    // the code is already on disk, a version turned down is kept in the history too
+                    let edited_code = record_unbuilt(
+                        &self.attributes.position,
+                        &self.changed_by,
+                        require_approval(
+                            self.context.approval.as_ref(),
+                            &ApprovalRequest {
+                                agent_position: &self.attributes.position,
+                                previous_code: self
+                                    .approved_code
+                                    .as_ref()
+                                    .map(SourceFiles::render)
+                                    .as_deref(),
+                                code: &fact_sheet.backend_code.clone().unwrap_or_default().render(),
+                                security_report: &security_report,
+                            },
+                        ),
+                    )
+                    .await?;
+                    if let Some(code) = edited_code {
+                        let backend_code = SourceFiles::parse(
+                            &code,
+                            &fact_sheet.backend_code.clone().unwrap_or_default(),
+                        )?;
+                        save_backend_code(&backend_code)?;
+                        fact_sheet.backend_code = Some(backend_code);
+                        self.changed_by.push_str(", edited by hand");
+                    }
+                    self.approved_code = fact_sheet.backend_code.clone();
+
+                    let build_backend_server = build_workspace(&self.context.sandbox).await?;
+                    record_iteration(
+                        &self.attributes.position,
+                        &self.changed_by,
+                        &build_backend_server,
+                    )
+                    .await;
+                    let mut bugs: Vec<String> = vec![];
+
+                    if build_backend_server.status.success() {
+                        let backend_code = fact_sheet.backend_code.clone().unwrap_or_default();
+                        match extract_routes(&backend_code) {
+                            Ok(api_endpoints) => {
+                                let protected_routes: Vec<RouteObject> = api_endpoints
+                                    .iter()
+                                    .filter(|route_object| !is_public_route(&route_object.route))
+                                    .cloned()
+                                    .collect();
+                                bugs = self.find_auth_bugs(&protected_routes).await?;
+                                if !has_logout_route(&protected_routes) {
+                                    bugs.push(format!("There is no POST {} route", LOGOUT_ROUTE));
+                                }
+                                if bugs.is_empty() {
+                                    add_workspace_dev_dependency("reqwest", r#""0.11""#)?;
+                                    add_workspace_dev_dependency("serde_json", r#""1""#)?;
+                                    add_workspace_dev_dependency(
+                                        "tokio",
+                                        r#"{ version = "1", features = ["macros", "rt-multi-thread"] }"#,
+                                    )?;
+                                    save_workspace_test(
+                                        AUTH_TEST_FILE,
+                                        &auth_test_file(
+                                            &read_workspace_package_name()?,
+                                            &protected_routes,
+                                        ),
+                                    )?;
+                                    // login, logout and signup are part of the API now
+                                    fact_sheet.api_endpoint_schema = api_endpoints;
+                                }
+                            }
+                            Err(e) => bugs.push(format!("Could not read the routes: {}", e)),
+                        }
+                    } else {
+                        bugs.push(
+                            String::from_utf8_lossy(&build_backend_server.stderr).to_string(),
+                        );
+                    }
+
+                    if bugs.is_empty() {
+                        PrintCommand::UnitTest.print_agent_message(
+                            self.attributes.position.as_str(),
+                            "Auth testing complete...",
+                        );
+                        self.attributes.state = AgentState::Finished;
+                        continue;
+                    }
+
+                    self.bug_count += 1;
+                    self.bug_errors = Some(bugs.join("\n"));
+
+                    // Exit if too many bugs
+                    if self.bug_count > MAX_AUTH_BUG_RETRIES {
+                        PrintCommand::Issue.print_agent_message(
+                            self.attributes.position.as_str(),
+                            "Auth Code Unit Testing: Too many bugs found in code...",
+                        );
+                        return Err(AgentError::Validation(format!(
+                            "too many auth bugs: {}",
+                            bugs.join("\n")
+                        )));
+                    }
+
+                    // Pass back for rework
+                    self.attributes.state = AgentState::Working;
+                }
+                _ => self.attributes.state = AgentState::Finished,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    fn route(route: &str, method: &str) -> RouteObject {
+        serde_json::from_value(serde_json::json!({
+            "route": route,
+            "method": method,
+            "request_body": "None",
+            "response": "None"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_public_routes_and_paths() {
+        assert!(is_public_route("/login/"));
+        assert!(!is_public_route("/logout"));
+        assert!(!is_public_route("/item/{id}"));
+        assert_eq!(concrete_path("/user/{user_id}/item/{id}"), "/user/1/item/1");
+    }
+
+    // A backend with a single user token, accepted until it is logged out or forever
+    async fn stub_backend(revokes_tokens: bool) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut is_logged_out = false;
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut stream = BufReader::new(stream);
+                let mut request_line = String::new();
+                stream.read_line(&mut request_line).await.unwrap();
+                let mut is_authorized = false;
+                let mut content_length = 0;
+                loop {
+                    let mut header = String::new();
+                    stream.read_line(&mut header).await.unwrap();
+                    let header = header.trim_end().to_ascii_lowercase();
+                    if header.is_empty() {
+                        break;
+                    }
+                    if let Some(length) = header.strip_prefix("content-length:") {
+                        content_length = length.trim().parse().unwrap();
+                    }
+                    is_authorized |= header == "authorization: bearer t0ken";
+                }
+                let mut body = vec![0; content_length];
+                stream.read_exact(&mut body).await.unwrap();
+
+                let (status, body) = match request_line.split(' ').nth(1).unwrap_or_default() {
+                    "/signup" => ("201 Created", ""),
+                    "/login" => ("200 OK", r#"{"token":"t0ken"}"#),
+                    _ if !is_authorized || is_logged_out => ("401 Unauthorized", ""),
+                    "/logout" => {
+                        is_logged_out = revokes_tokens;
+                        ("200 OK", "")
+                    }
+                    _ => ("404 Not Found", ""),
+                };
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        base_url
+    }
+
+    #[tokio::test]
+    async fn test_auth_flow_bugs() {
+        let protected_routes = [route("/item/{id}", "delete"), route("/logout", "post")];
+        let client = http_client(Duration::from_secs(5)).unwrap();
+
+        let base_url = stub_backend(true).await;
+        assert!(
+            auth_flow_bugs(&client, &base_url, &protected_routes, "user1")
+                .await
+                .is_empty()
+        );
+
+        let base_url = stub_backend(false).await;
+        assert_eq!(
+            auth_flow_bugs(&client, &base_url, &protected_routes, "user1").await,
+            vec![
+                "DELETE /item/1 returned status 404 after POST /logout, the token was not revoked"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_auth_test_file() {
+        let protected_routes = [
+            route("/item/{id}", "delete"),
+            route("/items", "get"),
+            route("/logout", "post"),
+        ];
+        assert!(has_logout_route(&protected_routes));
+        assert!(!has_logout_route(&protected_routes[..2]));
+        let tests = auth_test_file("web_template", &protected_routes);
+
+        assert!(tests.contains(
+            "const PROTECTED_ROUTES: [(&str, &str); 3] = \
+             [(\"DELETE\", \"/item/1\"), (\"GET\", \"/items\"), (\"POST\", \"/logout\")];"
+        ));
+        // the test starts the server it checks
+        assert!(tests.contains("Command::new(env!(\"CARGO_BIN_EXE_web_template\"))"));
+        assert!(tests.contains(&format!("\"password\": \"{}\"", TEST_PASSWORD)));
+        assert!(!tests.contains("{route_count}"));
+        assert_eq!(tests.matches("#[tokio::test]").count(), 1);
+    }
+}
@@ -1,38 +1,106 @@
-use std::{
-    process::{Command, Stdio},
-    time::Duration,
-};
+use std::time::Duration;
 
 use async_trait::async_trait;
-use reqwest::Client;
 use tokio::time;
 
 use crate::{
     ai_functions::aifunc_backend::{
-        print_backend_webserver_code, print_fixed_code, print_improved_webserver_code,
+        print_backend_webserver_code, print_fixed_code, print_fixed_code_edits,
+        print_improved_webserver_code, print_improved_webserver_code_edits,
         print_rest_api_endpoints,
     },
     helpers::{
+        code_analysis::extract_routes,
         command_line::{
-            is_code_safe, read_template_contents, save_api_endpoints, save_backend_code,
-            PrintCommand, CODE_TEMPLATE_PATH, EXEC_MAIN_PATH, WS_PROJECT_PATH,
+            read_template_contents, restore_backend_code, save_backend_code, PrintCommand,
+            CODE_TEMPLATE_PATH,
+        },
+        general::{
+            ai_task_request, ai_task_request_decoded, ai_task_request_files, fetch_status_and_body,
+            http_client,
+        },
+        source_files::SourceFiles,
+        workspace::{build_workspace, run_workspace_server, stop_workspace_server},
+        workspace_history::{record_iteration, record_unbuilt},
+    },
+    models::{
+        agent_basic::basic_agent::{AgentState, BasicAgent},
+        general::{
+            agent_error::AgentError,
+            api_route::HttpMethod,
+            approval::{require_approval, ApprovalRequest},
         },
-        general::{ai_task_request, ai_task_request_decoded, check_status_code},
     },
-    models::agent_basic::basic_agent::{AgentState, BasicAgent},
 };
 
-use super::agent_traits::{FactSheet, RouteObject, SpecialFunctions};
+use super::{
+    agent_code_reviewer::AgentCodeReviewer,
+    agent_context::{AgentContext, CodeEditMode},
+    agent_database::INITIAL_MIGRATION_FILE,
+    agent_security::AgentSecurityReviewer,
+    agent_traits::{FactSheet, FactSheetField, RouteObject, SpecialFunctions},
+};
+
+type AiFunction = for<'a> fn(&'a str) -> &'static str;
+
+// compile errors and failing endpoints are retried independently of each other
+const MAX_COMPILE_BUG_RETRIES: i8 = 2;
+const MAX_RUNTIME_BUG_RETRIES: i8 = 2;
+// how much evidence of a failing endpoint is handed back to the LLM
+const RESPONSE_BODY_EXCERPT_CHARS: usize = 300;
+const SERVER_LOG_TAIL_LINES: usize = 20;
+// edits aren't code, only the request for whole files gets this reminder
+const CODE_ONLY: &str = "THIS FUNCTION ONLY OUTPUTS CODE. JUST OUTPUT THE CODE.";
+
+// The versions of the code a roll back can go to
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum KnownGood {
+    // for compile errors, any code that built
+    Built,
+    // for failing endpoints, the code that built and passed them
+    Checked,
+}
+
+impl KnownGood {
+    fn description(self) -> &'static str {
+        match self {
+            Self::Built => "the last code that built",
+            Self::Checked => "the last code that passed its checks",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct EndpointFailure {
+    route: String,
+    expected_status: u16,
+    // None when the request itself could not be completed
+    actual_status: Option<u16>,
+    body_excerpt: String,
+}
 
 #[derive(Debug)]
 pub struct AgentBackendDeveloper {
     attributes: BasicAgent,
+    context: AgentContext,
+    // what the approval policy saw last, the next approval shows the changes since
+    approved_code: Option<SourceFiles>,
+    // the ai_function that wrote the current code, for the workspace history
+    changed_by: String,
     bug_errors: Option<String>,
+    // compile errors found in a row
     bug_count: i8,
+    // functional test failures found in a row
+    runtime_bug_count: i8,
+    // the fact sheet as of the last code that built, and that also passed the endpoint checks
+    last_built: Option<FactSheet>,
+    last_checked: Option<FactSheet>,
+    // set when the fixes ran out and the agent finished on the known-good code instead
+    degraded: Option<String>,
 }
 
 impl AgentBackendDeveloper {
-    pub fn new() -> Self {
+    pub fn new(context: AgentContext) -> Self {
         let attributes = BasicAgent {
             objective: "Develops backend code for webserver and the server database".to_string(),
             position: "Backend Developer".to_string(),
@@ -42,163 +110,451 @@ impl AgentBackendDeveloper {
 
         Self {
             attributes,
+            context,
+            approved_code: None,
+            changed_by: String::new(),
             bug_errors: None,
             bug_count: 0,
+            runtime_bug_count: 0,
+            last_built: None,
+            last_checked: None,
+            degraded: None,
         }
     }
 
-    async fn call_initial_backend_code(&mut self, fact_sheet: &mut FactSheet) {
+    // Start straight from the bug fixing step, for bugs found by other agents (e.g. failing tests).
+    // The code they tested passed the checks of this agent, fixes that don't work go back to it
+    pub fn with_bug_report(
+        context: AgentContext,
+        bug_errors: String,
+        fact_sheet: &FactSheet,
+    ) -> Self {
+        let mut agent = Self::new(context);
+        agent.attributes.state = AgentState::Working;
+        agent.bug_errors = Some(bug_errors);
+        agent.last_built = Some(fact_sheet.clone());
+        agent.last_checked = Some(fact_sheet.clone());
+        agent
+    }
+
+    async fn call_initial_backend_code(
+        &mut self,
+        fact_sheet: &mut FactSheet,
+    ) -> Result<(), AgentError> {
         // Read the code template contents
-        let code_template_string = read_template_contents(CODE_TEMPLATE_PATH);
+        let code_template_string = read_template_contents(CODE_TEMPLATE_PATH)?;
 
         // Concatenate instruction
-        let msg_context = format!(
+        let mut msg_context = format!(
             "CODE TEMPLATE: {}\n PROJECT DESCRIPTION: {}\n",
             code_template_string, fact_sheet.project_description
         );
+        if let Some(database_schema) = &fact_sheet.database_schema {
+            msg_context.push_str(&format!(
+                "DATABASE_SCHEMA: {}\n MIGRATION_FILE: migrations/{}\n",
+                serde_json::to_string(database_schema)
+                    .map_err(AgentError::encode("database schema"))?,
+                INITIAL_MIGRATION_FILE
+            ));
+        }
 
-        let ai_response: String = ai_task_request(
+        // the template is what main.rs starts out as
+        let backend_code = ai_task_request_files(
+            self.context.provider.as_ref(),
             msg_context,
+            &SourceFiles::from_main(code_template_string),
             &self.attributes.position,
             get_function_string!(print_backend_webserver_code),
             print_backend_webserver_code,
         )
-        .await;
+        .await?;
 
         // save code on disk in the other locally stored directory
-        save_backend_code(&ai_response);
+        save_backend_code(&backend_code)?;
         // and also save this in memory
-        fact_sheet.backend_code = Some(ai_response);
+        fact_sheet.backend_code = Some(backend_code);
+        self.changed_by = get_function_string!(print_backend_webserver_code).to_string();
+        Ok(())
     }
 
-    async fn call_improved_backend_code(&mut self, fact_sheet: &mut FactSheet) {
-        let msg_context = format!(
-            "CODE TEMPLATE: {:?}\n PROJECT_DESCRIPTION: {:?}\n",
-            fact_sheet.backend_code, fact_sheet
+    async fn call_improved_backend_code(
+        &mut self,
+        fact_sheet: &mut FactSheet,
+    ) -> Result<(), AgentError> {
+        let backend_code = fact_sheet.backend_code.clone().unwrap_or_default();
+        let mut msg_context = format!(
+            "CODE_INPUT: {}\n PROJECT_DESCRIPTION: {}\n EXTERNAL_URLS: {:?}\n CODE_REVIEW: {}\n",
+            backend_code.render(),
+            fact_sheet.project_description,
+            fact_sheet.external_urls,
+            serde_json::to_string(&fact_sheet.code_review.clone().unwrap_or_default())
+                .map_err(AgentError::encode("code review"))?
         );
+        // the improved code keeps the persistence the initial code was written with
+        if let Some(database_schema) = &fact_sheet.database_schema {
+            msg_context.push_str(&format!(
+                "DATABASE_SCHEMA: {}\n MIGRATION_FILE: migrations/{}\n",
+                serde_json::to_string(database_schema)
+                    .map_err(AgentError::encode("database schema"))?,
+                INITIAL_MIGRATION_FILE
+            ));
+        }
 
-        let ai_response: String = ai_task_request(
-            msg_context,
-            &self.attributes.position,
-            get_function_string!(print_improved_webserver_code),
-            print_improved_webserver_code,
-        )
-        .await;
+        let (backend_code, changed_by) = self
+            .request_code_change(
+                &backend_code,
+                &backend_code,
+                msg_context,
+                "",
+                (
+                    get_function_string!(print_improved_webserver_code_edits),
+                    print_improved_webserver_code_edits,
+                ),
+                (
+                    get_function_string!(print_improved_webserver_code),
+                    print_improved_webserver_code,
+                ),
+            )
+            .await?;
 
         // save code on disk in the other locally stored directory
-        save_backend_code(&ai_response);
+        save_backend_code(&backend_code)?;
         // and also save this in memory
-        fact_sheet.backend_code = Some(ai_response);
+        fact_sheet.backend_code = Some(backend_code);
+        self.changed_by = changed_by.to_string();
+        Ok(())
     }
 
-    async fn call_fix_code_bugs(&mut self, fact_sheet: &mut FactSheet) {
+    async fn call_fix_code_bugs(&mut self, fact_sheet: &mut FactSheet) -> Result<(), AgentError> {
+        let backend_code = fact_sheet.backend_code.clone().unwrap_or_default();
+        let bug_errors = self.bug_errors.clone().unwrap_or_default();
+        // only the files the errors point to, fixing one module shouldn't cost all of them
+        let broken_code = backend_code.referenced_by(&bug_errors);
         let msg_context = format!(
-            "BROKEN_CODE: {:?}\n ERROR_BUGS: {:?}\n
-            THIS FUNCTION ONLY OUTPUTS CODE. JUST OUTPUT THE CODE.",
-            fact_sheet.backend_code, self.bug_errors
+            "BROKEN_CODE: {}\n ERROR_BUGS: {}\n",
+            broken_code.render(),
+            bug_errors
         );
 
-        let ai_response: String = ai_task_request(
-            msg_context,
+        let (backend_code, changed_by) = self
+            .request_code_change(
+                &backend_code,
+                &broken_code,
+                msg_context,
+                CODE_ONLY,
+                (
+                    get_function_string!(print_fixed_code_edits),
+                    print_fixed_code_edits,
+                ),
+                (get_function_string!(print_fixed_code), print_fixed_code),
+            )
+            .await?;
+
+        // save code on disk in the other locally stored directory
+        save_backend_code(&backend_code)?;
+        // and also save this in memory
+        fact_sheet.backend_code = Some(backend_code);
+        self.changed_by = changed_by.to_string();
+        Ok(())
+    }
+
+    // The changed backend files and the ai_function that changed them: edits applied to them when
+    // patching, otherwise, or when the edits conflict with the code, the files printed in full
+    // replace their old versions. `sent` are the files in `msg_context`, `full_code_note` only
+    // goes with the request for full files
+    async fn request_code_change(
+        &self,
+        backend_code: &SourceFiles,
+        sent: &SourceFiles,
+        msg_context: String,
+        full_code_note: &str,
+        (patch_operation, patch_function): (&'static str, AiFunction),
+        (operation, function): (&'static str, AiFunction),
+    ) -> Result<(SourceFiles, &'static str), AgentError> {
+        if self.context.code_edits == CodeEditMode::Patch && !backend_code.is_empty() {
+            let patch = ai_task_request(
+                self.context.provider.as_ref(),
+                msg_context.clone(),
+                &self.attributes.position,
+                patch_operation,
+                patch_function,
+            )
+            .await?;
+            match backend_code.patched(&patch, sent) {
+                Ok(patched_code) => return Ok((patched_code, patch_operation)),
+                Err(e) => PrintCommand::Issue.print_agent_message(
+                    self.attributes.position.as_str(),
+                    format!(
+                        "Could not apply the edits ({}), regenerating the code...",
+                        e
+                    )
+                    .as_str(),
+                ),
+            }
+        }
+
+        let changed_files = ai_task_request_files(
+            self.context.provider.as_ref(),
+            format!("{}{}", msg_context, full_code_note),
+            sent,
             &self.attributes.position,
-            get_function_string!(print_fixed_code),
-            print_fixed_code,
+            operation,
+            function,
+        )
+        .await?;
+        let mut changed_code = backend_code.clone();
+        changed_code.merge(changed_files);
+        Ok((changed_code, operation))
+    }
+
+    // Out of fixes: finish degraded on the known-good code when it still builds, an error-free
+    // server with less in it beats none. Without one, the agent fails with `error`
+    async fn roll_back(
+        &mut self,
+        fact_sheet: &mut FactSheet,
+        target: KnownGood,
+        reason: &str,
+        error: AgentError,
+    ) -> Result<(), AgentError> {
+        let known_good = match target {
+            KnownGood::Built => self.last_built.clone(),
+            KnownGood::Checked => self.last_checked.clone(),
+        };
+        let Some(known_good) = known_good else {
+            return Err(error);
+        };
+        PrintCommand::Issue.print_agent_message(
+            self.attributes.position.as_str(),
+            format!("Rolling back to {}...", target.description()).as_str(),
+        );
+
+        restore_backend_code(
+            &fact_sheet.backend_code.clone().unwrap_or_default(),
+            &known_good.backend_code.clone().unwrap_or_default(),
+        )?;
+        let build_backend_server = build_workspace(&self.context.sandbox).await?;
+        record_iteration(
+            &self.attributes.position,
+            &format!("rollback to {}", target.description()),
+            &build_backend_server,
         )
         .await;
+        if !build_backend_server.status.success() {
+            return Err(error);
+        }
 
-        // save code on disk in the other locally stored directory
-        save_backend_code(&ai_response);
-        // and also save this in memoryprint_fixed_code
-        fact_sheet.backend_code = Some(ai_response);
+        for field in self.writes() {
+            fact_sheet.merge_field(&known_good, field);
+        }
+        self.degraded = Some(format!(
+            "{}, rolled back to {}",
+            reason,
+            target.description()
+        ));
+        self.attributes.state = AgentState::Finished;
+        Ok(())
     }
 
-    async fn call_extract_rest_api_endpoints(&self) -> String {
-        // get stringified code from our main.rs template file
-        // faster and cheaper to get it from the localfile than going
-        // through asking LLM for code
-        let backend_code = read_template_contents(EXEC_MAIN_PATH);
-        let msg_context = format!("CODE_INPUT: {}", backend_code);
-        let ai_response: String = ai_task_request(
+    async fn call_extract_rest_api_endpoints(
+        &self,
+        backend_code: &SourceFiles,
+    ) -> Result<Vec<RouteObject>, AgentError> {
+        // routes are literally written in the code, so try reading them ourselves first
+        match extract_routes(backend_code) {
+            Ok(api_endpoints) => return Ok(api_endpoints),
+            Err(e) => PrintCommand::Issue.print_agent_message(
+                self.attributes.position.as_str(),
+                format!(
+                    "Could not extract endpoints from code ({}), asking LLM...",
+                    e
+                )
+                .as_str(),
+            ),
+        }
+
+        let msg_context = format!("CODE_INPUT: {}", backend_code.render());
+        ai_task_request_decoded::<Vec<RouteObject>>(
+            self.context.provider.as_ref(),
             msg_context,
             &self.attributes.position,
             get_function_string!(print_rest_api_endpoints),
             print_rest_api_endpoints,
         )
-        .await;
-        ai_response
+        .await
     }
 }
 
+// Keep only the first `max_chars` characters of a response body
+fn excerpt(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut shortened: String = text.chars().take(max_chars).collect();
+    shortened.push_str("...");
+    shortened
+}
+
+// Keep only the last `max_lines` lines of the server output
+fn log_tail(log: &str, max_lines: usize) -> String {
+    let lines: Vec<&str> = log.lines().collect();
+    let start = lines.len().saturating_sub(max_lines);
+    lines[start..].join("\n")
+}
+
+// Turn failing endpoint checks into an ERROR_BUGS report the LLM can act on
+fn describe_endpoint_failures(failures: &[EndpointFailure], server_log_tail: &str) -> String {
+    let mut report = String::from("FAILING ENDPOINTS:\n");
+    for failure in failures {
+        let actual_status = match failure.actual_status {
+            Some(status_code) => status_code.to_string(),
+            None => "no response".to_string(),
+        };
+        report.push_str(&format!(
+            "- GET {}: expected status {}, got {}. Body: {}\n",
+            failure.route, failure.expected_status, actual_status, failure.body_excerpt
+        ));
+    }
+    report.push_str(&format!("SERVER LOG TAIL:\n{}", server_log_tail));
+    report
+}
+
 #[async_trait]
 impl SpecialFunctions for AgentBackendDeveloper {
     fn get_attributes_from_agent(&self) -> &BasicAgent {
         &self.attributes
     }
 
-    async fn execute(
-        &mut self,
-        fact_sheet: &mut FactSheet,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    fn reads(&self) -> Vec<FactSheetField> {
+        vec![
+            FactSheetField::ProjectDescription,
+            FactSheetField::ExternalUrls,
+            FactSheetField::DatabaseSchema,
+        ]
+    }
+
+    fn writes(&self) -> Vec<FactSheetField> {
+        vec![
+            FactSheetField::BackendCode,
+            FactSheetField::ApiEndpointSchema,
+            FactSheetField::SecurityReport,
+            FactSheetField::CodeReview,
+        ]
+    }
+
+    async fn execute(&mut self, fact_sheet: &mut FactSheet) -> Result<(), AgentError> {
+        if self.approved_code.is_none() {
+            self.approved_code = fact_sheet.backend_code.clone();
+        }
+
         while self.attributes.state != AgentState::Finished {
             match self.attributes.state {
                 AgentState::Discovering => {
-                    self.call_initial_backend_code(fact_sheet).await;
+                    self.call_initial_backend_code(fact_sheet).await?;
                     self.attributes.state = AgentState::Working;
                     continue;
                 }
                 AgentState::Working => {
-                    if self.bug_count == 0 {
-                        self.call_improved_backend_code(fact_sheet).await;
+                    if self.bug_errors.is_none() {
+                        // the improvement pass works from a concrete review, not a blind rewrite
+                        AgentCodeReviewer::new(self.context.clone())
+                            .execute(fact_sheet)
+                            .await?;
+                        self.call_improved_backend_code(fact_sheet).await?;
                     } else {
-                        self.call_fix_code_bugs(fact_sheet).await;
+                        self.call_fix_code_bugs(fact_sheet).await?;
                     }
                     self.attributes.state = AgentState::Validation;
                     continue;
                 }
                 AgentState::Validation => {
+                    // Guard: static review first, a blocking finding stops the project
+                    record_unbuilt(
+                        &self.attributes.position,
+                        &self.changed_by,
+                        AgentSecurityReviewer::with_policy(self.context.security)
+                            .execute(fact_sheet)
+                            .await,
+                    )
+                    .await?;
+                    let security_report = fact_sheet.security_report.clone().unwrap_or_default();
+
                     // Guard: ensure AI safety
                     PrintCommand::UnitTest.print_agent_message(
                         self.attributes.position.as_str(),
-                        "Backend Code Unit Testing: Requesting user input",
+                        "Backend Code Unit Testing: Requesting approval",
                     );
 
-                    if !is_code_safe() {
-                        println!("Exeting because AI generated code was deemed not safe.");
+                    // the code is already on disk, a version turned down is kept in the history too
+                    let edited_code = record_unbuilt(
+                        &self.attributes.position,
+                        &self.changed_by,
+                        require_approval(
+                            self.context.approval.as_ref(),
+                            &ApprovalRequest {
+                                agent_position: &self.attributes.position,
+                                previous_code: self
+                                    .approved_code
+                                    .as_ref()
+                                    .map(SourceFiles::render)
+                                    .as_deref(),
+                                code: &fact_sheet.backend_code.clone().unwrap_or_default().render(),
+                                security_report: &security_report,
+                            },
+                        ),
+                    )
+                    .await?;
+                    if let Some(code) = edited_code {
+                        let backend_code = SourceFiles::parse(
+                            &code,
+                            &fact_sheet.backend_code.clone().unwrap_or_default(),
+                        )?;
+                        save_backend_code(&backend_code)?;
+                        fact_sheet.backend_code = Some(backend_code);
+                        self.changed_by.push_str(", edited by hand");
                     }
+                    self.approved_code = fact_sheet.backend_code.clone();
 
                     PrintCommand::UnitTest.print_agent_message(
                         self.attributes.position.as_str(),
                         "Backend Code Unit Testing: building project...",
                     );
 
-                    let build_backend_server = Command::new("cargo")
-                        .arg("build")
-                        .current_dir(WS_PROJECT_PATH)
-                        // return information back to us
-                        .stdout(Stdio::piped())
-                        .stderr(Stdio::piped())
-                        .output()
-                        .expect("Failed to build backend application");
+                    let build_backend_server = build_workspace(&self.context.sandbox).await?;
+                    record_iteration(
+                        &self.attributes.position,
+                        &self.changed_by,
+                        &build_backend_server,
+                    )
+                    .await;
 
                     if build_backend_server.status.success() {
                         self.bug_count = 0;
+                        self.bug_errors = None;
 
                         PrintCommand::UnitTest.print_agent_message(
                             self.attributes.position.as_str(),
                             "Backend Code Unit Testing: Test server build succcessful...",
                         );
                     } else {
-                        let error_string = String::from_utf8(build_backend_server.stderr).unwrap();
+                        let error_string =
+                            String::from_utf8_lossy(&build_backend_server.stderr).to_string();
                         self.bug_count += 1;
-                        self.bug_errors = Some(error_string);
+                        self.bug_errors = Some(error_string.clone());
 
                         // Exit if too many bugs
-                        if self.bug_count > 2 {
+                        if self.bug_count > MAX_COMPILE_BUG_RETRIES {
                             PrintCommand::Issue.print_agent_message(
                                 self.attributes.position.as_str(),
                                 "Backend Code Unit Testing: Too many bugs found in code...",
                             );
-                            panic!("Error: too many bugs...");
+                            return self
+                                .roll_back(
+                                    fact_sheet,
+                                    KnownGood::Built,
+                                    "compile errors remained after the last fix",
+                                    AgentError::Build(error_string),
+                                )
+                                .await;
                         }
 
                         // Pass back for rework
@@ -206,35 +562,32 @@ impl SpecialFunctions for AgentBackendDeveloper {
                         continue;
                     }
 
-                    let api_endpoints_str = self.call_extract_rest_api_endpoints().await;
                     // Extract and test API endpoints
-                    let api_endpoints: Vec<RouteObject> =
-                        serde_json::from_str(api_endpoints_str.as_str())
-                            .expect("Failed to deserialize endpoints into RouteObjects");
+                    let api_endpoints: Vec<RouteObject> = self
+                        .call_extract_rest_api_endpoints(
+                            &fact_sheet.backend_code.clone().unwrap_or_default(),
+                        )
+                        .await?;
 
                     let static_endpoints = api_endpoints
                         .iter()
                         .filter(|&route_object| {
-                            route_object.method == "get" && route_object.is_route_dynamic == "false"
+                            route_object.method == HttpMethod::Get && !route_object.is_route_dynamic
                         })
                         .cloned()
                         .collect::<Vec<RouteObject>>();
 
-                    fact_sheet.api_endpoint_schema.clone_from(&static_endpoints);
+                    fact_sheet.api_endpoint_schema.clone_from(&api_endpoints);
+                    // it built, with the endpoints extracted from this very code
+                    self.last_built = Some(fact_sheet.clone());
 
                     PrintCommand::UnitTest.print_agent_message(
                         self.attributes.position.as_str(),
                         "Backend Code Unit Testing: Starting Web Server...",
                     );
 
-                    let mut run_backend_server = Command::new("cargo")
-                        .arg("run")
-                        .current_dir(WS_PROJECT_PATH)
-                        // return information back to us
-                        .stdout(Stdio::piped())
-                        .stderr(Stdio::piped())
-                        .spawn()
-                        .expect("Failed to run backend application");
+                    let client = http_client(Duration::from_secs(5))?;
+                    let run_backend_server = run_workspace_server(&self.context.sandbox).await?;
 
                     PrintCommand::UnitTest.print_agent_message(
                         self.attributes.position.as_str(),
@@ -243,6 +596,8 @@ impl SpecialFunctions for AgentBackendDeveloper {
 
                     time::sleep(Duration::from_secs(5)).await;
 
+                    let mut endpoint_failures: Vec<EndpointFailure> = vec![];
+
                     for endpoint in static_endpoints {
                         let testing_msg = format!("Testing endpoint '{}'...", endpoint.route);
 
@@ -253,14 +608,9 @@ impl SpecialFunctions for AgentBackendDeveloper {
 
                         let url = format!("http://localhost:8080{}", endpoint.route);
 
-                        let client = Client::builder()
-                            .timeout(Duration::from_secs(5))
-                            .build()
-                            .unwrap();
-
                         // Print out the result of testing
-                        match check_status_code(&client, &url).await {
-                            Ok(status_code) => {
+                        match fetch_status_and_body(&client, &url).await {
+                            Ok((status_code, body)) => {
                                 if status_code != 200 {
                                     PrintCommand::Issue.print_agent_message(
                                         self.attributes.position.as_str(),
@@ -270,6 +620,12 @@ impl SpecialFunctions for AgentBackendDeveloper {
                                         )
                                         .as_str(),
                                     );
+                                    endpoint_failures.push(EndpointFailure {
+                                        route: endpoint.route.clone(),
+                                        expected_status: 200,
+                                        actual_status: Some(status_code),
+                                        body_excerpt: excerpt(&body, RESPONSE_BODY_EXCERPT_CHARS),
+                                    });
                                 }
                             }
                             Err(e) => {
@@ -277,20 +633,62 @@ impl SpecialFunctions for AgentBackendDeveloper {
                                     self.attributes.position.as_str(),
                                     format!("Error checking backend {}", e).as_str(),
                                 );
+                                endpoint_failures.push(EndpointFailure {
+                                    route: endpoint.route.clone(),
+                                    expected_status: 200,
+                                    actual_status: None,
+                                    body_excerpt: e.to_string(),
+                                });
                             }
                         }
                     }
-                    save_api_endpoints(&api_endpoints_str);
+
+                    // collect whatever the server logged while being tested
+                    let server_output =
+                        stop_workspace_server(&self.context.sandbox, run_backend_server).await?;
+
+                    if !endpoint_failures.is_empty() {
+                        self.runtime_bug_count += 1;
+                        let server_log = format!(
+                            "{}{}",
+                            String::from_utf8_lossy(&server_output.stdout),
+                            String::from_utf8_lossy(&server_output.stderr)
+                        );
+                        let bug_report = describe_endpoint_failures(
+                            &endpoint_failures,
+                            &log_tail(&server_log, SERVER_LOG_TAIL_LINES),
+                        );
+                        self.bug_errors = Some(bug_report.clone());
+
+                        // Exit if too many failing endpoints
+                        if self.runtime_bug_count > MAX_RUNTIME_BUG_RETRIES {
+                            PrintCommand::Issue.print_agent_message(
+                                self.attributes.position.as_str(),
+                                "Backend Code Unit Testing: Too many failing endpoints...",
+                            );
+                            return self
+                                .roll_back(
+                                    fact_sheet,
+                                    KnownGood::Checked,
+                                    "endpoints still failed after the last fix",
+                                    AgentError::Validation(bug_report),
+                                )
+                                .await;
+                        }
+
+                        // Pass back for rework
+                        self.attributes.state = AgentState::Working;
+                        continue;
+                    }
+
+                    self.runtime_bug_count = 0;
 
                     PrintCommand::UnitTest.print_agent_message(
                         self.attributes.position.as_str(),
                         "Backend testing complete...",
                     );
 
-                    run_backend_server
-                        .kill()
-                        .expect("Failed to kill backend web server");
-
+                    self.last_checked = Some(fact_sheet.clone());
                     self.attributes.state = AgentState::Finished;
                 }
                 _ => self.attributes.state = AgentState::Finished,
@@ -298,17 +696,190 @@ impl SpecialFunctions for AgentBackendDeveloper {
         }
         Ok(())
     }
+
+    fn degraded(&self) -> Option<String> {
+        self.degraded.clone()
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    #[cfg(feature = "openai-coverage")]
+    use std::sync::{Arc, Mutex};
+
     use super::*;
+    use crate::{
+        apis::call_requests::{CallGptError, LlmProvider},
+        helpers::source_files::MAIN_FILE,
+        models::general::{approval::AlwaysAllow, llm::Message},
+    };
+
+    #[test]
+    fn test_log_tail_keeps_last_lines() {
+        let log = "line 1\nline 2\nline 3\nline 4";
+        assert_eq!(log_tail(log, 2), "line 3\nline 4");
+        assert_eq!(log_tail(log, 10), log);
+    }
+
+    #[test]
+    fn test_describe_endpoint_failures() {
+        let failures = vec![
+            EndpointFailure {
+                route: "/items".to_string(),
+                expected_status: 200,
+                actual_status: Some(500),
+                body_excerpt: excerpt("database file missing", 8),
+            },
+            EndpointFailure {
+                route: "/health".to_string(),
+                expected_status: 200,
+                actual_status: None,
+                body_excerpt: "connection refused".to_string(),
+            },
+        ];
+
+        let report = describe_endpoint_failures(&failures, "thread 'main' panicked");
+
+        assert!(report.contains("- GET /items: expected status 200, got 500. Body: database..."));
+        assert!(report.contains("- GET /health: expected status 200, got no response."));
+        assert!(report.ends_with("SERVER LOG TAIL:\nthread 'main' panicked"));
+    }
+
+    // Answers the prompts in order, one answer each
+    #[derive(Debug)]
+    struct ScriptedProvider(Mutex<Vec<&'static str>>);
+
+    #[async_trait]
+    impl LlmProvider for ScriptedProvider {
+        async fn call(&self, _messages: Vec<Message>) -> Result<String, CallGptError> {
+            let mut answers = self.0.lock().unwrap();
+            Ok(answers.remove(0).to_string())
+        }
+    }
+
+    fn patching_agent(answers: Vec<&'static str>) -> AgentBackendDeveloper {
+        let mut context = AgentContext::new(
+            Arc::new(ScriptedProvider(Mutex::new(answers))),
+            Arc::new(AlwaysAllow),
+        );
+        context.code_edits = CodeEditMode::Patch;
+        AgentBackendDeveloper::new(context)
+    }
+
+    #[tokio::test]
+    async fn test_patch_edits_fall_back_to_regeneration() {
+        // a fact sheet from before the backend was split into files
+        let fact_sheet: FactSheet = serde_json::from_value(serde_json::json!({
+            "project_description": "build a website that tracks crypto prices",
+            "project_scope": null,
+            "external_urls": [],
+            "backend_code": "mod routes;\n\nfn main() {\n    println!(\"hello\");\n}\n",
+            "api_endpoint_schema": []
+        }))
+        .unwrap();
+        let backend_code = fact_sheet.backend_code.unwrap();
+        let functions = (
+            (
+                get_function_string!(print_fixed_code_edits),
+                print_fixed_code_edits as AiFunction,
+            ),
+            (
+                get_function_string!(print_fixed_code),
+                print_fixed_code as AiFunction,
+            ),
+        );
+
+        let agent = patching_agent(vec![
+            "// FILE: src/main.rs\n<<<<<<< SEARCH\n    println!(\"hello\");\n=======\n    println!(\"hi\");\n>>>>>>> REPLACE\n\
+             // FILE: src/routes.rs\n<<<<<<< SEARCH\n=======\npub fn health() {}\n>>>>>>> REPLACE",
+        ]);
+        let (backend_code, changed_by) = agent
+            .request_code_change(
+                &backend_code,
+                &backend_code,
+                String::new(),
+                "",
+                functions.0,
+                functions.1,
+            )
+            .await
+            .unwrap();
+        assert_eq!(changed_by, "print_fixed_code_edits");
+        assert_eq!(
+            backend_code.get(MAIN_FILE),
+            Some("mod routes;\n\nfn main() {\n    println!(\"hi\");\n}\n")
+        );
+        assert_eq!(
+            backend_code.get("src/routes.rs"),
+            Some("pub fn health() {}\n")
+        );
+
+        // the edits don't match the code, so the files are printed in full,
+        // the ones that aren't keep their contents
+        let agent = patching_agent(vec![
+            "<<<<<<< SEARCH\n    println!(\"hello\");\n=======\n>>>>>>> REPLACE",
+            "// FILE: src/main.rs\nmod routes;\n\nfn main() {}\n",
+        ]);
+        let (backend_code, changed_by) = agent
+            .request_code_change(
+                &backend_code,
+                &backend_code,
+                String::new(),
+                "",
+                functions.0,
+                functions.1,
+            )
+            .await
+            .unwrap();
+        assert_eq!(changed_by, "print_fixed_code");
+        assert_eq!(
+            backend_code.get(MAIN_FILE),
+            Some("mod routes;\n\nfn main() {}\n")
+        );
+        assert_eq!(
+            backend_code.get("src/routes.rs"),
+            Some("pub fn health() {}\n")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_roll_back_needs_known_good_code() {
+        // a fresh backend has no version that built or passed the checks yet, so it fails as before
+        let mut agent = patching_agent(vec![]);
+        let mut fact_sheet: FactSheet = serde_json::from_value(serde_json::json!({
+            "project_description": "build a website",
+            "project_scope": null,
+            "external_urls": [],
+            "backend_code": "fn main() {\n",
+            "api_endpoint_schema": []
+        }))
+        .unwrap();
+
+        let result = agent
+            .roll_back(
+                &mut fact_sheet,
+                KnownGood::Built,
+                "compile errors remained after the last fix",
+                AgentError::Build("unclosed delimiter".to_string()),
+            )
+            .await;
+        assert!(matches!(result, Err(AgentError::Build(_))));
+        let result = agent
+            .roll_back(
+                &mut fact_sheet,
+                KnownGood::Checked,
+                "endpoints still failed after the last fix",
+                AgentError::Validation("GET /items: 500".to_string()),
+            )
+            .await;
+        assert!(matches!(result, Err(AgentError::Validation(_))));
+        assert_eq!(agent.degraded(), None);
+        assert_ne!(agent.attributes.state, AgentState::Finished);
+    }
 
     #[tokio::test]
     #[cfg(feature = "openai-coverage")]
     async fn test_writing_backend_code() {
-        let mut agent = AgentBackendDeveloper::new();
+        let mut agent = AgentBackendDeveloper::new(AgentContext::default());
         let factsheet_string: &str = r#"
       {
         "project_description": "build a website that fetches and tracks fitness progress with timezone information",
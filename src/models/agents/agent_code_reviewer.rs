@@ -0,0 +1,168 @@
+use async_trait::async_trait;
+
+use crate::{
+    ai_functions::aifunc_review::print_code_review,
+    helpers::{
+        command_line::{save_workspace_file, PrintCommand},
+        general::ai_task_request_decoded,
+    },
+    models::{
+        agent_basic::basic_agent::{AgentState, BasicAgent},
+        general::agent_error::AgentError,
+    },
+};
+
+use super::{
+    agent_context::AgentContext,
+    agent_security::url_origin,
+    agent_traits::{CodeReview, FactSheet, FactSheetField, SpecialFunctions},
+};
+
+const CODE_REVIEW_FILE: &str = "CODE_REVIEW.md";
+
+#[derive(Debug)]
+pub struct AgentCodeReviewer {
+    attributes: BasicAgent,
+    context: AgentContext,
+}
+
+impl AgentCodeReviewer {
+    pub fn new(context: AgentContext) -> Self {
+        let attributes = BasicAgent {
+            objective: "Reviews the backend code against the project description".to_string(),
+            position: "Code Reviewer".to_string(),
+            state: AgentState::Discovering,
+            memory: vec![],
+        };
+
+        Self {
+            attributes,
+            context,
+        }
+    }
+
+    async fn call_code_review(&self, fact_sheet: &FactSheet) -> Result<CodeReview, AgentError> {
+        let msg_context = format!(
+            "PROJECT_DESCRIPTION: {}\n CODE_INPUT: {}\n",
+            fact_sheet.project_description,
+            fact_sheet.backend_code.clone().unwrap_or_default().render()
+        );
+
+        ai_task_request_decoded::<CodeReview>(
+            self.context.provider.as_ref(),
+            msg_context,
+            &self.attributes.position,
+            get_function_string!(print_code_review),
+            print_code_review,
+        )
+        .await
+    }
+}
+
+// External urls the architect picked that the code never calls, neither fully nor by origin
+fn unused_external_urls(backend_code: &str, external_urls: &[String]) -> Vec<String> {
+    external_urls
+        .iter()
+        .filter(|url| !backend_code.contains(url_origin(url)))
+        .cloned()
+        .collect()
+}
+
+fn code_review_markdown(code_review: &CodeReview) -> String {
+    let sections = [
+        ("Missing requirements", &code_review.missing_requirements),
+        ("Error handling gaps", &code_review.error_handling_gaps),
+        ("Unused external urls", &code_review.unused_external_urls),
+    ];
+
+    let mut markdown = String::from("# Code review\n");
+    for (title, items) in sections {
+        markdown.push_str(&format!("\n## {}\n\n", title));
+        if items.is_empty() {
+            markdown.push_str("Nothing found.\n");
+        }
+        for item in items {
+            markdown.push_str(&format!("- {}\n", item));
+        }
+    }
+    markdown
+}
+
+#[async_trait]
+impl SpecialFunctions for AgentCodeReviewer {
+    fn get_attributes_from_agent(&self) -> &BasicAgent {
+        &self.attributes
+    }
+
+    fn reads(&self) -> Vec<FactSheetField> {
+        vec![
+            FactSheetField::ProjectDescription,
+            FactSheetField::ExternalUrls,
+            FactSheetField::BackendCode,
+        ]
+    }
+
+    fn writes(&self) -> Vec<FactSheetField> {
+        vec![FactSheetField::CodeReview]
+    }
+
+    async fn execute(&mut self, fact_sheet: &mut FactSheet) -> Result<(), AgentError> {
+        while self.attributes.state != AgentState::Finished {
+            match self.attributes.state {
+                AgentState::Discovering => {
+                    PrintCommand::AICall.print_agent_message(
+                        self.attributes.position.as_str(),
+                        "Reviewing backend code...",
+                    );
+                    let mut code_review = self.call_code_review(fact_sheet).await?;
+                    code_review.unused_external_urls = unused_external_urls(
+                        &fact_sheet.backend_code.clone().unwrap_or_default().render(),
+                        &fact_sheet.external_urls,
+                    );
+
+                    save_workspace_file(CODE_REVIEW_FILE, &code_review_markdown(&code_review))?;
+                    fact_sheet.code_review = Some(code_review);
+                    self.attributes.state = AgentState::Finished;
+                }
+                _ => self.attributes.state = AgentState::Finished,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unused_external_urls() {
+        let backend_code = r#"
+            let url = format!("{}/assets/{}", "https://api.coincap.io/v2", symbol);
+        "#;
+        let external_urls = vec![
+            "https://api.coincap.io/v2/assets".to_string(),
+            "https://worldtimeapi.org/api/timezone".to_string(),
+        ];
+
+        assert_eq!(
+            unused_external_urls(backend_code, &external_urls),
+            vec!["https://worldtimeapi.org/api/timezone"]
+        );
+    }
+
+    #[test]
+    fn test_code_review_markdown() {
+        let code_review: CodeReview =
+            serde_json::from_str(r#"{ "missing_requirements": ["No route to delete an item"] }"#)
+                .unwrap();
+
+        assert_eq!(
+            code_review_markdown(&code_review),
+            "# Code review\n\n\
+             ## Missing requirements\n\n- No route to delete an item\n\n\
+             ## Error handling gaps\n\nNothing found.\n\n\
+             ## Unused external urls\n\nNothing found.\n"
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use std::sync::Arc;
+
+use crate::{
+    apis::call_requests::{LlmProvider, OpenAiProvider},
+    helpers::sandbox::Sandbox,
+    models::general::approval::{ApprovalPolicy, InteractiveApproval},
+};
+
+use super::agent_security::SecurityPolicy;
+
+// How agents change code they wrote before
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CodeEditMode {
+    // the LLM prints the whole file again
+    #[default]
+    Regenerate,
+    // the LLM prints search/replace blocks or a unified diff, the whole file is only
+    // regenerated when those don't apply
+    Patch,
+}
+
+// The hooks agents share: who answers their prompts, which security findings stop the project,
+// who approves running generated code and the sandbox it then runs in. Cloning is cheap, every
+// agent keeps its own copy
+#[derive(Debug, Clone)]
+pub struct AgentContext {
+    pub provider: Arc<dyn LlmProvider>,
+    pub approval: Arc<dyn ApprovalPolicy>,
+    pub security: SecurityPolicy,
+    pub sandbox: Sandbox,
+    pub code_edits: CodeEditMode,
+}
+
+impl AgentContext {
+    pub fn new(provider: Arc<dyn LlmProvider>, approval: Arc<dyn ApprovalPolicy>) -> Self {
+        Self {
+            provider,
+            approval,
+            security: SecurityPolicy::default(),
+            sandbox: Sandbox::default(),
+            code_edits: CodeEditMode::default(),
+        }
+    }
+}
+
+// OpenAI with the keys from the environment, approval asked at the terminal, and the default sandbox
+impl Default for AgentContext {
+    fn default() -> Self {
+        Self::new(
+            Arc::new(OpenAiProvider::from_env()),
+            Arc::new(InteractiveApproval),
+        )
+    }
+}
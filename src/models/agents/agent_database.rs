@@ -0,0 +1,340 @@
+use async_trait::async_trait;
+use rusqlite::Connection;
+
+use crate::{
+    ai_functions::aifunc_database::print_database_schema,
+    helpers::{
+        command_line::{add_workspace_dependency, save_migration, PrintCommand},
+        general::ai_task_request_decoded,
+    },
+    models::{
+        agent_basic::basic_agent::{AgentState, BasicAgent},
+        general::agent_error::AgentError,
+    },
+};
+
+use super::{
+    agent_context::AgentContext,
+    agent_traits::{DatabaseSchema, FactSheet, FactSheetField, SpecialFunctions, TableSchema},
+};
+
+const MAX_SCHEMA_BUG_RETRIES: i8 = 2;
+pub const INITIAL_MIGRATION_FILE: &str = "0001_init.sql";
+const SQLITE_TYPES: [&str; 5] = ["INTEGER", "TEXT", "REAL", "BLOB", "NUMERIC"];
+
+#[derive(Debug)]
+pub struct AgentDatabaseEngineer {
+    attributes: BasicAgent,
+    context: AgentContext,
+    bug_errors: Option<String>,
+    bug_count: i8,
+}
+
+impl AgentDatabaseEngineer {
+    pub fn new(context: AgentContext) -> Self {
+        let attributes = BasicAgent {
+            objective: "Designs the database schema and SQLite migrations for CRUD websites"
+                .to_string(),
+            position: "Database Engineer".to_string(),
+            state: AgentState::Discovering,
+            memory: vec![],
+        };
+
+        Self {
+            attributes,
+            context,
+            bug_errors: None,
+            bug_count: 0,
+        }
+    }
+
+    async fn call_design_schema(&mut self, fact_sheet: &mut FactSheet) -> Result<(), AgentError> {
+        let msg_context = match &self.bug_errors {
+            Some(bug_errors) => format!(
+                "PROJECT_DESCRIPTION: {}\n PREVIOUS_SCHEMA: {:?}\n ERROR_BUGS: {}\n",
+                fact_sheet.project_description, fact_sheet.database_schema, bug_errors
+            ),
+            None => format!("PROJECT_DESCRIPTION: {}\n", fact_sheet.project_description),
+        };
+
+        let ai_response = ai_task_request_decoded::<DatabaseSchema>(
+            self.context.provider.as_ref(),
+            msg_context,
+            &self.attributes.position,
+            get_function_string!(print_database_schema),
+            print_database_schema,
+        )
+        .await?;
+
+        fact_sheet.database_schema = Some(ai_response);
+        Ok(())
+    }
+}
+
+// Everything in the schema that would produce broken or unsafe SQL
+fn schema_problems(schema: &DatabaseSchema) -> Vec<String> {
+    let mut problems: Vec<String> = vec![];
+
+    if schema.tables.is_empty() {
+        problems.push("The schema has no tables".to_string());
+    }
+    for table in &schema.tables {
+        if !is_identifier(&table.name) {
+            problems.push(format!("Invalid table name '{}'", table.name));
+        }
+        let primary_keys = table
+            .columns
+            .iter()
+            .filter(|column| column.is_primary_key)
+            .count();
+        if primary_keys != 1 {
+            problems.push(format!(
+                "Table '{}' has {} primary key columns instead of 1",
+                table.name, primary_keys
+            ));
+        }
+        for column in &table.columns {
+            if !is_identifier(&column.name) {
+                problems.push(format!(
+                    "Invalid column name '{}.{}'",
+                    table.name, column.name
+                ));
+            }
+            if !SQLITE_TYPES.contains(&column.sql_type.to_uppercase().as_str()) {
+                problems.push(format!(
+                    "Column '{}.{}' has unsupported type '{}'",
+                    table.name, column.name, column.sql_type
+                ));
+            }
+        }
+    }
+
+    problems
+}
+
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn create_table_sql(table: &TableSchema) -> String {
+    let columns: Vec<String> = table
+        .columns
+        .iter()
+        .map(|column| {
+            let mut definition = format!("    {} {}", column.name, column.sql_type.to_uppercase());
+            if column.is_primary_key {
+                definition.push_str(" PRIMARY KEY");
+            } else if !column.is_nullable {
+                definition.push_str(" NOT NULL");
+            }
+            definition
+        })
+        .collect();
+
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} (\n{}\n);\n",
+        table.name,
+        columns.join(",\n")
+    )
+}
+
+// The migration is generated from the schema rather than written by the LLM
+fn migration_sql(schema: &DatabaseSchema) -> String {
+    schema
+        .tables
+        .iter()
+        .map(create_table_sql)
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// Apply the migration to an in-memory SQLite database and check every table got created
+fn migration_problems(schema: &DatabaseSchema, sql: &str) -> Result<Vec<String>, AgentError> {
+    // not being able to open SQLite isn't a bug of the schema
+    let connection = Connection::open_in_memory()
+        .map_err(|e| AgentError::Config(format!("Failed to open SQLite: {}", e)))?;
+    if let Err(e) = connection.execute_batch(sql) {
+        return Ok(vec![format!("Migration failed: {}", e)]);
+    }
+
+    let created_tables = connection
+        .prepare("SELECT name FROM sqlite_master WHERE type = 'table'")
+        .and_then(|mut statement| {
+            statement
+                .query_map([], |row| row.get::<_, String>(0))?
+                .collect::<Result<Vec<String>, _>>()
+        })
+        .map_err(|e| AgentError::Config(format!("Failed to list SQLite tables: {}", e)))?;
+    let missing_tables: Vec<&str> = schema
+        .tables
+        .iter()
+        .map(|table| table.name.as_str())
+        .filter(|name| !created_tables.iter().any(|created| created == name))
+        .collect();
+    if missing_tables.is_empty() {
+        Ok(vec![])
+    } else {
+        Ok(vec![format!(
+            "Tables missing after migration: {}",
+            missing_tables.join(", ")
+        )])
+    }
+}
+
+#[async_trait]
+impl SpecialFunctions for AgentDatabaseEngineer {
+    fn get_attributes_from_agent(&self) -> &BasicAgent {
+        &self.attributes
+    }
+
+    fn reads(&self) -> Vec<FactSheetField> {
+        vec![
+            FactSheetField::ProjectDescription,
+            FactSheetField::ProjectScope,
+        ]
+    }
+
+    fn writes(&self) -> Vec<FactSheetField> {
+        vec![FactSheetField::DatabaseSchema]
+    }
+
+    async fn execute(&mut self, fact_sheet: &mut FactSheet) -> Result<(), AgentError> {
+        while self.attributes.state != AgentState::Finished {
+            match self.attributes.state {
+                AgentState::Discovering => {
+                    let is_crud_required = fact_sheet
+                        .project_scope
+                        .is_some_and(|project_scope| project_scope.is_crud_required);
+                    if !is_crud_required {
+                        PrintCommand::AICall.print_agent_message(
+                            self.attributes.position.as_str(),
+                            "No CRUD functionality required, skipping database design",
+                        );
+                        self.attributes.state = AgentState::Finished;
+                        continue;
+                    }
+                    self.attributes.state = AgentState::Working;
+                }
+                AgentState::Working => {
+                    self.call_design_schema(fact_sheet).await?;
+                    self.attributes.state = AgentState::Validation;
+                }
+                AgentState::Validation => {
+                    PrintCommand::UnitTest.print_agent_message(
+                        self.attributes.position.as_str(),
+                        "Database Unit Testing: running migrations against an in-memory database...",
+                    );
+
+                    let schema = fact_sheet.database_schema.as_ref().ok_or_else(|| {
+                        AgentError::Validation("No database schema to validate".to_string())
+                    })?;
+                    let mut problems = schema_problems(schema);
+                    let sql = migration_sql(schema);
+                    if problems.is_empty() {
+                        problems.extend(migration_problems(schema, &sql)?);
+                    }
+
+                    if problems.is_empty() {
+                        save_migration(INITIAL_MIGRATION_FILE, &sql)?;
+                        add_workspace_dependency(
+                            "rusqlite",
+                            r#"{ version = "0.29", features = ["bundled"] }"#,
+                        )?;
+                        PrintCommand::UnitTest.print_agent_message(
+                            self.attributes.position.as_str(),
+                            "Database testing complete...",
+                        );
+                        self.attributes.state = AgentState::Finished;
+                        continue;
+                    }
+
+                    self.bug_count += 1;
+                    self.bug_errors = Some(problems.join("\n"));
+
+                    // Exit if too many bugs
+                    if self.bug_count > MAX_SCHEMA_BUG_RETRIES {
+                        PrintCommand::Issue.print_agent_message(
+                            self.attributes.position.as_str(),
+                            "Database Unit Testing: Too many bugs found in schema...",
+                        );
+                        return Err(AgentError::Validation(format!(
+                            "too many database schema bugs: {}",
+                            problems.join("\n")
+                        )));
+                    }
+
+                    // Pass back for rework
+                    self.attributes.state = AgentState::Working;
+                }
+                _ => self.attributes.state = AgentState::Finished,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::agents::agent_traits::ColumnSchema;
+
+    fn column(name: &str, sql_type: &str, is_primary_key: bool) -> ColumnSchema {
+        ColumnSchema {
+            name: name.to_string(),
+            sql_type: sql_type.to_string(),
+            is_primary_key,
+            is_nullable: false,
+        }
+    }
+
+    fn dummy_schema() -> DatabaseSchema {
+        DatabaseSchema {
+            tables: vec![TableSchema {
+                name: "item".to_string(),
+                columns: vec![column("id", "integer", true), column("name", "TEXT", false)],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_migration_sql() {
+        assert_eq!(
+            migration_sql(&dummy_schema()),
+            "CREATE TABLE IF NOT EXISTS item (\n    id INTEGER PRIMARY KEY,\n    name TEXT NOT NULL\n);\n"
+        );
+    }
+
+    #[test]
+    fn test_schema_problems() {
+        assert!(schema_problems(&dummy_schema()).is_empty());
+
+        let mut broken_schema = dummy_schema();
+        broken_schema.tables[0].name = "item; DROP TABLE user".to_string();
+        broken_schema.tables[0].columns[0].is_primary_key = false;
+        broken_schema.tables[0].columns[1].sql_type = "VARCHAR(20)".to_string();
+
+        let problems = schema_problems(&broken_schema);
+        assert_eq!(problems.len(), 3, "{:?}", problems);
+    }
+
+    #[test]
+    fn test_migration_problems() {
+        let schema = dummy_schema();
+        assert!(migration_problems(&schema, &migration_sql(&schema))
+            .unwrap()
+            .is_empty());
+
+        let problems = migration_problems(&schema, "CREATE TABLE other (id INTEGER);").unwrap();
+        assert_eq!(problems, vec!["Tables missing after migration: item"]);
+
+        let problems =
+            migration_problems(&schema, "CREATE TABLE item (id INTEGER, id TEXT);").unwrap();
+        assert!(
+            problems[0].starts_with("Migration failed: "),
+            "{:?}",
+            problems
+        );
+    }
+}
@@ -0,0 +1,569 @@
+use async_trait::async_trait;
+use serde_json::json;
+use serde_yaml::Value;
+
+use crate::{
+    helpers::{
+        command_line::{read_workspace_package_name, save_workspace_file, PrintCommand},
+        source_files::SourceFiles,
+        workspace::build_workspace_image,
+    },
+    models::{
+        agent_basic::basic_agent::{AgentState, BasicAgent},
+        general::agent_error::AgentError,
+    },
+};
+
+use super::agent_traits::{FactSheet, FactSheetField, SpecialFunctions};
+
+const SERVER_PORT: u16 = 8080;
+pub const DOCKERFILE: &str = "Dockerfile";
+const DOCKERIGNORE: &str = ".dockerignore";
+pub const COMPOSE_FILE: &str = "docker-compose.yml";
+// holds the SQLite file of CRUD websites across container restarts
+const DATA_VOLUME: &str = "app-data";
+const DOCKERFILE_INSTRUCTIONS: [&str; 18] = [
+    "FROM",
+    "RUN",
+    "CMD",
+    "LABEL",
+    "EXPOSE",
+    "ENV",
+    "ADD",
+    "COPY",
+    "ENTRYPOINT",
+    "VOLUME",
+    "USER",
+    "WORKDIR",
+    "ARG",
+    "ONBUILD",
+    "STOPSIGNAL",
+    "HEALTHCHECK",
+    "SHELL",
+    "MAINTAINER",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+struct DeploymentFiles {
+    dockerfile: String,
+    dockerignore: String,
+    docker_compose: String,
+}
+
+#[derive(Debug)]
+pub struct AgentDevOpsEngineer {
+    attributes: BasicAgent,
+    package_name: String,
+    deployment_files: Option<DeploymentFiles>,
+}
+
+impl AgentDevOpsEngineer {
+    pub fn new() -> Self {
+        let attributes = BasicAgent {
+            objective: "Writes the Dockerfile and docker-compose file to deploy the webserver"
+                .to_string(),
+            position: "DevOps Engineer".to_string(),
+            state: AgentState::Discovering,
+            memory: vec![],
+        };
+
+        Self {
+            attributes,
+            package_name: String::new(),
+            deployment_files: None,
+        }
+    }
+}
+
+impl Default for AgentDevOpsEngineer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn needs_database(fact_sheet: &FactSheet) -> bool {
+    fact_sheet.database_schema.is_some()
+}
+
+fn needs_auth(fact_sheet: &FactSheet) -> bool {
+    fact_sheet
+        .project_scope
+        .is_some_and(|project_scope| project_scope.is_user_login_and_logout)
+}
+
+fn dockerfile(fact_sheet: &FactSheet, package_name: &str) -> String {
+    let mut dockerfile = String::from(
+        "# Generated by Auto-Gippity\n\
+         FROM rust:1 AS builder\n\
+         WORKDIR /app\n\
+         COPY Cargo.toml Cargo.lock* ./\n\
+         COPY src ./src\n",
+    );
+    if needs_database(fact_sheet) {
+        // migrations are compiled into the binary with include_str!
+        dockerfile.push_str("COPY migrations ./migrations\n");
+    }
+    dockerfile.push_str(&format!(
+        "RUN cargo build --release --bin {package_name}\n\n\
+         FROM debian:bookworm-slim AS runtime\n"
+    ));
+
+    // reqwest links against OpenSSL, calling https urls also needs the CA certificates
+    let mut runtime_packages = vec!["libssl3"];
+    if fact_sheet
+        .external_urls
+        .iter()
+        .any(|url| url.starts_with("https://"))
+    {
+        runtime_packages.push("ca-certificates");
+    }
+    dockerfile.push_str(&format!(
+        "RUN apt-get update \\\n    \
+         && apt-get install -y --no-install-recommends {} \\\n    \
+         && rm -rf /var/lib/apt/lists/*\n\
+         COPY --from=builder /app/target/release/{package_name} /usr/local/bin/{package_name}\n\
+         WORKDIR /app\n",
+        runtime_packages.join(" ")
+    ));
+    if needs_database(fact_sheet) {
+        dockerfile.push_str("VOLUME /app\n");
+    }
+    dockerfile.push_str(&format!(
+        "EXPOSE {SERVER_PORT}\n\
+         CMD [\"{package_name}\"]\n"
+    ));
+
+    dockerfile
+}
+
+fn dockerignore() -> String {
+    ["target", ".git", ".env", "*.db", "frontend", "tests"].join("\n") + "\n"
+}
+
+fn docker_compose(fact_sheet: &FactSheet, package_name: &str) -> Result<String, AgentError> {
+    let mut service = json!({
+        "build": ".",
+        "image": format!("{}:latest", package_name),
+        "ports": [format!("{SERVER_PORT}:{SERVER_PORT}")],
+        "restart": "unless-stopped",
+    });
+    if needs_auth(fact_sheet) {
+        service["environment"] = json!({
+            "JWT_SECRET": "${JWT_SECRET:?set JWT_SECRET to sign login tokens}"
+        });
+    }
+
+    let mut compose = json!({ "services": { "web": service } });
+    if needs_database(fact_sheet) {
+        compose["services"]["web"]["volumes"] = json!([format!("{}:/app", DATA_VOLUME)]);
+        compose["volumes"] = json!({ DATA_VOLUME: {} });
+    }
+
+    serde_yaml::to_string(&compose).map_err(AgentError::encode("docker-compose file"))
+}
+
+fn deployment_files(
+    fact_sheet: &FactSheet,
+    package_name: &str,
+) -> Result<DeploymentFiles, AgentError> {
+    Ok(DeploymentFiles {
+        dockerfile: dockerfile(fact_sheet, package_name),
+        dockerignore: dockerignore(),
+        docker_compose: docker_compose(fact_sheet, package_name)?,
+    })
+}
+
+// Instructions with their line continuations joined, comments and blank lines dropped
+fn dockerfile_instructions(dockerfile: &str) -> Vec<String> {
+    let mut instructions: Vec<String> = vec![];
+    let mut current = String::new();
+
+    for line in dockerfile.lines().map(str::trim) {
+        if current.is_empty() && (line.is_empty() || line.starts_with('#')) {
+            continue;
+        }
+        match line.strip_suffix('\\') {
+            Some(continued) => current.push_str(continued),
+            None => {
+                current.push_str(line);
+                instructions.push(std::mem::take(&mut current));
+            }
+        }
+    }
+    if !current.is_empty() {
+        instructions.push(current);
+    }
+
+    instructions
+}
+
+fn lint_dockerfile(dockerfile: &str) -> Vec<String> {
+    let mut problems: Vec<String> = vec![];
+    // instructions of every build stage, keyed by the stage name
+    let mut stages: Vec<(Option<String>, Vec<String>)> = vec![];
+
+    for instruction in dockerfile_instructions(dockerfile) {
+        let mut words = instruction.split_whitespace();
+        let keyword = words.next().unwrap_or_default().to_uppercase();
+        let arguments: Vec<&str> = words.collect();
+
+        if !DOCKERFILE_INSTRUCTIONS.contains(&keyword.as_str()) {
+            problems.push(format!("Unknown instruction '{}'", keyword));
+            continue;
+        }
+        if keyword == "FROM" {
+            let stage_name = match arguments.as_slice() {
+                [_, as_keyword, name] if as_keyword.eq_ignore_ascii_case("as") => {
+                    Some(name.to_string())
+                }
+                _ => None,
+            };
+            if let Some(name) = stage_name.as_deref() {
+                if stages
+                    .iter()
+                    .any(|(stage, _)| stage.as_deref() == Some(name))
+                {
+                    problems.push(format!("Stage '{}' is defined twice", name));
+                }
+            }
+            stages.push((stage_name, vec![]));
+            continue;
+        }
+
+        if stages.is_empty() {
+            if keyword != "ARG" {
+                problems.push(format!("'{}' comes before the first FROM", keyword));
+            }
+            continue;
+        }
+        if keyword == "COPY" {
+            if let Some(source_stage) = arguments
+                .iter()
+                .find_map(|argument| argument.strip_prefix("--from="))
+            {
+                let earlier_stages = &stages[..stages.len() - 1];
+                let is_known = earlier_stages
+                    .iter()
+                    .any(|(name, _)| name.as_deref() == Some(source_stage))
+                    || source_stage
+                        .parse::<usize>()
+                        .is_ok_and(|index| index < earlier_stages.len());
+                if !is_known {
+                    problems.push(format!(
+                        "COPY --from={} names no earlier stage",
+                        source_stage
+                    ));
+                }
+            }
+        }
+        if let Some((_, stage_instructions)) = stages.last_mut() {
+            stage_instructions.push(keyword);
+        }
+    }
+
+    if stages.len() < 2 {
+        problems.push(
+            "Not a multi-stage build, the runtime image would carry the Rust toolchain".to_string(),
+        );
+    }
+    match stages.last() {
+        Some((_, runtime_instructions)) => {
+            if !runtime_instructions
+                .iter()
+                .any(|keyword| keyword == "CMD" || keyword == "ENTRYPOINT")
+            {
+                problems.push("The runtime stage has no CMD or ENTRYPOINT".to_string());
+            }
+        }
+        None => problems.push("No FROM instruction".to_string()),
+    }
+    let exposes_port = dockerfile_instructions(dockerfile)
+        .iter()
+        .any(|instruction| {
+            let mut words = instruction.split_whitespace();
+            words
+                .next()
+                .is_some_and(|keyword| keyword.eq_ignore_ascii_case("EXPOSE"))
+                && words.any(|port| port.split('/').next() == Some(&SERVER_PORT.to_string()))
+        });
+    if !exposes_port {
+        problems.push(format!("Port {} is not exposed", SERVER_PORT));
+    }
+
+    problems
+}
+
+fn lint_dockerignore(dockerignore: &str) -> Vec<String> {
+    let ignores_target = dockerignore
+        .lines()
+        .map(|line| line.trim().trim_matches('/'))
+        .any(|pattern| pattern == "target");
+    if ignores_target {
+        vec![]
+    } else {
+        vec![
+            "target is not ignored, the build context would include every build artifact"
+                .to_string(),
+        ]
+    }
+}
+
+fn lint_docker_compose(docker_compose: &str) -> Vec<String> {
+    let compose: Value = match serde_yaml::from_str(docker_compose) {
+        Ok(compose) => compose,
+        Err(e) => return vec![format!("Invalid YAML: {}", e)],
+    };
+    let Some(services) = compose.get("services").and_then(Value::as_mapping) else {
+        return vec!["No services are defined".to_string()];
+    };
+
+    let mut problems: Vec<String> = vec![];
+    let published_port = format!("{SERVER_PORT}:{SERVER_PORT}");
+    let mut publishes_port = false;
+
+    for (service_name, service) in services {
+        let service_name = service_name.as_str().unwrap_or_default();
+        if service.get("build").is_none() && service.get("image").is_none() {
+            problems.push(format!(
+                "Service '{}' has neither build nor image",
+                service_name
+            ));
+        }
+        publishes_port |= service
+            .get("ports")
+            .and_then(Value::as_sequence)
+            .is_some_and(|ports| {
+                ports
+                    .iter()
+                    .any(|port| port.as_str() == Some(&published_port))
+            });
+
+        let volumes = service.get("volumes").and_then(Value::as_sequence);
+        for volume in volumes.into_iter().flatten().filter_map(Value::as_str) {
+            let source = volume.split(':').next().unwrap_or_default();
+            // bind mounts start with a path, anything else is a named volume
+            let is_named_volume = !source.starts_with(['.', '/', '~', '$']);
+            let is_declared = compose
+                .get("volumes")
+                .and_then(|declared| declared.get(source))
+                .is_some();
+            if is_named_volume && !is_declared {
+                problems.push(format!(
+                    "Service '{}' uses the undeclared volume '{}'",
+                    service_name, source
+                ));
+            }
+        }
+    }
+    if !publishes_port {
+        problems.push(format!("Port {} is not published", published_port));
+    }
+
+    problems
+}
+
+// A server bound to the loopback interface can't be reached from outside its container
+fn binds_to_loopback(backend_code: &str) -> bool {
+    backend_code.lines().any(|line| {
+        line.contains(".bind(") && (line.contains("127.0.0.1") || line.contains("localhost"))
+    })
+}
+
+#[async_trait]
+impl SpecialFunctions for AgentDevOpsEngineer {
+    fn get_attributes_from_agent(&self) -> &BasicAgent {
+        &self.attributes
+    }
+
+    fn reads(&self) -> Vec<FactSheetField> {
+        vec![
+            FactSheetField::ProjectScope,
+            FactSheetField::ExternalUrls,
+            FactSheetField::BackendCode,
+            FactSheetField::DatabaseSchema,
+        ]
+    }
+
+    fn writes(&self) -> Vec<FactSheetField> {
+        vec![FactSheetField::DeploymentFiles]
+    }
+
+    async fn execute(&mut self, fact_sheet: &mut FactSheet) -> Result<(), AgentError> {
+        while self.attributes.state != AgentState::Finished {
+            match self.attributes.state {
+                AgentState::Discovering => {
+                    self.package_name = read_workspace_package_name()?;
+                    self.deployment_files = Some(deployment_files(fact_sheet, &self.package_name)?);
+                    self.attributes.state = AgentState::Validation;
+                }
+                AgentState::Validation => {
+                    let files = self.deployment_files.clone().ok_or_else(|| {
+                        AgentError::Validation("No deployment files to lint".to_string())
+                    })?;
+
+                    PrintCommand::UnitTest.print_agent_message(
+                        self.attributes.position.as_str(),
+                        "Deployment Testing: linting deployment files...",
+                    );
+                    let problems: Vec<String> = [
+                        (DOCKERFILE, lint_dockerfile(&files.dockerfile)),
+                        (DOCKERIGNORE, lint_dockerignore(&files.dockerignore)),
+                        (COMPOSE_FILE, lint_docker_compose(&files.docker_compose)),
+                    ]
+                    .into_iter()
+                    .flat_map(|(file_name, problems)| {
+                        problems
+                            .into_iter()
+                            .map(move |problem| format!("{}: {}", file_name, problem))
+                    })
+                    .collect();
+                    if !problems.is_empty() {
+                        return Err(AgentError::Validation(format!(
+                            "Deployment files did not pass linting:\n{}",
+                            problems.join("\n")
+                        )));
+                    }
+
+                    // the image would build, but nothing outside the container could reach the
+                    // server. The files are right all the same, the backend code is what needs the fix
+                    for (path, _) in fact_sheet
+                        .backend_code
+                        .iter()
+                        .flat_map(SourceFiles::iter)
+                        .filter(|(_, code)| binds_to_loopback(code))
+                    {
+                        PrintCommand::Issue.print_agent_message(
+                            self.attributes.position.as_str(),
+                            format!(
+                                "WARNING: {}: the server binds to 127.0.0.1, bind it to 0.0.0.0 to reach it from outside the container",
+                                path
+                            )
+                            .as_str(),
+                        );
+                    }
+
+                    save_workspace_file(DOCKERFILE, &files.dockerfile)?;
+                    save_workspace_file(DOCKERIGNORE, &files.dockerignore)?;
+                    save_workspace_file(COMPOSE_FILE, &files.docker_compose)?;
+                    fact_sheet.deployment_files = [DOCKERFILE, DOCKERIGNORE, COMPOSE_FILE]
+                        .map(String::from)
+                        .to_vec();
+
+                    // building the image is a bonus, the files are useful without it
+                    let image_tag = format!("{}:latest", self.package_name);
+                    match build_workspace_image(&image_tag).await? {
+                        None => PrintCommand::UnitTest.print_agent_message(
+                            self.attributes.position.as_str(),
+                            "Deployment Testing: no container runtime found, skipping the image build",
+                        ),
+                        Some(output) if output.status.success() => {
+                            PrintCommand::UnitTest.print_agent_message(
+                                self.attributes.position.as_str(),
+                                format!("Deployment Testing: built image {}", image_tag).as_str(),
+                            )
+                        }
+                        Some(output) => PrintCommand::Issue.print_agent_message(
+                            self.attributes.position.as_str(),
+                            format!(
+                                "Deployment Testing: image build failed:\n{}",
+                                String::from_utf8_lossy(&output.stderr)
+                            )
+                            .as_str(),
+                        ),
+                    }
+
+                    self.attributes.state = AgentState::Finished;
+                }
+                _ => self.attributes.state = AgentState::Finished,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fact_sheet(with_database_and_auth: bool) -> FactSheet {
+        let mut fact_sheet: FactSheet = serde_json::from_value(json!({
+            "project_description": "build a website that tracks crypto prices",
+            "project_scope": {
+                "is_crud_required": with_database_and_auth,
+                "is_user_login_and_logout": with_database_and_auth,
+                "is_external_urls_required": true
+            },
+            "external_urls": ["https://api.coincap.io/v2/assets"],
+            "backend_code": null,
+            "api_endpoint_schema": []
+        }))
+        .unwrap();
+        if with_database_and_auth {
+            fact_sheet.database_schema = serde_json::from_value(json!({ "tables": [] })).unwrap();
+        }
+        fact_sheet
+    }
+
+    #[test]
+    fn test_deployment_files_pass_linting() {
+        for with_database_and_auth in [false, true] {
+            let files = deployment_files(&fact_sheet(with_database_and_auth), "web").unwrap();
+
+            assert_eq!(lint_dockerfile(&files.dockerfile), Vec::<String>::new());
+            assert_eq!(lint_dockerignore(&files.dockerignore), Vec::<String>::new());
+            assert_eq!(
+                lint_docker_compose(&files.docker_compose),
+                Vec::<String>::new()
+            );
+            assert!(files.dockerfile.contains("ca-certificates"));
+            assert_eq!(
+                files.dockerfile.contains("COPY migrations"),
+                with_database_and_auth
+            );
+            assert_eq!(
+                files.docker_compose.contains("JWT_SECRET"),
+                with_database_and_auth
+            );
+        }
+    }
+
+    #[test]
+    fn test_lint_dockerfile() {
+        let dockerfile = "FROM rust:1\n\
+                          RUN cargo build --release \\\n    --bin web\n\
+                          COPY --from=builder /app/target/release/web /web\n\
+                          RUNN echo typo\n";
+
+        assert_eq!(
+            lint_dockerfile(dockerfile),
+            vec![
+                "COPY --from=builder names no earlier stage",
+                "Unknown instruction 'RUNN'",
+                "Not a multi-stage build, the runtime image would carry the Rust toolchain",
+                "The runtime stage has no CMD or ENTRYPOINT",
+                "Port 8080 is not exposed",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint_docker_compose() {
+        let docker_compose = "services:\n  web:\n    ports: ['3000:8080']\n    volumes: ['data:/app', './logs:/logs']\n";
+
+        assert_eq!(
+            lint_docker_compose(docker_compose),
+            vec![
+                "Service 'web' has neither build nor image",
+                "Service 'web' uses the undeclared volume 'data'",
+                "Port 8080:8080 is not published",
+            ]
+        );
+        assert!(lint_docker_compose("services: [")[0].starts_with("Invalid YAML"));
+    }
+
+    #[test]
+    fn test_binds_to_loopback() {
+        assert!(binds_to_loopback("    .bind((\"127.0.0.1\", 8080))?"));
+        assert!(!binds_to_loopback("    .bind(\"0.0.0.0:8080\")?"));
+    }
+}
@@ -0,0 +1,348 @@
+use async_trait::async_trait;
+use serde_json::{Map, Value};
+
+use crate::{
+    helpers::command_line::{
+        read_workspace_package_name, save_workspace_file, workspace_file_exists, PrintCommand,
+    },
+    models::{
+        agent_basic::basic_agent::{AgentState, BasicAgent},
+        general::{
+            agent_error::AgentError,
+            api_route::{concrete_path, ScalarType, ShapeDescriptor},
+        },
+    },
+};
+
+use super::{
+    agent_auth::{is_public_route, AUTH_TEST_FILE},
+    agent_database::INITIAL_MIGRATION_FILE,
+    agent_devops::{COMPOSE_FILE, DOCKERFILE},
+    agent_qa::API_TEST_NAME,
+    agent_traits::{FactSheet, FactSheetField, RouteObject, SpecialFunctions},
+};
+
+const README_FILE: &str = "README.md";
+const SERVER_URL: &str = "http://localhost:8080";
+
+// Files written by earlier agents that the README points to
+#[derive(Debug)]
+struct GeneratedFiles {
+    has_api_tests: bool,
+    has_auth_tests: bool,
+    has_docker: bool,
+}
+
+impl GeneratedFiles {
+    fn from_workspace(fact_sheet: &FactSheet) -> Self {
+        let is_deployment_file = |file_name: &str| {
+            fact_sheet
+                .deployment_files
+                .iter()
+                .any(|deployment_file| deployment_file == file_name)
+        };
+
+        Self {
+            has_api_tests: workspace_file_exists(&format!("tests/{}.rs", API_TEST_NAME)),
+            has_auth_tests: workspace_file_exists(&format!("tests/{}", AUTH_TEST_FILE)),
+            has_docker: is_deployment_file(DOCKERFILE) && is_deployment_file(COMPOSE_FILE),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AgentDocumentationWriter {
+    attributes: BasicAgent,
+}
+
+impl AgentDocumentationWriter {
+    pub fn new() -> Self {
+        let attributes = BasicAgent {
+            objective: "Writes the README of the generated website".to_string(),
+            position: "Documentation Writer".to_string(),
+            state: AgentState::Discovering,
+            memory: vec![],
+        };
+
+        Self { attributes }
+    }
+}
+
+impl Default for AgentDocumentationWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn needs_auth(fact_sheet: &FactSheet) -> bool {
+    fact_sheet
+        .project_scope
+        .is_some_and(|project_scope| project_scope.is_user_login_and_logout)
+}
+
+fn yes_no(flag: bool) -> &'static str {
+    if flag {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+// A value of the right shape to put in an example request
+fn example_value(shape: &ShapeDescriptor) -> Value {
+    match shape {
+        ShapeDescriptor::None | ShapeDescriptor::NotProvided => Value::Null,
+        ShapeDescriptor::Scalar(ScalarType::Number) => Value::from(1),
+        ShapeDescriptor::Scalar(ScalarType::Bool) => Value::from(true),
+        ShapeDescriptor::Scalar(_) => Value::from("text"),
+        ShapeDescriptor::Array(item) => Value::Array(vec![example_value(item)]),
+        ShapeDescriptor::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(name, field)| (name.clone(), example_value(field)))
+                .collect::<Map<String, Value>>(),
+        ),
+    }
+}
+
+fn curl_example(route_object: &RouteObject, requires_token: bool) -> String {
+    let mut curl = format!(
+        "curl -X {} {}{}",
+        route_object.method.as_ref().to_uppercase(),
+        SERVER_URL,
+        concrete_path(&route_object.route)
+    );
+    if requires_token {
+        curl.push_str(" -H \"Authorization: Bearer $TOKEN\"");
+    }
+    let body = example_value(&route_object.request_body);
+    if !body.is_null() {
+        curl.push_str(&format!(
+            " -H \"Content-Type: application/json\" -d '{}'",
+            body
+        ));
+    }
+    curl
+}
+
+fn endpoints_section(fact_sheet: &FactSheet) -> String {
+    if fact_sheet.api_endpoint_schema.is_empty() {
+        return String::new();
+    }
+
+    let mut section = String::from(
+        "## Endpoints\n\n\
+         The full description is in `openapi.json` (OpenAPI 3.1).\n\n\
+         | Method | Route | Example |\n\
+         | :----: | :---- | :------ |\n",
+    );
+    for route_object in &fact_sheet.api_endpoint_schema {
+        let requires_token = needs_auth(fact_sheet) && !is_public_route(&route_object.route);
+        section.push_str(&format!(
+            "| {} | `{}` | `{}` |\n",
+            route_object.method.as_ref().to_uppercase(),
+            route_object.route,
+            curl_example(route_object, requires_token)
+        ));
+    }
+    section.push('\n');
+    section
+}
+
+fn readme(fact_sheet: &FactSheet, package_name: &str, generated_files: &GeneratedFiles) -> String {
+    let mut readme = format!(
+        "# {}\n\n{}\n\n_Generated by Auto-Gippity._\n\n",
+        package_name,
+        fact_sheet.project_description.trim()
+    );
+
+    if let Some(project_scope) = fact_sheet.project_scope {
+        readme.push_str(&format!(
+            "## Scope\n\n\
+             | Feature | Included |\n\
+             | :------ | :------: |\n\
+             | Create, read, update and delete data | {} |\n\
+             | User login and logout | {} |\n\
+             | External data sources | {} |\n\n",
+            yes_no(project_scope.is_crud_required),
+            yes_no(project_scope.is_user_login_and_logout),
+            yes_no(project_scope.is_external_urls_required),
+        ));
+    }
+
+    if !fact_sheet.external_urls.is_empty() {
+        readme.push_str("## External data sources\n\n");
+        for url in &fact_sheet.external_urls {
+            readme.push_str(&format!("- <{}>\n", url));
+        }
+        readme.push('\n');
+    }
+
+    if let Some(database_schema) = &fact_sheet.database_schema {
+        readme.push_str(&format!(
+            "## Database\n\n\
+             Data is kept in the `app.db` SQLite file. The tables are created by \
+             `migrations/{}` when the server starts:\n\n",
+            INITIAL_MIGRATION_FILE
+        ));
+        for table in &database_schema.tables {
+            let columns: Vec<&str> = table
+                .columns
+                .iter()
+                .map(|column| column.name.as_str())
+                .collect();
+            readme.push_str(&format!("- `{}`: {}\n", table.name, columns.join(", ")));
+        }
+        readme.push('\n');
+    }
+
+    readme.push_str(&endpoints_section(fact_sheet));
+
+    readme.push_str("## Running\n\n```sh\n");
+    if needs_auth(fact_sheet) {
+        readme.push_str("export JWT_SECRET=<a long random string>\n");
+    }
+    readme.push_str(&format!(
+        "cargo run\n```\n\nThe server listens on {}.\n",
+        SERVER_URL
+    ));
+    if needs_auth(fact_sheet) {
+        readme.push_str(&format!(
+            "\nSign up and log in to get a token for the protected routes:\n\n```sh\n\
+             curl -X POST {url}/signup -H \"Content-Type: application/json\" -d '{{\"username\":\"me\",\"password\":\"secret\"}}'\n\
+             export TOKEN=$(curl -s -X POST {url}/login -H \"Content-Type: application/json\" -d '{{\"username\":\"me\",\"password\":\"secret\"}}' | jq -r .token)\n\
+             ```\n",
+            url = SERVER_URL
+        ));
+    }
+    // written alongside, a project always has one
+    readme.push_str("\nOpen `frontend/index.html` in a browser to use the website.\n");
+    if generated_files.has_docker {
+        readme.push_str(&format!(
+            "\nOr run it in a container:\n\n```sh\ndocker compose -f {} up --build\n```\n",
+            COMPOSE_FILE
+        ));
+    }
+    readme.push('\n');
+
+    if generated_files.has_api_tests || generated_files.has_auth_tests {
+        readme.push_str("## Testing\n\n```sh\n");
+        if generated_files.has_api_tests {
+            readme.push_str(&format!("cargo test --test {}\n", API_TEST_NAME));
+        }
+        if generated_files.has_auth_tests {
+            readme.push_str(&format!(
+                "# needs the server to be running\ncargo test --test {}\n",
+                AUTH_TEST_FILE.trim_end_matches(".rs")
+            ));
+        }
+        readme.push_str("```\n\n");
+    }
+
+    readme.trim_end().to_string() + "\n"
+}
+
+#[async_trait]
+impl SpecialFunctions for AgentDocumentationWriter {
+    fn get_attributes_from_agent(&self) -> &BasicAgent {
+        &self.attributes
+    }
+
+    fn reads(&self) -> Vec<FactSheetField> {
+        vec![
+            FactSheetField::ProjectDescription,
+            FactSheetField::ProjectScope,
+            FactSheetField::ExternalUrls,
+            FactSheetField::DatabaseSchema,
+            FactSheetField::ApiEndpointSchema,
+            FactSheetField::DeploymentFiles,
+        ]
+    }
+
+    fn writes(&self) -> Vec<FactSheetField> {
+        vec![]
+    }
+
+    async fn execute(&mut self, fact_sheet: &mut FactSheet) -> Result<(), AgentError> {
+        while self.attributes.state != AgentState::Finished {
+            match self.attributes.state {
+                AgentState::Discovering => {
+                    PrintCommand::AICall.print_agent_message(
+                        self.attributes.position.as_str(),
+                        "Writing the README from the fact sheet...",
+                    );
+                    let package_name = read_workspace_package_name()?;
+                    let readme = readme(
+                        fact_sheet,
+                        &package_name,
+                        &GeneratedFiles::from_workspace(fact_sheet),
+                    );
+                    save_workspace_file(README_FILE, &readme)?;
+                    self.attributes.state = AgentState::Finished;
+                }
+                _ => self.attributes.state = AgentState::Finished,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fact_sheet() -> FactSheet {
+        serde_json::from_value(serde_json::json!({
+            "project_description": "build a website that tracks my todo items",
+            "project_scope": {
+                "is_crud_required": true,
+                "is_user_login_and_logout": true,
+                "is_external_urls_required": false
+            },
+            "external_urls": [],
+            "backend_code": null,
+            "api_endpoint_schema": [
+                { "route": "/login", "method": "post", "request_body": { "username": "string", "password": "string" }, "response": { "token": "string" } },
+                { "route": "/todo/{id}", "method": "put", "request_body": { "title": "string", "done": "bool" }, "response": "None" }
+            ],
+            "database_schema": {
+                "tables": [{ "name": "todos", "columns": [
+                    { "name": "id", "sql_type": "INTEGER", "is_primary_key": true },
+                    { "name": "title", "sql_type": "TEXT" }
+                ] }]
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_curl_example() {
+        let fact_sheet = fact_sheet();
+
+        assert_eq!(
+            curl_example(&fact_sheet.api_endpoint_schema[1], true),
+            r#"curl -X PUT http://localhost:8080/todo/1 -H "Authorization: Bearer $TOKEN" -H "Content-Type: application/json" -d '{"done":true,"title":"text"}'"#
+        );
+    }
+
+    #[test]
+    fn test_readme() {
+        let generated_files = GeneratedFiles {
+            has_api_tests: true,
+            has_auth_tests: false,
+            has_docker: true,
+        };
+
+        let readme = readme(&fact_sheet(), "todo_web", &generated_files);
+
+        assert!(readme.starts_with("# todo_web\n\nbuild a website that tracks my todo items\n"));
+        assert!(readme.contains("| User login and logout | yes |\n"));
+        assert!(readme.contains("- `todos`: id, title\n"));
+        assert!(readme.contains("| POST | `/login` | `curl -X POST http://localhost:8080/login -H"));
+        assert!(!readme.contains("/login -H \"Authorization"));
+        assert!(readme.contains("export JWT_SECRET="));
+        assert!(readme.contains("docker compose -f docker-compose.yml up --build"));
+        assert!(readme.contains("cargo test --test api\n"));
+        assert!(!readme.contains("External data sources\n\n-"));
+    }
+}
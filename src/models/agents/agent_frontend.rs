@@ -0,0 +1,455 @@
+use std::{path::PathBuf, str::FromStr, time::Duration};
+
+use async_trait::async_trait;
+
+use crate::{
+    ai_functions::aifunc_frontend::{print_fixed_frontend_code, print_frontend_code},
+    helpers::{
+        command_line::{save_frontend_code, PrintCommand, FRONTEND_PROJECT_PATH},
+        general::{ai_task_request, check_status_code, find_cors_bugs, http_client},
+        static_server::serve_directory,
+    },
+    models::{
+        agent_basic::basic_agent::{AgentState, BasicAgent},
+        general::{
+            agent_error::AgentError,
+            api_route::{route_matches, HttpMethod},
+        },
+    },
+};
+
+use super::{
+    agent_context::AgentContext,
+    agent_traits::{FactSheet, FactSheetField, RouteObject, SpecialFunctions},
+};
+
+const MAX_FRONTEND_BUG_RETRIES: i8 = 2;
+// the whole frontend is this one page
+const FRONTEND_PAGE: &str = "/";
+
+// A fetch() call of the frontend
+#[derive(Debug, PartialEq)]
+struct FetchCall {
+    // None when the options are a variable, so the method can't be read from the code
+    method: Option<HttpMethod>,
+    path: String,
+}
+
+impl FetchCall {
+    fn calls(&self, route_object: &RouteObject) -> bool {
+        route_matches(&route_object.route, &self.path)
+            && self
+                .method
+                .is_none_or(|method| method == route_object.method)
+    }
+}
+
+#[derive(Debug)]
+pub struct AgentFrontendDeveloper {
+    attributes: BasicAgent,
+    context: AgentContext,
+    bug_errors: Option<String>,
+    bug_count: i8,
+}
+
+impl AgentFrontendDeveloper {
+    pub fn new(context: AgentContext) -> Self {
+        let attributes = BasicAgent {
+            objective: "Develops a static frontend calling the webserver endpoints".to_string(),
+            position: "Frontend Developer".to_string(),
+            state: AgentState::Discovering,
+            memory: vec![],
+        };
+
+        Self {
+            attributes,
+            context,
+            bug_errors: None,
+            bug_count: 0,
+        }
+    }
+
+    async fn call_initial_frontend_code(
+        &mut self,
+        fact_sheet: &mut FactSheet,
+    ) -> Result<(), AgentError> {
+        let msg_context = format!(
+            "PROJECT_DESCRIPTION: {}\n API_ENDPOINTS: {}\n",
+            fact_sheet.project_description,
+            api_endpoints_json(&fact_sheet.api_endpoint_schema)?
+        );
+
+        let ai_response: String = ai_task_request(
+            self.context.provider.as_ref(),
+            msg_context,
+            &self.attributes.position,
+            get_function_string!(print_frontend_code),
+            print_frontend_code,
+        )
+        .await?;
+
+        save_frontend_code(&ai_response)?;
+        fact_sheet.frontend_code = Some(ai_response);
+        Ok(())
+    }
+
+    async fn call_fix_frontend_bugs(
+        &mut self,
+        fact_sheet: &mut FactSheet,
+    ) -> Result<(), AgentError> {
+        let msg_context = format!(
+            "BROKEN_FRONTEND: {:?}\n API_ENDPOINTS: {}\n ERROR_BUGS: {:?}\n
+            THIS FUNCTION ONLY OUTPUTS CODE. JUST OUTPUT THE CODE.",
+            fact_sheet.frontend_code,
+            api_endpoints_json(&fact_sheet.api_endpoint_schema)?,
+            self.bug_errors
+        );
+
+        let ai_response: String = ai_task_request(
+            self.context.provider.as_ref(),
+            msg_context,
+            &self.attributes.position,
+            get_function_string!(print_fixed_frontend_code),
+            print_fixed_frontend_code,
+        )
+        .await?;
+
+        save_frontend_code(&ai_response)?;
+        fact_sheet.frontend_code = Some(ai_response);
+        Ok(())
+    }
+
+    // Serve the frontend and report a page that doesn't load and fetch calls to unknown routes,
+    // along with the origin it was served from
+    async fn find_frontend_bugs(
+        &self,
+        fact_sheet: &FactSheet,
+    ) -> Result<(String, Vec<String>), AgentError> {
+        let mut bugs: Vec<String> = vec![];
+
+        let client = http_client(Duration::from_secs(5))?;
+        let (address, server) = serve_directory(PathBuf::from(FRONTEND_PROJECT_PATH))
+            .await
+            .map_err(AgentError::io("serve frontend"))?;
+
+        PrintCommand::UnitTest.print_agent_message(
+            self.attributes.position.as_str(),
+            format!("Testing page '{}'...", FRONTEND_PAGE).as_str(),
+        );
+        let url = format!("http://{}{}", address, FRONTEND_PAGE);
+        match check_status_code(&client, &url).await {
+            Ok(200) => {}
+            Ok(status_code) => bugs.push(format!(
+                "Page {} returned status {} instead of 200",
+                FRONTEND_PAGE, status_code
+            )),
+            Err(e) => bugs.push(format!("Page {} could not be loaded: {}", FRONTEND_PAGE, e)),
+        }
+        server.abort();
+
+        let frontend_code = fact_sheet.frontend_code.as_deref().unwrap_or_default();
+        for fetch_call in extract_fetch_calls(frontend_code) {
+            if !fact_sheet
+                .api_endpoint_schema
+                .iter()
+                .any(|route_object| fetch_call.calls(route_object))
+            {
+                let method = fetch_call
+                    .method
+                    .map_or(String::new(), |method| format!("{} ", method.as_ref()));
+                bugs.push(format!(
+                    "fetch() call to '{}{}' does not match any backend route",
+                    method.to_uppercase(),
+                    fetch_call.path
+                ));
+            }
+        }
+
+        Ok((format!("http://{}", address), bugs))
+    }
+
+    // The routes the frontend calls that don't allow its origin. The QA engineer already had the
+    // backend allow other origins, this checks the frontend's real one
+    async fn find_cors_bugs(
+        &self,
+        fact_sheet: &FactSheet,
+        origin: &str,
+    ) -> Result<Vec<String>, AgentError> {
+        let frontend_code = fact_sheet.frontend_code.as_deref().unwrap_or_default();
+        let fetch_calls = extract_fetch_calls(frontend_code);
+        let called_routes: Vec<&RouteObject> = fact_sheet
+            .api_endpoint_schema
+            .iter()
+            .filter(|route_object| fetch_calls.iter().any(|call| call.calls(route_object)))
+            .collect();
+        find_cors_bugs(
+            &self.context.sandbox,
+            &self.attributes.position,
+            &called_routes,
+            origin,
+        )
+        .await
+    }
+}
+
+fn api_endpoints_json(api_endpoints: &[RouteObject]) -> Result<String, AgentError> {
+    serde_json::to_string(api_endpoints).map_err(AgentError::encode("API endpoints"))
+}
+
+// The fetch() calls whose URL is a string or template literal, e.g.
+// fetch(`${API_URL}/item/${id}`, { method: "DELETE" }) -> DELETE "/item/${id}".
+// Calls with a variable URL can't be checked
+fn extract_fetch_calls(frontend_code: &str) -> Vec<FetchCall> {
+    let mut fetch_calls: Vec<FetchCall> = vec![];
+
+    for (position, _) in frontend_code.match_indices("fetch(") {
+        let arguments = frontend_code[position + "fetch(".len()..].trim_start();
+        let Some(quote) = arguments
+            .chars()
+            .next()
+            .filter(|c| matches!(c, '"' | '\'' | '`'))
+        else {
+            continue;
+        };
+        let Some(url_length) = arguments[1..].find(quote) else {
+            continue;
+        };
+        fetch_calls.push(FetchCall {
+            method: fetch_method(&arguments[url_length + 2..]),
+            path: url_path(&arguments[1..1 + url_length]),
+        });
+    }
+
+    fetch_calls
+}
+
+// The method of a fetch() call from what follows its URL: GET without options, the `method`
+// of an object literal, None when the options are a variable
+fn fetch_method(rest_of_call: &str) -> Option<HttpMethod> {
+    let Some(options) = rest_of_call.trim_start().strip_prefix(',') else {
+        return Some(HttpMethod::Get);
+    };
+    let options = options.trim_start();
+    if options.starts_with(')') {
+        return Some(HttpMethod::Get);
+    }
+    let object = options.strip_prefix('{')?;
+
+    // only the object literal itself, not the code after the call
+    let mut depth = 1;
+    let object_length = object
+        .char_indices()
+        .find(|&(_, c)| {
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                _ => {}
+            }
+            depth == 0
+        })
+        .map_or(object.len(), |(index, _)| index);
+    let object = &object[..object_length];
+
+    let Some(method_start) = object.find("method") else {
+        return Some(HttpMethod::Get);
+    };
+    let value = object[method_start + "method".len()..]
+        .trim_start()
+        .strip_prefix(':')?
+        .trim_start();
+    let quote = value
+        .chars()
+        .next()
+        .filter(|c| matches!(c, '"' | '\'' | '`'))?;
+    let method = value[1..].split(quote).next()?;
+    HttpMethod::from_str(method).ok()
+}
+
+// Strip the origin off a fetch URL: either a literal one or a leading ${BASE_URL} placeholder
+fn url_path(url: &str) -> String {
+    let mut path = url;
+    if let Some(scheme_end) = path.find("://") {
+        let host_and_path = &path[scheme_end + 3..];
+        path = host_and_path
+            .find('/')
+            .map_or("/", |path_start| &host_and_path[path_start..]);
+    } else if path.starts_with("${") {
+        if let Some(placeholder_end) = path.find('}') {
+            path = &path[placeholder_end + 1..];
+        }
+    }
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+
+    if path.is_empty() {
+        "/".to_string()
+    } else {
+        path.to_string()
+    }
+}
+
+#[async_trait]
+impl SpecialFunctions for AgentFrontendDeveloper {
+    fn get_attributes_from_agent(&self) -> &BasicAgent {
+        &self.attributes
+    }
+
+    // the backend code is run to check its CORS headers
+    fn reads(&self) -> Vec<FactSheetField> {
+        vec![
+            FactSheetField::ProjectDescription,
+            FactSheetField::BackendCode,
+            FactSheetField::ApiEndpointSchema,
+        ]
+    }
+
+    fn writes(&self) -> Vec<FactSheetField> {
+        vec![FactSheetField::FrontendCode]
+    }
+
+    async fn execute(&mut self, fact_sheet: &mut FactSheet) -> Result<(), AgentError> {
+        while self.attributes.state != AgentState::Finished {
+            match self.attributes.state {
+                AgentState::Discovering => {
+                    self.call_initial_frontend_code(fact_sheet).await?;
+                    self.attributes.state = AgentState::Validation;
+                }
+                AgentState::Working => {
+                    self.call_fix_frontend_bugs(fact_sheet).await?;
+                    self.attributes.state = AgentState::Validation;
+                }
+                AgentState::Validation => {
+                    let (origin, bugs) = self.find_frontend_bugs(fact_sheet).await?;
+
+                    if bugs.is_empty() {
+                        let cors_bugs = self.find_cors_bugs(fact_sheet, &origin).await?;
+                        if cors_bugs.is_empty() {
+                            PrintCommand::UnitTest.print_agent_message(
+                                self.attributes.position.as_str(),
+                                "Frontend testing complete...",
+                            );
+                            self.attributes.state = AgentState::Finished;
+                            continue;
+                        }
+
+                        // the backend is the QA engineer's to fix, the frontend can't help it
+                        PrintCommand::Issue.print_agent_message(
+                            self.attributes.position.as_str(),
+                            "Frontend Unit Testing: Backend refuses the frontend...",
+                        );
+                        return Err(AgentError::Validation(format!(
+                            "MISSING CORS HEADERS: the frontend is served from {}, another origin \
+                             than the backend, and the browser refuses these responses:\n{}",
+                            origin,
+                            cors_bugs.join("\n")
+                        )));
+                    }
+
+                    self.bug_count += 1;
+                    self.bug_errors = Some(bugs.join("\n"));
+
+                    // Exit if too many bugs
+                    if self.bug_count > MAX_FRONTEND_BUG_RETRIES {
+                        PrintCommand::Issue.print_agent_message(
+                            self.attributes.position.as_str(),
+                            "Frontend Unit Testing: Too many bugs found in code...",
+                        );
+                        return Err(AgentError::Validation(format!(
+                            "too many frontend bugs: {}",
+                            bugs.join("\n")
+                        )));
+                    }
+
+                    // Pass back for rework
+                    self.attributes.state = AgentState::Working;
+                }
+                _ => self.attributes.state = AgentState::Finished,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_fetch_calls() {
+        let frontend_code = r#"
+            const API_URL = "http://localhost:8080";
+            fetch("http://localhost:8080/items").then(r => r.json());
+            fetch(`${API_URL}/item/${id}`, { method: "DELETE" });
+            fetch('/item', { headers: { "Content-Type": "application/json" }, method: 'post' });
+            fetch('/health?verbose=true', options);
+            fetch(url);
+        "#;
+
+        let fetch_call = |method: Option<HttpMethod>, path: &str| FetchCall {
+            method,
+            path: path.to_string(),
+        };
+        assert_eq!(
+            extract_fetch_calls(frontend_code),
+            vec![
+                fetch_call(Some(HttpMethod::Get), "/items"),
+                fetch_call(Some(HttpMethod::Delete), "/item/${id}"),
+                fetch_call(Some(HttpMethod::Post), "/item"),
+                fetch_call(None, "/health"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fetch_call_matches_method() {
+        let route_object: RouteObject = serde_json::from_value(serde_json::json!({
+            "route": "/item",
+            "method": "get",
+            "request_body": "None",
+            "response": "None"
+        }))
+        .unwrap();
+        let calls =
+            |frontend_code: &str| extract_fetch_calls(frontend_code)[0].calls(&route_object);
+
+        assert!(calls("fetch('/item')"));
+        assert!(!calls("fetch('/item', { method: 'DELETE' })"));
+        // the method of variable options could be anything
+        assert!(calls("fetch('/item', options)"));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "openai-coverage")]
+    async fn test_writing_frontend_code() {
+        let mut agent = AgentFrontendDeveloper::new(AgentContext::default());
+        let factsheet_string: &str = r#"
+      {
+        "project_description": "build a website that tracks todo items",
+        "project_scope": {
+          "is_crud_required": true,
+          "is_user_login_and_logout": false,
+          "is_external_urls_required": false
+        },
+        "external_urls": [],
+        "backend_code": null,
+        "api_endpoint_schema": [
+          {
+            "route": "/item/{id}",
+            "is_route_dynamic": true,
+            "method": "get",
+            "request_body": "None",
+            "response": { "id": "number", "name": "string", "completed": "bool" }
+          }
+        ],
+        "frontend_code": null
+      }"#;
+
+        let mut fact_sheet: FactSheet = serde_json::from_str(factsheet_string).unwrap();
+
+        agent
+            .execute(&mut fact_sheet)
+            .await
+            .expect("Failed to execute Frontend Developer agent");
+
+        assert!(fact_sheet.frontend_code.is_some());
+    }
+}
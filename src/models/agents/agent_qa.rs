@@ -0,0 +1,425 @@
+use async_trait::async_trait;
+use strum::IntoEnumIterator;
+
+use crate::{
+    ai_functions::aifunc_qa::{print_api_integration_tests, print_fixed_api_integration_tests},
+    helpers::{
+        command_line::{save_workspace_test, PrintCommand},
+        general::{ai_task_request, find_cors_bugs},
+        workspace::test_workspace,
+    },
+    models::{
+        agent_basic::basic_agent::{AgentState, BasicAgent},
+        general::{
+            agent_error::AgentError,
+            api_route::{route_matches, HttpMethod},
+        },
+    },
+};
+
+use super::{
+    agent_backend::AgentBackendDeveloper,
+    agent_context::AgentContext,
+    agent_traits::{FactSheet, FactSheetField, RouteObject, SpecialFunctions},
+};
+
+// broken test code is fixed by the QA engineer, failing tests and missing CORS headers by the
+// backend developer
+const MAX_TEST_CODE_BUG_RETRIES: i8 = 2;
+const MAX_BACKEND_FIX_ROUNDS: i8 = 2;
+pub const API_TEST_NAME: &str = "api";
+// a static frontend on a port of its own, the frontend developer checks its real origin again
+const FRONTEND_ORIGIN: &str = "http://127.0.0.1:5500";
+// makes every item of the generated main.rs visible to the tests,
+// the modules it declares are still found next to it in src/
+const API_TEST_PREAMBLE: &str = concat!(
+    "// Generated by Auto-Gippity: integration tests for every API endpoint.\n",
+    "// Run with: cargo test --test api\n",
+    "#![allow(dead_code, unused_imports)]\n",
+    "include!(\"../src/main.rs\");\n\n",
+);
+
+#[derive(Debug, PartialEq)]
+enum TestOutcome {
+    Passed,
+    // the tests themselves need fixing
+    BrokenTests(String),
+    // the tests ran and the server did not behave, or refused requests from the frontend
+    FailingTests(String),
+}
+
+#[derive(Debug)]
+pub struct AgentQaEngineer {
+    attributes: BasicAgent,
+    context: AgentContext,
+    api_tests: Option<String>,
+    bug_errors: Option<String>,
+    bug_count: i8,
+    backend_fix_rounds: i8,
+    // set when the backend developer rolled back instead of fixing the failing tests
+    degraded: Option<String>,
+}
+
+impl AgentQaEngineer {
+    pub fn new(context: AgentContext) -> Self {
+        let attributes = BasicAgent {
+            objective: "Writes and runs integration tests for every webserver endpoint".to_string(),
+            position: "QA Engineer".to_string(),
+            state: AgentState::Discovering,
+            memory: vec![],
+        };
+
+        Self {
+            attributes,
+            context,
+            api_tests: None,
+            bug_errors: None,
+            bug_count: 0,
+            backend_fix_rounds: 0,
+            degraded: None,
+        }
+    }
+
+    async fn call_initial_api_tests(&mut self, fact_sheet: &FactSheet) -> Result<(), AgentError> {
+        let msg_context = format!(
+            "CODE_INPUT: {}\n API_ENDPOINTS: {}\n",
+            fact_sheet.backend_code.clone().unwrap_or_default().render(),
+            serde_json::to_string(&fact_sheet.api_endpoint_schema)
+                .map_err(AgentError::encode("API endpoints"))?
+        );
+
+        let ai_response: String = ai_task_request(
+            self.context.provider.as_ref(),
+            msg_context,
+            &self.attributes.position,
+            get_function_string!(print_api_integration_tests),
+            print_api_integration_tests,
+        )
+        .await?;
+
+        self.save_api_tests(ai_response)
+    }
+
+    async fn call_fix_api_tests(&mut self, fact_sheet: &FactSheet) -> Result<(), AgentError> {
+        let msg_context = format!(
+            "BROKEN_TESTS: {:?}\n CODE_INPUT: {}\n API_ENDPOINTS: {}\n ERROR_BUGS: {:?}\n
+            THIS FUNCTION ONLY OUTPUTS CODE. JUST OUTPUT THE CODE.",
+            self.api_tests,
+            fact_sheet.backend_code.clone().unwrap_or_default().render(),
+            serde_json::to_string(&fact_sheet.api_endpoint_schema)
+                .map_err(AgentError::encode("API endpoints"))?,
+            self.bug_errors
+        );
+
+        let ai_response: String = ai_task_request(
+            self.context.provider.as_ref(),
+            msg_context,
+            &self.attributes.position,
+            get_function_string!(print_fixed_api_integration_tests),
+            print_fixed_api_integration_tests,
+        )
+        .await?;
+
+        self.save_api_tests(ai_response)
+    }
+
+    fn save_api_tests(&mut self, tests: String) -> Result<(), AgentError> {
+        let api_tests = format!("{}{}", API_TEST_PREAMBLE, tests);
+        save_workspace_test(&format!("{}.rs", API_TEST_NAME), &api_tests)?;
+        self.api_tests = Some(api_tests);
+        Ok(())
+    }
+
+    async fn run_api_tests(&self, fact_sheet: &FactSheet) -> Result<TestOutcome, AgentError> {
+        let api_tests = self.api_tests.as_deref().unwrap_or_default();
+        let uncovered_routes = uncovered_routes(api_tests, &fact_sheet.api_endpoint_schema);
+        if !uncovered_routes.is_empty() {
+            return Ok(TestOutcome::BrokenTests(format!(
+                "No test calls these API_ENDPOINTS: {}",
+                uncovered_routes.join(", ")
+            )));
+        }
+
+        PrintCommand::UnitTest.print_agent_message(
+            self.attributes.position.as_str(),
+            "Integration Testing: running cargo test...",
+        );
+        let test_output = test_workspace(&self.context.sandbox, API_TEST_NAME).await?;
+        let test_outcome = classify_test_output(
+            test_output.status.success(),
+            &String::from_utf8_lossy(&test_output.stdout),
+            &String::from_utf8_lossy(&test_output.stderr),
+        );
+        if test_outcome != TestOutcome::Passed {
+            return Ok(test_outcome);
+        }
+
+        // the frontend calls the backend from another origin, so the backend is fixed here,
+        // before the frontend developer runs
+        let routes: Vec<&RouteObject> = fact_sheet.api_endpoint_schema.iter().collect();
+        let cors_bugs = find_cors_bugs(
+            &self.context.sandbox,
+            &self.attributes.position,
+            &routes,
+            FRONTEND_ORIGIN,
+        )
+        .await?;
+        if cors_bugs.is_empty() {
+            return Ok(TestOutcome::Passed);
+        }
+        Ok(TestOutcome::FailingTests(format!(
+            "MISSING CORS HEADERS: the frontend is served from {}, another origin than the \
+             backend, and the browser refuses these responses:\n{}",
+            FRONTEND_ORIGIN,
+            cors_bugs.join("\n")
+        )))
+    }
+}
+
+// Routes, with their method, that none of the requests of the tests call
+fn uncovered_routes(api_tests: &str, api_endpoints: &[RouteObject]) -> Vec<String> {
+    let tested_requests = tested_requests(api_tests);
+
+    api_endpoints
+        .iter()
+        .filter(|route_object| {
+            !tested_requests.iter().any(|(method, path)| {
+                *method == route_object.method && route_matches(&route_object.route, path)
+            })
+        })
+        .map(|route_object| format!("{} {}", route_object.method, route_object.route))
+        .collect()
+}
+
+// (method, path) of the requests of the tests: every string literal that is a path, with the
+// method named closest to it in the same statement, e.g. TestRequest::post().uri("/item")
+fn tested_requests(api_tests: &str) -> Vec<(HttpMethod, &str)> {
+    let quotes: Vec<usize> = api_tests
+        .match_indices('"')
+        .map(|(index, _)| index)
+        .collect();
+
+    quotes
+        .chunks_exact(2)
+        .filter_map(|literal| {
+            let (start, end) = (literal[0] + 1, literal[1]);
+            let path = &api_tests[start..end];
+            // routes the test app registers aren't requests
+            let is_registration = ["route(", "resource(", "scope("]
+                .iter()
+                .any(|call| api_tests[..start - 1].trim_end().ends_with(call));
+            if !path.starts_with('/') || is_registration {
+                return None;
+            }
+            let statement_start = api_tests[..start]
+                .rfind([';', '{', '}'])
+                .map_or(0, |index| index + 1);
+            let statement_end = api_tests[end..]
+                .find(';')
+                .map_or(api_tests.len(), |index| end + index);
+            let method = closest_method(
+                &api_tests[statement_start..statement_end],
+                start - statement_start,
+            )?;
+            Some((method, path.split('?').next().unwrap_or_default()))
+        })
+        .collect()
+}
+
+// The method named nearest to `position`, as Method::POST, TestRequest::post(), client.post( or "POST"
+fn closest_method(statement: &str, position: usize) -> Option<HttpMethod> {
+    HttpMethod::iter()
+        .flat_map(|method| {
+            let lowercase = method.as_ref();
+            let uppercase = lowercase.to_uppercase();
+            [
+                format!("Method::{}", uppercase),
+                format!("::{}(", lowercase),
+                format!(".{}(", lowercase),
+                format!("\"{}\"", uppercase),
+            ]
+            .into_iter()
+            .flat_map(|pattern| {
+                statement
+                    .match_indices(&pattern)
+                    .map(|(index, _)| index.abs_diff(position))
+                    .collect::<Vec<usize>>()
+            })
+            .map(move |distance| (distance, method))
+        })
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, method)| method)
+}
+
+fn classify_test_output(success: bool, stdout: &str, stderr: &str) -> TestOutcome {
+    if success {
+        return TestOutcome::Passed;
+    }
+    // the test binary only prints a result line when it got to run
+    if stdout.contains("test result:") {
+        TestOutcome::FailingTests(format!("FAILING INTEGRATION TESTS:\n{}", stdout))
+    } else {
+        TestOutcome::BrokenTests(stderr.to_string())
+    }
+}
+
+#[async_trait]
+impl SpecialFunctions for AgentQaEngineer {
+    fn get_attributes_from_agent(&self) -> &BasicAgent {
+        &self.attributes
+    }
+
+    // the external urls are for the security review of the backend developer's fixes
+    fn reads(&self) -> Vec<FactSheetField> {
+        vec![
+            FactSheetField::ExternalUrls,
+            FactSheetField::BackendCode,
+            FactSheetField::ApiEndpointSchema,
+        ]
+    }
+
+    // through the backend developer, who fixes what the tests found
+    fn writes(&self) -> Vec<FactSheetField> {
+        vec![
+            FactSheetField::BackendCode,
+            FactSheetField::ApiEndpointSchema,
+            FactSheetField::SecurityReport,
+        ]
+    }
+
+    async fn execute(&mut self, fact_sheet: &mut FactSheet) -> Result<(), AgentError> {
+        while self.attributes.state != AgentState::Finished {
+            match self.attributes.state {
+                AgentState::Discovering => {
+                    self.call_initial_api_tests(fact_sheet).await?;
+                    self.attributes.state = AgentState::Validation;
+                }
+                AgentState::Working => {
+                    self.call_fix_api_tests(fact_sheet).await?;
+                    self.attributes.state = AgentState::Validation;
+                }
+                AgentState::Validation => match self.run_api_tests(fact_sheet).await? {
+                    TestOutcome::Passed => {
+                        PrintCommand::UnitTest.print_agent_message(
+                            self.attributes.position.as_str(),
+                            "Integration testing complete...",
+                        );
+                        self.attributes.state = AgentState::Finished;
+                    }
+                    TestOutcome::BrokenTests(bug_errors) => {
+                        self.bug_count += 1;
+                        self.bug_errors = Some(bug_errors.clone());
+
+                        // Exit if too many bugs
+                        if self.bug_count > MAX_TEST_CODE_BUG_RETRIES {
+                            PrintCommand::Issue.print_agent_message(
+                                self.attributes.position.as_str(),
+                                "Integration Testing: Too many bugs found in tests...",
+                            );
+                            return Err(AgentError::Validation(format!(
+                                "too many bugs in integration tests: {}",
+                                bug_errors
+                            )));
+                        }
+
+                        // Pass back for rework
+                        self.attributes.state = AgentState::Working;
+                    }
+                    TestOutcome::FailingTests(test_report) => {
+                        self.backend_fix_rounds += 1;
+                        if self.backend_fix_rounds > MAX_BACKEND_FIX_ROUNDS {
+                            PrintCommand::Issue.print_agent_message(
+                                self.attributes.position.as_str(),
+                                "Integration Testing: Backend still fails its tests...",
+                            );
+                            return Err(AgentError::Validation(format!(
+                                "too many failing integration tests: {}",
+                                test_report
+                            )));
+                        }
+
+                        PrintCommand::Issue.print_agent_message(
+                            self.attributes.position.as_str(),
+                            "Integration Testing: Tests failed, handing back to the backend developer...",
+                        );
+                        let mut backend_developer = AgentBackendDeveloper::with_bug_report(
+                            self.context.clone(),
+                            test_report,
+                            fact_sheet,
+                        );
+                        backend_developer.execute(fact_sheet).await?;
+                        // rolled back to the code that just failed, testing it again won't help
+                        if let Some(reason) = backend_developer.degraded() {
+                            self.degraded = Some(format!(
+                                "integration tests still fail, the backend {}",
+                                reason
+                            ));
+                            self.attributes.state = AgentState::Finished;
+                            continue;
+                        }
+                        // tests are run again against the fixed backend
+                    }
+                },
+                _ => self.attributes.state = AgentState::Finished,
+            }
+        }
+        Ok(())
+    }
+
+    fn degraded(&self) -> Option<String> {
+        self.degraded.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uncovered_routes() {
+        let api_endpoints: Vec<RouteObject> = serde_json::from_value(serde_json::json!([
+            { "route": "/item/{id}", "method": "get", "request_body": "None", "response": "None" },
+            { "route": "/items", "method": "get", "request_body": "None", "response": "None" },
+            { "route": "/item", "method": "post", "request_body": "None", "response": "None" },
+            { "route": "/item/{id}", "method": "delete", "request_body": "None", "response": "None" }
+        ]))
+        .unwrap();
+        let api_tests = r#"
+            let app = test::init_service(App::new().route("/item", web::post().to(create_item))).await;
+            let req = test::TestRequest::get().uri("/item/1").to_request();
+            let req = test::TestRequest::get().uri("/items?page=2").to_request();
+            let response = client.request(reqwest::Method::DELETE, "/item/2").send().await;
+        "#;
+
+        // neither registering POST /item nor a GET of /item/1 covers the POST to /item
+        assert_eq!(
+            uncovered_routes(api_tests, &api_endpoints),
+            vec!["post /item"]
+        );
+
+        let api_tests = r#"
+            let req = test::TestRequest::default()
+                .uri("/item")
+                .method(Method::POST)
+                .set_json(json!({ "name": "milk" }))
+                .to_request();
+        "#;
+        assert_eq!(
+            tested_requests(api_tests),
+            vec![(HttpMethod::Post, "/item")]
+        );
+    }
+
+    #[test]
+    fn test_classify_test_output() {
+        assert_eq!(classify_test_output(true, "", ""), TestOutcome::Passed);
+        assert_eq!(
+            classify_test_output(false, "", "error[E0425]: cannot find value"),
+            TestOutcome::BrokenTests("error[E0425]: cannot find value".to_string())
+        );
+        assert!(matches!(
+            classify_test_output(false, "test result: FAILED. 1 passed; 1 failed", ""),
+            TestOutcome::FailingTests(_)
+        ));
+    }
+}
@@ -0,0 +1,385 @@
+use async_trait::async_trait;
+
+use crate::{
+    helpers::{command_line::PrintCommand, source_files::SourceFiles},
+    models::{
+        agent_basic::basic_agent::{AgentState, BasicAgent},
+        general::agent_error::AgentError,
+    },
+};
+
+use super::agent_traits::{
+    FactSheet, FactSheetField, SecurityFinding, SecurityReport, Severity, SpecialFunctions,
+};
+
+const FILESYSTEM_WRITES: [&str; 8] = [
+    "fs::write",
+    "File::create",
+    "OpenOptions",
+    "fs::remove_file",
+    "fs::remove_dir",
+    "fs::rename",
+    "fs::copy",
+    "fs::create_dir",
+];
+const SECRET_NAMES: [&str; 5] = ["secret", "password", "api_key", "apikey", "token"];
+const LOCAL_HOSTS: [&str; 3] = ["localhost", "127.0.0.1", "0.0.0.0"];
+// shorter literals are too likely to be field names or placeholders
+const MIN_SECRET_LEN: usize = 8;
+
+// Findings at or above `block_at` stop the project before anything is built
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SecurityPolicy {
+    pub block_at: Severity,
+}
+
+impl Default for SecurityPolicy {
+    fn default() -> Self {
+        Self {
+            block_at: Severity::Critical,
+        }
+    }
+}
+
+impl SecurityPolicy {
+    pub fn blocks(&self, security_report: &SecurityReport) -> bool {
+        security_report
+            .highest_severity()
+            .is_some_and(|severity| severity >= self.block_at)
+    }
+}
+
+#[derive(Debug)]
+pub struct AgentSecurityReviewer {
+    attributes: BasicAgent,
+    policy: SecurityPolicy,
+}
+
+impl AgentSecurityReviewer {
+    pub fn new() -> Self {
+        Self::with_policy(SecurityPolicy::default())
+    }
+
+    pub fn with_policy(policy: SecurityPolicy) -> Self {
+        let attributes = BasicAgent {
+            objective:
+                "Statically reviews generated code for dangerous patterns before it is built"
+                    .to_string(),
+            position: "Security Reviewer".to_string(),
+            state: AgentState::Discovering,
+            memory: vec![],
+        };
+
+        Self { attributes, policy }
+    }
+}
+
+impl Default for AgentSecurityReviewer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// "a "b" c" -> code parts ["a ", " c"] and literals ["b"]
+fn split_literals(line: &str) -> (String, Vec<&str>) {
+    let code = line.split('"').step_by(2).collect::<Vec<&str>>().join(" ");
+    let literals = line.split('"').skip(1).step_by(2).collect();
+    (code, literals)
+}
+
+fn has_word(code: &str, word: &str) -> bool {
+    code.split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .any(|token| token == word)
+}
+
+// "https://api.example.com:443/v1?x=1" -> "https://api.example.com:443"
+pub fn url_origin(url: &str) -> &str {
+    let host_start = url.find("://").map_or(0, |i| i + 3);
+    match url[host_start..].find(['/', '?', '#']) {
+        Some(i) => &url[..host_start + i],
+        None => url,
+    }
+}
+
+fn is_local_url(url: &str) -> bool {
+    let host = url_origin(url).split("://").last().unwrap_or_default();
+    let host = host.split(':').next().unwrap_or_default();
+    LOCAL_HOSTS.contains(&host)
+}
+
+fn is_outside_workspace(path: &str) -> bool {
+    path.starts_with('/') || path.starts_with('~') || path.split('/').any(|part| part == "..")
+}
+
+fn looks_like_secret(literal: &str) -> bool {
+    let is_openai_key = literal.starts_with("sk-") && literal.len() >= 20;
+    let is_aws_key = literal.len() == 20
+        && literal.starts_with("AKIA")
+        && literal
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit());
+    is_openai_key || is_aws_key || literal.contains("PRIVATE KEY-----")
+}
+
+fn is_secret_value(literal: &str) -> bool {
+    literal.len() >= MIN_SECRET_LEN
+        && !literal.contains(char::is_whitespace)
+        && !literal.starts_with('/')
+        && !literal.contains("://")
+        && !literal.contains("{}")
+}
+
+// Line based scan of every generated file, comments are skipped
+pub fn scan_code(files: &SourceFiles, external_urls: &[String]) -> SecurityReport {
+    let allowed_origins: Vec<&str> = external_urls.iter().map(|url| url_origin(url)).collect();
+    let mut findings: Vec<SecurityFinding> = vec![];
+
+    for (path, code) in files.iter() {
+        scan_file(path, code, &allowed_origins, &mut findings);
+    }
+
+    SecurityReport { findings }
+}
+
+fn scan_file(
+    path: &str,
+    code: &str,
+    allowed_origins: &[&str],
+    findings: &mut Vec<SecurityFinding>,
+) {
+    for (index, line) in code.lines().enumerate() {
+        let snippet = line.trim();
+        if snippet.starts_with("//") {
+            continue;
+        }
+        let (code_part, literals) = split_literals(line);
+        let mut flag = |severity: Severity, rule: &str, message: String| {
+            findings.push(SecurityFinding {
+                severity,
+                rule: rule.to_string(),
+                file: path.to_string(),
+                line: index + 1,
+                snippet: snippet.to_string(),
+                message,
+            })
+        };
+
+        if code_part.contains("process::Command") || code_part.contains("Command::new") {
+            flag(
+                Severity::Critical,
+                "process-command",
+                "Spawns processes with std::process::Command".to_string(),
+            );
+        }
+
+        if FILESYSTEM_WRITES
+            .iter()
+            .any(|write| code_part.contains(write))
+        {
+            if let Some(path) = literals.iter().find(|path| is_outside_workspace(path)) {
+                flag(
+                    Severity::Critical,
+                    "filesystem-write",
+                    format!("Writes to '{}', outside of the workspace", path),
+                );
+            } else if literals.is_empty() {
+                flag(
+                    Severity::Medium,
+                    "filesystem-write",
+                    "Writes to a computed path, check that it stays inside the workspace"
+                        .to_string(),
+                );
+            }
+        }
+
+        if has_word(&code_part, "unsafe") {
+            flag(
+                Severity::High,
+                "unsafe-code",
+                "Uses unsafe Rust".to_string(),
+            );
+        }
+
+        for url in literals
+            .iter()
+            .filter(|literal| literal.starts_with("http://") || literal.starts_with("https://"))
+            .filter(|url| !is_local_url(url) && !allowed_origins.contains(&url_origin(url)))
+        {
+            flag(
+                Severity::Medium,
+                "outbound-url",
+                format!("Calls '{}', which is not one of the external urls", url),
+            );
+        }
+
+        let code_lowercase = code_part.to_lowercase();
+        let is_secret_assignment = SECRET_NAMES
+            .iter()
+            .any(|name| code_lowercase.contains(name))
+            && code_part.contains('=')
+            && literals.iter().any(|literal| is_secret_value(literal));
+        if is_secret_assignment || literals.iter().any(|literal| looks_like_secret(literal)) {
+            flag(
+                Severity::High,
+                "hard-coded-secret",
+                "Hard-codes a secret, read it from an environment variable instead".to_string(),
+            );
+        }
+    }
+}
+
+#[async_trait]
+impl SpecialFunctions for AgentSecurityReviewer {
+    fn get_attributes_from_agent(&self) -> &BasicAgent {
+        &self.attributes
+    }
+
+    fn reads(&self) -> Vec<FactSheetField> {
+        vec![FactSheetField::ExternalUrls, FactSheetField::BackendCode]
+    }
+
+    fn writes(&self) -> Vec<FactSheetField> {
+        vec![FactSheetField::SecurityReport]
+    }
+
+    async fn execute(&mut self, fact_sheet: &mut FactSheet) -> Result<(), AgentError> {
+        while self.attributes.state != AgentState::Finished {
+            match self.attributes.state {
+                AgentState::Discovering => {
+                    PrintCommand::UnitTest.print_agent_message(
+                        self.attributes.position.as_str(),
+                        "Security Review: scanning generated code...",
+                    );
+                    let backend_code = fact_sheet.backend_code.clone().unwrap_or_default();
+                    fact_sheet.security_report =
+                        Some(scan_code(&backend_code, &fact_sheet.external_urls));
+                    self.attributes.state = AgentState::Validation;
+                }
+                AgentState::Validation => {
+                    let security_report = fact_sheet.security_report.clone().unwrap_or_default();
+                    if self.policy.blocks(&security_report) {
+                        PrintCommand::Issue.print_agent_message(
+                            self.attributes.position.as_str(),
+                            format!(
+                                "Security Review: findings at {} or above block this project",
+                                self.policy.block_at
+                            )
+                            .as_str(),
+                        );
+                        let findings: Vec<String> = security_report
+                            .findings
+                            .iter()
+                            .filter(|finding| finding.severity >= self.policy.block_at)
+                            .map(|finding| finding.to_string())
+                            .collect();
+                        return Err(AgentError::Validation(format!(
+                            "AI generated code was blocked by the security policy:\n{}",
+                            findings.join("\n")
+                        )));
+                    }
+                    self.attributes.state = AgentState::Finished;
+                }
+                _ => self.attributes.state = AgentState::Finished,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(security_report: &SecurityReport) -> Vec<(&str, Severity)> {
+        security_report
+            .findings
+            .iter()
+            .map(|finding| (finding.rule.as_str(), finding.severity))
+            .collect()
+    }
+
+    #[test]
+    fn test_scan_code() {
+        let code = r#"
+            use std::process::Command;
+            // unsafe { this is only a comment }
+            let _ = fs::write("../secrets.txt", data);
+            let _ = fs::write("db.json", data);
+            let _ = File::create(path);
+            let len = unsafe { libc::strlen(ptr) };
+            let weather = reqwest::get("https://api.weather.com/v1/today").await;
+            let crypto = reqwest::get("https://api.coincap.io/v2/assets?limit=5").await;
+            HttpServer::new(app).bind("127.0.0.1:8080")?;
+            let local = "http://localhost:8080/items";
+            let jwt_secret = "hunter2hunter2";
+            let key = "sk-abcdefghijklmnopqrstuvwx";
+            println!("the token is invalid");
+        "#;
+        let external_urls = vec!["https://api.coincap.io/v2/assets".to_string()];
+
+        let security_report = scan_code(&SourceFiles::from_main(code), &external_urls);
+
+        assert_eq!(
+            rules(&security_report),
+            vec![
+                ("process-command", Severity::Critical),
+                ("filesystem-write", Severity::Critical),
+                ("filesystem-write", Severity::Medium),
+                ("unsafe-code", Severity::High),
+                ("outbound-url", Severity::Medium),
+                ("hard-coded-secret", Severity::High),
+                ("hard-coded-secret", Severity::High),
+            ]
+        );
+        assert_eq!(security_report.findings[0].line, 2);
+        assert_eq!(
+            security_report.findings[0].to_string(),
+            "[CRITICAL] src/main.rs:2 (process-command): Spawns processes with std::process::Command\n    use std::process::Command;"
+        );
+        assert_eq!(security_report.highest_severity(), Some(Severity::Critical));
+    }
+
+    #[test]
+    fn test_security_policy() {
+        let security_report = scan_code(
+            &SourceFiles::from_main("let len = unsafe { strlen(ptr) };"),
+            &[],
+        );
+
+        assert!(!SecurityPolicy::default().blocks(&security_report));
+        assert!(SecurityPolicy {
+            block_at: Severity::High
+        }
+        .blocks(&security_report));
+        assert!(!SecurityPolicy::default().blocks(&SecurityReport::default()));
+    }
+
+    #[tokio::test]
+    async fn test_reviewer_policy() {
+        let mut fact_sheet: FactSheet = serde_json::from_value(serde_json::json!({
+            "project_description": "build a website",
+            "project_scope": null,
+            "external_urls": [],
+            "backend_code": null,
+            "api_endpoint_schema": []
+        }))
+        .unwrap();
+        fact_sheet.backend_code = Some(SourceFiles::from_main("let len = unsafe { strlen(ptr) };"));
+
+        // unsafe code is a high finding, below the default threshold
+        assert!(AgentSecurityReviewer::new()
+            .execute(&mut fact_sheet.clone())
+            .await
+            .is_ok());
+        let blocked = AgentSecurityReviewer::with_policy(SecurityPolicy {
+            block_at: Severity::High,
+        })
+        .execute(&mut fact_sheet)
+        .await;
+        assert!(matches!(blocked, Err(AgentError::Validation(e)) if e.contains("unsafe-code")));
+        assert_eq!(
+            fact_sheet.security_report.unwrap().highest_severity(),
+            Some(Severity::High)
+        );
+    }
+}
@@ -1,17 +1,52 @@
+use std::fmt;
+
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter};
 
-use crate::models::agent_basic::basic_agent::BasicAgent;
+use crate::{
+    helpers::source_files::SourceFiles,
+    models::{
+        agent_basic::basic_agent::BasicAgent,
+        general::agent_error::AgentError,
+        general::api_route::{
+            deserialize_lenient_bool, parse_path_params, HttpMethod, ShapeDescriptor,
+        },
+    },
+};
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(from = "RawRouteObject")]
 pub struct RouteObject {
-    pub is_route_dynamic: String,
-    pub method: String,
-    pub request_body: serde_json::Value,
-    pub response: serde_json::Value,
+    pub is_route_dynamic: bool,
+    pub method: HttpMethod,
+    pub request_body: ShapeDescriptor,
+    pub response: ShapeDescriptor,
     pub route: String,
 }
 
+// RouteObject as printed by the LLM, its is_route_dynamic flag is left out for the route to decide
+#[derive(Deserialize)]
+struct RawRouteObject {
+    method: HttpMethod,
+    request_body: ShapeDescriptor,
+    response: ShapeDescriptor,
+    route: String,
+}
+
+impl From<RawRouteObject> for RouteObject {
+    fn from(raw: RawRouteObject) -> Self {
+        Self {
+            is_route_dynamic: !parse_path_params(&raw.route).is_empty(),
+            method: raw.method,
+            request_body: raw.request_body,
+            response: raw.response,
+            route: raw.route,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
 pub struct ProjectScope {
     pub is_crud_required: bool,
@@ -19,23 +54,301 @@ pub struct ProjectScope {
     pub is_external_urls_required: bool,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ColumnSchema {
+    pub name: String,
+    // SQLite type affinity: INTEGER, TEXT, REAL, BLOB or NUMERIC
+    pub sql_type: String,
+    #[serde(default, deserialize_with = "deserialize_lenient_bool")]
+    pub is_primary_key: bool,
+    #[serde(default, deserialize_with = "deserialize_lenient_bool")]
+    pub is_nullable: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TableSchema {
+    pub name: String,
+    pub columns: Vec<ColumnSchema>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DatabaseSchema {
+    pub tables: Vec<TableSchema>,
+}
+
+// Ordered from least to most dangerous, so that policies can compare severities
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "UPPERCASE")]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SecurityFinding {
+    pub severity: Severity,
+    pub rule: String,
+    // path of the backend file, empty in reports from before the backend was split into files
+    #[serde(default)]
+    pub file: String,
+    pub line: usize,
+    pub snippet: String,
+    pub message: String,
+}
+
+impl fmt::Display for SecurityFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let location = if self.file.is_empty() {
+            format!("line {}", self.line)
+        } else {
+            format!("{}:{}", self.file, self.line)
+        };
+        write!(
+            f,
+            "[{}] {} ({}): {}\n    {}",
+            self.severity, location, self.rule, self.message, self.snippet
+        )
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct SecurityReport {
+    pub findings: Vec<SecurityFinding>,
+}
+
+impl SecurityReport {
+    pub fn highest_severity(&self) -> Option<Severity> {
+        self.findings.iter().map(|finding| finding.severity).max()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct CodeReview {
+    #[serde(default)]
+    pub missing_requirements: Vec<String>,
+    #[serde(default)]
+    pub error_handling_gaps: Vec<String>,
+    // filled in from the code itself rather than by the LLM
+    #[serde(default)]
+    pub unused_external_urls: Vec<String>,
+}
+
+// Every part of the fact sheet that agents can read or write
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumIter)]
+#[strum(serialize_all = "snake_case")]
+pub enum FactSheetField {
+    ProjectDescription,
+    ProjectScope,
+    ExternalUrls,
+    BackendCode,
+    ApiEndpointSchema,
+    FrontendCode,
+    DatabaseSchema,
+    SecurityReport,
+    CodeReview,
+    DeploymentFiles,
+}
+
+impl FactSheetField {
+    // What the agents writing the field save into the generated project, relative to its root.
+    // The API schema files are exported by the managing agent from the merged fact sheet
+    pub fn workspace_paths(&self) -> &'static [&'static str] {
+        match self {
+            Self::BackendCode => &["src", "tests", "Cargo.toml", "Cargo.lock"],
+            Self::FrontendCode => &["frontend"],
+            Self::DatabaseSchema => &["migrations", "Cargo.toml", "Cargo.lock"],
+            Self::CodeReview => &["CODE_REVIEW.md"],
+            Self::DeploymentFiles => &["Dockerfile", ".dockerignore", "docker-compose.yml"],
+            Self::ProjectDescription
+            | Self::ProjectScope
+            | Self::ExternalUrls
+            | Self::ApiEndpointSchema
+            | Self::SecurityReport => &[],
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct FactSheet {
     pub project_description: String,
     pub project_scope: Option<ProjectScope>,
     pub external_urls: Vec<String>,
-    pub backend_code: Option<String>,
+    pub backend_code: Option<SourceFiles>,
     pub api_endpoint_schema: Vec<RouteObject>,
+    pub frontend_code: Option<String>,
+    pub database_schema: Option<DatabaseSchema>,
+    pub security_report: Option<SecurityReport>,
+    pub code_review: Option<CodeReview>,
+    // files written next to the generated code to deploy it, e.g. its Dockerfile
+    #[serde(default)]
+    pub deployment_files: Vec<String>,
+}
+
+impl FactSheet {
+    // Fields that hold a value before any agent ran
+    pub fn provided_fields(&self) -> Vec<FactSheetField> {
+        [
+            (
+                FactSheetField::ProjectDescription,
+                !self.project_description.is_empty(),
+            ),
+            (FactSheetField::ProjectScope, self.project_scope.is_some()),
+            (FactSheetField::ExternalUrls, !self.external_urls.is_empty()),
+            (FactSheetField::BackendCode, self.backend_code.is_some()),
+            (
+                FactSheetField::ApiEndpointSchema,
+                !self.api_endpoint_schema.is_empty(),
+            ),
+            (FactSheetField::FrontendCode, self.frontend_code.is_some()),
+            (
+                FactSheetField::DatabaseSchema,
+                self.database_schema.is_some(),
+            ),
+            (
+                FactSheetField::SecurityReport,
+                self.security_report.is_some(),
+            ),
+            (FactSheetField::CodeReview, self.code_review.is_some()),
+            (
+                FactSheetField::DeploymentFiles,
+                !self.deployment_files.is_empty(),
+            ),
+        ]
+        .into_iter()
+        .filter(|(_, is_provided)| *is_provided)
+        .map(|(field, _)| field)
+        .collect()
+    }
+
+    // Fields holding a different value in the other fact sheet, e.g. after a person edited it
+    pub fn changed_fields(&self, other: &FactSheet) -> Vec<FactSheetField> {
+        FactSheetField::iter()
+            .filter(|&field| self.field_value(field) != other.field_value(field))
+            .collect()
+    }
+
+    // One field as JSON, under the name it is serialized with
+    pub fn field_value(&self, field: FactSheetField) -> serde_json::Value {
+        serde_json::to_value(self)
+            .ok()
+            .and_then(|mut fact_sheet| {
+                fact_sheet
+                    .get_mut(field.to_string())
+                    .map(|value| value.take())
+            })
+            .unwrap_or_default()
+    }
+
+    // Take over one field from the copy of the fact sheet that an agent worked on
+    pub fn merge_field(&mut self, other: &FactSheet, field: FactSheetField) {
+        match field {
+            FactSheetField::ProjectDescription => {
+                self.project_description = other.project_description.clone()
+            }
+            FactSheetField::ProjectScope => self.project_scope = other.project_scope,
+            FactSheetField::ExternalUrls => self.external_urls = other.external_urls.clone(),
+            FactSheetField::BackendCode => self.backend_code = other.backend_code.clone(),
+            FactSheetField::ApiEndpointSchema => {
+                self.api_endpoint_schema = other.api_endpoint_schema.clone()
+            }
+            FactSheetField::FrontendCode => self.frontend_code = other.frontend_code.clone(),
+            FactSheetField::DatabaseSchema => self.database_schema = other.database_schema.clone(),
+            FactSheetField::SecurityReport => self.security_report = other.security_report.clone(),
+            FactSheetField::CodeReview => self.code_review = other.code_review.clone(),
+            FactSheetField::DeploymentFiles => {
+                self.deployment_files = other.deployment_files.clone()
+            }
+        }
+    }
 }
 
 #[async_trait]
-pub trait SpecialFunctions: std::fmt::Debug {
+pub trait SpecialFunctions: std::fmt::Debug + Send {
     // Used by the manager to get agents' attributes
     fn get_attributes_from_agent(&self) -> &BasicAgent;
 
+    // Used by the manager to work out which agents depend on each other
+    fn reads(&self) -> Vec<FactSheetField>;
+    fn writes(&self) -> Vec<FactSheetField>;
+
     // managing agent can call agents to execute whatever task
-    async fn execute(
-        &mut self,
-        fact_sheet: &mut FactSheet,
-    ) -> Result<(), Box<dyn std::error::Error>>;
+    async fn execute(&mut self, fact_sheet: &mut FactSheet) -> Result<(), AgentError>;
+
+    // Why the agent finished with less than it set out to do, None when it did it all
+    fn degraded(&self) -> Option<String> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_object_from_llm_output() {
+        let llm_output = r#"[
+          {
+            "route": "/item/{id}",
+            "is_route_dynamic": "false",
+            "method": "GET",
+            "request_body": "None",
+            "response": { "id": "number", "name": "string" }
+          },
+          {
+            "route": "/crypto",
+            "is_route_dynamic": "true",
+            "method": "get",
+            "request_body": "None",
+            "response": "not_provided"
+          }
+        ]"#;
+
+        let routes: Vec<RouteObject> = serde_json::from_str(llm_output).unwrap();
+
+        assert!(routes[0].is_route_dynamic);
+        assert_eq!(routes[0].method, HttpMethod::Get);
+        // the route decides, not the flag the LLM printed
+        assert!(!routes[1].is_route_dynamic);
+        assert_eq!(routes[1].response, ShapeDescriptor::NotProvided);
+
+        let reparsed: Vec<RouteObject> =
+            serde_json::from_str(&serde_json::to_string(&routes).unwrap()).unwrap();
+        assert_eq!(reparsed, routes);
+    }
+
+    #[test]
+    fn test_changed_fields() {
+        let fact_sheet: FactSheet = serde_json::from_value(serde_json::json!({
+            "project_description": "build a website that tracks crypto prices",
+            "project_scope": null,
+            "external_urls": ["https://api.coincap.io/v2/assets"],
+            "backend_code": null,
+            "api_endpoint_schema": [],
+            "frontend_code": null,
+            "database_schema": null,
+            "security_report": null,
+            "code_review": null
+        }))
+        .unwrap();
+        let mut edited = fact_sheet.clone();
+        edited.external_urls = vec![];
+        edited.project_scope = Some(ProjectScope {
+            is_crud_required: true,
+            is_user_login_and_logout: false,
+            is_external_urls_required: false,
+        });
+
+        assert_eq!(
+            fact_sheet.changed_fields(&edited),
+            vec![FactSheetField::ProjectScope, FactSheetField::ExternalUrls]
+        );
+        assert_eq!(
+            edited.field_value(FactSheetField::ExternalUrls),
+            serde_json::json!([])
+        );
+        assert!(fact_sheet.changed_fields(&fact_sheet).is_empty());
+    }
 }
@@ -0,0 +1,533 @@
+use std::{
+    any::Any,
+    fmt,
+    panic::AssertUnwindSafe,
+    time::{Duration, Instant},
+};
+
+use futures_util::FutureExt;
+
+use crate::{
+    helpers::{workspace::run_blocking, workspace_history::Snapshot},
+    models::{
+        agent_basic::basic_agent::AgentState,
+        agents::agent_traits::{FactSheet, FactSheetField, SpecialFunctions},
+        general::agent_error::AgentError,
+    },
+};
+
+// Creates the agent that takes over from a failed one
+pub type CreateAgent = Box<dyn Fn() -> Box<dyn SpecialFunctions> + Send + Sync>;
+
+// What the managing agent does when an agent returns an error or panics.
+// Whatever the policy, a denied approval always aborts
+pub enum FailurePolicy {
+    // stop the project once the agents running alongside it are done
+    Abort,
+    // carry on without anything the agent wrote
+    Skip,
+    // run the agent again, it picks up from the state it failed in, then abort
+    Retry(u8),
+    // start another agent from scratch in its place, then abort
+    Fallback(CreateAgent),
+}
+
+impl fmt::Debug for FailurePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Abort => write!(f, "Abort"),
+            Self::Skip => write!(f, "Skip"),
+            Self::Retry(retries) => write!(f, "Retry({})", retries),
+            Self::Fallback(_) => write!(f, "Fallback"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AgentRunResult {
+    Succeeded { attempts: u8 },
+    // finished, but had to settle for less, e.g. an earlier version of the code
+    Degraded { reason: String },
+    FellBack { error: String },
+    Skipped { error: String },
+    Failed { error: String },
+    NotRun,
+}
+
+impl AgentRunResult {
+    // whether the fields the agent wrote can be used by the agents after it
+    pub fn is_usable(&self) -> bool {
+        matches!(
+            self,
+            Self::Succeeded { .. } | Self::Degraded { .. } | Self::FellBack { .. }
+        )
+    }
+}
+
+impl fmt::Display for AgentRunResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Succeeded { attempts: 1 } => write!(f, "ok"),
+            Self::Succeeded { attempts } => write!(f, "ok after {} attempts", attempts),
+            Self::Degraded { reason } => write!(f, "degraded ({})", reason),
+            Self::FellBack { error } => write!(f, "fell back ({})", error),
+            Self::Skipped { error } => write!(f, "skipped ({})", error),
+            Self::Failed { error } => write!(f, "FAILED ({})", error),
+            Self::NotRun => write!(f, "not run"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgentRunReport {
+    pub position: String,
+    pub state: AgentState,
+    pub result: AgentRunResult,
+    pub elapsed: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RunSummary {
+    pub reports: Vec<AgentRunReport>,
+}
+
+impl RunSummary {
+    pub fn is_failed(&self) -> bool {
+        self.reports
+            .iter()
+            .any(|report| matches!(report.result, AgentRunResult::Failed { .. }))
+    }
+
+    pub fn is_degraded(&self) -> bool {
+        self.reports
+            .iter()
+            .any(|report| matches!(report.result, AgentRunResult::Degraded { .. }))
+    }
+}
+
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let position_width = self
+            .reports
+            .iter()
+            .map(|report| report.position.len())
+            .max()
+            .unwrap_or_default()
+            .max("Agent".len());
+
+        writeln!(
+            f,
+            "{:<position_width$}  {:<11}  {:>8}  Result",
+            "Agent", "State", "Elapsed"
+        )?;
+        for report in &self.reports {
+            writeln!(
+                f,
+                "{:<position_width$}  {:<11}  {:>7.1}s  {}",
+                report.position,
+                format!("{:?}", report.state),
+                report.elapsed.as_secs_f64(),
+                report.result
+            )?;
+        }
+        write!(
+            f,
+            "Project {}",
+            if self.is_failed() {
+                "failed"
+            } else if self.is_degraded() {
+                "degraded"
+            } else {
+                "succeeded"
+            }
+        )
+    }
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown reason".to_string())
+}
+
+// Agents return an AgentError when they give up, a panic still counts as a failure too
+async fn execute_agent(
+    agent: &mut dyn SpecialFunctions,
+    fact_sheet: &mut FactSheet,
+) -> Result<(), AgentError> {
+    match AssertUnwindSafe(agent.execute(fact_sheet))
+        .catch_unwind()
+        .await
+    {
+        Ok(response) => response,
+        Err(panic) => Err(AgentError::Panicked(panic_message(panic))),
+    }
+}
+
+// Take back what a failed agent saved into the project, the history still has it
+async fn restore_workspace(
+    snapshot: Option<&Snapshot>,
+    fields: Vec<FactSheetField>,
+) -> Result<(), AgentError> {
+    let Some(snapshot) = snapshot.cloned() else {
+        return Ok(());
+    };
+    run_blocking(move || {
+        let mut paths: Vec<&str> = fields
+            .iter()
+            .flat_map(|field| field.workspace_paths().iter().copied())
+            .collect();
+        paths.sort_unstable();
+        paths.dedup();
+        snapshot.restore(&paths)
+    })
+    .await
+}
+
+// Run an agent on its own copy of the fact sheet, applying its failure policy.
+// The files of an agent that is skipped or replaced go back to the snapshot taken before it ran
+pub async fn run_agent(
+    agent: &mut dyn SpecialFunctions,
+    failure_policy: &FailurePolicy,
+    fact_sheet: &FactSheet,
+    snapshot: Option<&Snapshot>,
+) -> (FactSheet, AgentRunReport) {
+    let started = Instant::now();
+    let position = agent.get_attributes_from_agent().position.clone();
+    let mut working_copy = fact_sheet.clone();
+
+    let mut attempts: u8 = 1;
+    let mut response = execute_agent(agent, &mut working_copy).await;
+    if let FailurePolicy::Retry(retries) = failure_policy {
+        while response.as_ref().is_err_and(|e| !e.is_denial()) && attempts <= *retries {
+            attempts += 1;
+            response = execute_agent(agent, &mut working_copy).await;
+        }
+    }
+    let mut state = agent.get_attributes_from_agent().state;
+
+    let result = match (response, failure_policy) {
+        (Ok(()), _) => match agent.degraded() {
+            Some(reason) => AgentRunResult::Degraded { reason },
+            None => AgentRunResult::Succeeded { attempts },
+        },
+        (Err(error), _) if error.is_denial() => AgentRunResult::Failed {
+            error: error.to_string(),
+        },
+        (Err(error), FailurePolicy::Skip) => {
+            match restore_workspace(snapshot, agent.writes()).await {
+                Ok(()) => AgentRunResult::Skipped {
+                    error: error.to_string(),
+                },
+                Err(restore_error) => AgentRunResult::Failed {
+                    error: format!(
+                        "{}, then restoring its files failed: {}",
+                        error, restore_error
+                    ),
+                },
+            }
+        }
+        (Err(error), FailurePolicy::Fallback(create_fallback_agent)) => {
+            if let Err(restore_error) = restore_workspace(snapshot, agent.writes()).await {
+                AgentRunResult::Failed {
+                    error: format!(
+                        "{}, then restoring its files failed: {}",
+                        error, restore_error
+                    ),
+                }
+            } else {
+                let mut fallback_agent = create_fallback_agent();
+                working_copy = fact_sheet.clone();
+                let fallback_response =
+                    execute_agent(fallback_agent.as_mut(), &mut working_copy).await;
+                state = fallback_agent.get_attributes_from_agent().state;
+                match fallback_response {
+                    Ok(()) => AgentRunResult::FellBack {
+                        error: error.to_string(),
+                    },
+                    Err(fallback_error) => AgentRunResult::Failed {
+                        error: format!("{}, then the fallback failed: {}", error, fallback_error),
+                    },
+                }
+            }
+        }
+        (Err(error), FailurePolicy::Abort | FailurePolicy::Retry(_)) => AgentRunResult::Failed {
+            error: error.to_string(),
+        },
+    };
+
+    let report = AgentRunReport {
+        position,
+        state,
+        result,
+        elapsed: started.elapsed(),
+    };
+    (working_copy, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+
+    use std::{fs, path::PathBuf};
+
+    use super::*;
+    use crate::{
+        helpers::workspace_history::WorkspaceHistory, models::agent_basic::basic_agent::BasicAgent,
+    };
+
+    #[derive(Debug, Clone, Copy)]
+    enum Failure {
+        Error,
+        Panic,
+        Denial,
+    }
+
+    // Fails a given number of times, then writes the frontend code
+    #[derive(Debug)]
+    struct FlakyAgent {
+        attributes: BasicAgent,
+        failures_left: u8,
+        failure: Failure,
+    }
+
+    impl FlakyAgent {
+        fn new(failures_left: u8, failure: Failure) -> Self {
+            Self {
+                attributes: BasicAgent {
+                    objective: "".to_string(),
+                    position: "Flaky".to_string(),
+                    state: AgentState::Discovering,
+                    memory: vec![],
+                },
+                failures_left,
+                failure,
+            }
+        }
+    }
+
+    #[async_trait]
+    impl SpecialFunctions for FlakyAgent {
+        fn get_attributes_from_agent(&self) -> &BasicAgent {
+            &self.attributes
+        }
+
+        fn reads(&self) -> Vec<FactSheetField> {
+            vec![]
+        }
+
+        fn writes(&self) -> Vec<FactSheetField> {
+            vec![FactSheetField::FrontendCode]
+        }
+
+        async fn execute(&mut self, fact_sheet: &mut FactSheet) -> Result<(), AgentError> {
+            self.attributes.state = AgentState::Working;
+            if self.failures_left > 0 {
+                self.failures_left -= 1;
+                return Err(match self.failure {
+                    Failure::Error => AgentError::Validation("no luck".to_string()),
+                    Failure::Panic => panic!("too many bugs"),
+                    Failure::Denial => AgentError::ApprovalDenied {
+                        agent: "Flaky".to_string(),
+                        reason: "no".to_string(),
+                    },
+                });
+            }
+            fact_sheet.frontend_code = Some("<html></html>".to_string());
+            self.attributes.state = AgentState::Finished;
+            Ok(())
+        }
+    }
+
+    fn fact_sheet() -> FactSheet {
+        serde_json::from_value(serde_json::json!({
+            "project_description": "build a website",
+            "project_scope": null,
+            "external_urls": [],
+            "backend_code": null,
+            "api_endpoint_schema": []
+        }))
+        .unwrap()
+    }
+
+    async fn result_of(mut agent: FlakyAgent, failure_policy: FailurePolicy) -> AgentRunResult {
+        let (fact_sheet, report) =
+            run_agent(&mut agent, &failure_policy, &fact_sheet(), None).await;
+        assert_eq!(
+            fact_sheet.frontend_code.is_some(),
+            report.result.is_usable()
+        );
+        report.result
+    }
+
+    #[tokio::test]
+    async fn test_failure_policies() {
+        assert_eq!(
+            result_of(FlakyAgent::new(0, Failure::Error), FailurePolicy::Abort).await,
+            AgentRunResult::Succeeded { attempts: 1 }
+        );
+        assert_eq!(
+            result_of(FlakyAgent::new(2, Failure::Error), FailurePolicy::Retry(2)).await,
+            AgentRunResult::Succeeded { attempts: 3 }
+        );
+        assert_eq!(
+            result_of(FlakyAgent::new(1, Failure::Panic), FailurePolicy::Skip).await,
+            AgentRunResult::Skipped {
+                error: "Agent panicked: too many bugs".to_string()
+            }
+        );
+        assert_eq!(
+            result_of(
+                FlakyAgent::new(3, Failure::Error),
+                FailurePolicy::Fallback(Box::new(|| Box::new(FlakyAgent::new(0, Failure::Error))))
+            )
+            .await,
+            AgentRunResult::FellBack {
+                error: "Validation failed: no luck".to_string()
+            }
+        );
+        assert_eq!(
+            result_of(FlakyAgent::new(3, Failure::Error), FailurePolicy::Retry(1)).await,
+            AgentRunResult::Failed {
+                error: "Validation failed: no luck".to_string()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_denial_is_never_retried() {
+        let denied = AgentRunResult::Failed {
+            error: "Flaky was denied approval: no".to_string(),
+        };
+        assert_eq!(
+            result_of(FlakyAgent::new(1, Failure::Denial), FailurePolicy::Retry(3)).await,
+            denied
+        );
+        assert_eq!(
+            result_of(
+                FlakyAgent::new(1, Failure::Denial),
+                FailurePolicy::Fallback(Box::new(|| Box::new(FlakyAgent::new(0, Failure::Error))))
+            )
+            .await,
+            denied
+        );
+        assert_eq!(
+            result_of(FlakyAgent::new(1, Failure::Denial), FailurePolicy::Skip).await,
+            denied
+        );
+    }
+
+    // Saves the frontend into the project, then gives up
+    #[derive(Debug)]
+    struct HalfDoneAgent {
+        attributes: BasicAgent,
+        root: PathBuf,
+    }
+
+    #[async_trait]
+    impl SpecialFunctions for HalfDoneAgent {
+        fn get_attributes_from_agent(&self) -> &BasicAgent {
+            &self.attributes
+        }
+
+        fn reads(&self) -> Vec<FactSheetField> {
+            vec![]
+        }
+
+        fn writes(&self) -> Vec<FactSheetField> {
+            vec![FactSheetField::FrontendCode]
+        }
+
+        async fn execute(&mut self, _fact_sheet: &mut FactSheet) -> Result<(), AgentError> {
+            fs::create_dir_all(self.root.join("frontend")).unwrap();
+            fs::write(self.root.join("frontend/index.html"), "<html>").unwrap();
+            Err(AgentError::Validation("no luck".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_skip_restores_files() {
+        let root = std::env::temp_dir().join(format!("auto_gippity_{}_skip", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("Cargo.toml"), "[package]\n").unwrap();
+        let history = WorkspaceHistory::new(&root);
+        let snapshot = history.snapshot("Before the Flaky").unwrap();
+
+        let mut agent = HalfDoneAgent {
+            attributes: FlakyAgent::new(0, Failure::Error).attributes,
+            root: root.clone(),
+        };
+        let (_, report) = run_agent(
+            &mut agent,
+            &FailurePolicy::Skip,
+            &fact_sheet(),
+            Some(&snapshot),
+        )
+        .await;
+
+        assert_eq!(
+            report.result,
+            AgentRunResult::Skipped {
+                error: "Validation failed: no luck".to_string()
+            }
+        );
+        assert!(!root.join("frontend").exists());
+        assert!(root.join("Cargo.toml").is_file());
+        fs::remove_dir_all(&root).unwrap();
+        fs::remove_dir_all(format!("{}.git", root.display())).unwrap();
+    }
+
+    #[test]
+    fn test_run_summary() {
+        let report = |position: &str, state: AgentState, result: AgentRunResult| AgentRunReport {
+            position: position.to_string(),
+            state,
+            result,
+            elapsed: Duration::from_millis(1500),
+        };
+        let run_summary = RunSummary {
+            reports: vec![
+                report(
+                    "Backend Developer",
+                    AgentState::Finished,
+                    AgentRunResult::Succeeded { attempts: 1 },
+                ),
+                report(
+                    "QA Engineer",
+                    AgentState::Validation,
+                    AgentRunResult::Failed {
+                        error: "tests fail".to_string(),
+                    },
+                ),
+                report("Frontend", AgentState::Discovering, AgentRunResult::NotRun),
+            ],
+        };
+
+        assert!(run_summary.is_failed());
+        assert_eq!(
+            run_summary.to_string(),
+            "Agent              State         Elapsed  Result\n\
+             Backend Developer  Finished         1.5s  ok\n\
+             QA Engineer        Validation       1.5s  FAILED (tests fail)\n\
+             Frontend           Discovering      1.5s  not run\n\
+             Project failed"
+        );
+
+        let run_summary = RunSummary {
+            reports: vec![report(
+                "Backend Developer",
+                AgentState::Finished,
+                AgentRunResult::Degraded {
+                    reason: "rolled back".to_string(),
+                },
+            )],
+        };
+        assert!(!run_summary.is_failed());
+        assert!(run_summary.to_string().ends_with(
+            "Backend Developer  Finished         1.5s  degraded (rolled back)\n\
+             Project degraded"
+        ));
+    }
+}
@@ -0,0 +1,169 @@
+use thiserror::Error;
+
+use crate::models::agents::agent_traits::FactSheetField;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum PlanError {
+    #[error(
+        "{agent} reads {field}, but no agent writes it and the fact sheet does not provide it"
+    )]
+    UnmetInput {
+        agent: String,
+        field: FactSheetField,
+    },
+    #[error("agents depend on each other in a cycle: {}", .agents.join(", "))]
+    Cycle { agents: Vec<String> },
+}
+
+// What the plan needs to know about an agent
+#[derive(Debug, Clone)]
+pub struct AgentNode {
+    pub position: String,
+    pub reads: Vec<FactSheetField>,
+    pub writes: Vec<FactSheetField>,
+}
+
+fn writers_before(nodes: &[AgentNode], index: usize, field: FactSheetField) -> Vec<usize> {
+    (0..index)
+        .filter(|&writer| nodes[writer].writes.contains(&field))
+        .collect()
+}
+
+// Agents that have to finish before the agent at `index` can start.
+// Several agents may write the same field, then the order in which they were added decides:
+// a reader waits for the writers added before it, or for all writers if none was added before it.
+fn dependencies(
+    nodes: &[AgentNode],
+    index: usize,
+    provided: &[FactSheetField],
+) -> Result<Vec<usize>, PlanError> {
+    let node = &nodes[index];
+    let mut dependencies: Vec<usize> = vec![];
+
+    for &field in &node.reads {
+        let earlier_writers = writers_before(nodes, index, field);
+        let all_writers: Vec<usize> = (0..nodes.len())
+            .filter(|&writer| writer != index && nodes[writer].writes.contains(&field))
+            .collect();
+
+        if !earlier_writers.is_empty() {
+            dependencies.extend(earlier_writers);
+        } else if !all_writers.is_empty() {
+            dependencies.extend(all_writers);
+        } else if !provided.contains(&field) {
+            return Err(PlanError::UnmetInput {
+                agent: node.position.clone(),
+                field,
+            });
+        }
+    }
+
+    for &field in &node.writes {
+        // two writers of a field never run at the same time
+        dependencies.extend(writers_before(nodes, index, field));
+        // nor is a field overwritten before an earlier reader of its previous value got to it
+        dependencies.extend((0..index).filter(|&reader| {
+            nodes[reader].reads.contains(&field) && !writers_before(nodes, reader, field).is_empty()
+        }));
+    }
+
+    dependencies.sort_unstable();
+    dependencies.dedup();
+    Ok(dependencies)
+}
+
+// Group agents into waves: every agent of a wave only depends on agents of earlier waves,
+// so the agents of one wave can run concurrently
+pub fn plan_waves(
+    nodes: &[AgentNode],
+    provided: &[FactSheetField],
+) -> Result<Vec<Vec<usize>>, PlanError> {
+    let dependencies: Vec<Vec<usize>> = (0..nodes.len())
+        .map(|index| dependencies(nodes, index, provided))
+        .collect::<Result<_, _>>()?;
+
+    let mut waves: Vec<Vec<usize>> = vec![];
+    let mut is_done = vec![false; nodes.len()];
+
+    while is_done.contains(&false) {
+        let wave: Vec<usize> = (0..nodes.len())
+            .filter(|&index| !is_done[index])
+            .filter(|&index| {
+                dependencies[index]
+                    .iter()
+                    .all(|&dependency| is_done[dependency])
+            })
+            .collect();
+        if wave.is_empty() {
+            return Err(PlanError::Cycle {
+                agents: (0..nodes.len())
+                    .filter(|&index| !is_done[index])
+                    .map(|index| nodes[index].position.clone())
+                    .collect(),
+            });
+        }
+        for &index in &wave {
+            is_done[index] = true;
+        }
+        waves.push(wave);
+    }
+
+    Ok(waves)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use FactSheetField::*;
+
+    fn node(position: &str, reads: &[FactSheetField], writes: &[FactSheetField]) -> AgentNode {
+        AgentNode {
+            position: position.to_string(),
+            reads: reads.to_vec(),
+            writes: writes.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_plan_waves() {
+        let nodes = [
+            // added before the agent it depends on
+            node("Frontend", &[ApiEndpointSchema], &[FrontendCode]),
+            node("Architect", &[ProjectDescription], &[ProjectScope]),
+            node(
+                "Backend",
+                &[ProjectScope],
+                &[BackendCode, ApiEndpointSchema],
+            ),
+            node("Auth", &[BackendCode], &[BackendCode, ApiEndpointSchema]),
+            node("DevOps", &[BackendCode], &[DeploymentFiles]),
+            node("Docs", &[FrontendCode, DeploymentFiles], &[]),
+        ];
+
+        assert_eq!(
+            plan_waves(&nodes, &[ProjectDescription]),
+            Ok(vec![vec![1], vec![2], vec![3], vec![0, 4], vec![5]])
+        );
+    }
+
+    #[test]
+    fn test_plan_errors() {
+        let nodes = [node("Backend", &[ProjectScope], &[BackendCode])];
+        assert_eq!(
+            plan_waves(&nodes, &[ProjectDescription]).unwrap_err().to_string(),
+            "Backend reads project_scope, but no agent writes it and the fact sheet does not provide it"
+        );
+
+        let nodes = [
+            node("Reviewer", &[BackendCode], &[CodeReview]),
+            node("Backend", &[CodeReview], &[BackendCode]),
+            node("Frontend", &[], &[FrontendCode]),
+        ];
+        assert_eq!(
+            plan_waves(&nodes, &[]),
+            Err(PlanError::Cycle {
+                agents: vec!["Reviewer".to_string(), "Backend".to_string()]
+            })
+        );
+    }
+}
@@ -1,12 +1,40 @@
+use std::{mem, sync::Arc, time::Duration};
+
+use futures_util::future::join_all;
+
 use crate::{
     ai_functions::aifunc_managing::convert_user_input_to_goal,
-    helpers::general::ai_task_request,
+    apis::call_requests::{LlmProvider, OpenAiConfig, OpenAiProvider},
+    helpers::{
+        command_line::PrintCommand,
+        general::ai_task_request,
+        openapi::export_api_schema,
+        sandbox::Sandbox,
+        workspace::run_blocking,
+        workspace_history::{Snapshot, WorkspaceHistory},
+    },
     models::{
         agent_basic::basic_agent::{AgentState, BasicAgent},
         agents::{
             agent_architect::AgentSolutionArchitect,
+            agent_auth::AgentAuthEngineer,
             agent_backend::AgentBackendDeveloper,
-            agent_traits::{FactSheet, SpecialFunctions},
+            agent_context::{AgentContext, CodeEditMode},
+            agent_database::AgentDatabaseEngineer,
+            agent_devops::AgentDevOpsEngineer,
+            agent_documentation::AgentDocumentationWriter,
+            agent_frontend::AgentFrontendDeveloper,
+            agent_qa::AgentQaEngineer,
+            agent_security::SecurityPolicy,
+            agent_traits::{FactSheet, RouteObject, SpecialFunctions},
+        },
+        agents_manager::{
+            agent_run::{run_agent, AgentRunReport, AgentRunResult, FailurePolicy, RunSummary},
+            execution_plan::{plan_waves, AgentNode},
+        },
+        general::{
+            agent_error::AgentError, approval::ApprovalPolicy, checkpoint::FactSheetCheckpoint,
+            llm::Message,
         },
     },
 };
@@ -15,11 +43,98 @@ use crate::{
 pub struct ManagingAgent {
     attributes: BasicAgent,
     fact_sheet: FactSheet,
-    agents: Vec<Box<dyn SpecialFunctions>>,
+    context: AgentContext,
+    // when set, a person reviews the fact sheet between waves of agents
+    checkpoint: Option<Arc<dyn FactSheetCheckpoint>>,
+    agents: Vec<(Box<dyn SpecialFunctions>, FailurePolicy)>,
+}
+
+// Sets up a managing agent for callers embedding the crate, e.g.
+// `ManagingAgent::builder().provider(my_provider).approval(InteractiveApproval).build(request)`
+#[derive(Debug, Default)]
+pub struct ManagingAgentBuilder {
+    provider: Option<Arc<dyn LlmProvider>>,
+    approval: Option<Arc<dyn ApprovalPolicy>>,
+    security: Option<SecurityPolicy>,
+    sandbox: Option<Sandbox>,
+    code_edits: Option<CodeEditMode>,
+    checkpoint: Option<Arc<dyn FactSheetCheckpoint>>,
+}
+
+impl ManagingAgentBuilder {
+    // Who answers the prompts of every agent, OpenAI with the keys from the environment by default
+    pub fn provider(mut self, provider: impl LlmProvider + 'static) -> Self {
+        self.provider = Some(Arc::new(provider));
+        self
+    }
+
+    // OpenAI with explicit keys and model instead of the environment
+    pub fn openai_config(self, config: OpenAiConfig) -> Self {
+        self.provider(OpenAiProvider::new(config))
+    }
+
+    // Who approves running generated code, the person at the terminal by default
+    pub fn approval(mut self, approval: impl ApprovalPolicy + 'static) -> Self {
+        self.approval = Some(Arc::new(approval));
+        self
+    }
+
+    // Which security findings stop the project before its code is built, critical ones by default
+    pub fn security_policy(mut self, security: SecurityPolicy) -> Self {
+        self.security = Some(security);
+        self
+    }
+
+    // Where generated code is built and run, namespaces and resource limits on Linux by default
+    pub fn sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.sandbox = Some(sandbox);
+        self
+    }
+
+    // Whether code is changed through edits rather than regenerated, regenerated by default
+    pub fn code_edits(mut self, code_edits: CodeEditMode) -> Self {
+        self.code_edits = Some(code_edits);
+        self
+    }
+
+    // Review the fact sheet after every wave of agents, no checkpoints by default
+    pub fn checkpoint(mut self, checkpoint: impl FactSheetCheckpoint + 'static) -> Self {
+        self.checkpoint = Some(Arc::new(checkpoint));
+        self
+    }
+
+    // Turns the user request into a project description, the only LLM call before execute_project
+    pub async fn build(self, user_request: String) -> Result<ManagingAgent, AgentError> {
+        let default_context = AgentContext::default();
+        let mut context = AgentContext::new(
+            self.provider.unwrap_or(default_context.provider),
+            self.approval.unwrap_or(default_context.approval),
+        );
+        if let Some(security) = self.security {
+            context.security = security;
+        }
+        if let Some(sandbox) = self.sandbox {
+            context.sandbox = sandbox;
+        }
+        if let Some(code_edits) = self.code_edits {
+            context.code_edits = code_edits;
+        }
+        let mut managing_agent = ManagingAgent::with_context(context, user_request).await?;
+        managing_agent.checkpoint = self.checkpoint;
+        Ok(managing_agent)
+    }
 }
 
 impl ManagingAgent {
-    pub async fn new(user_request: String) -> Result<Self, Box<dyn std::error::Error>> {
+    pub fn builder() -> ManagingAgentBuilder {
+        ManagingAgentBuilder::default()
+    }
+
+    pub async fn new(user_request: String) -> Result<Self, AgentError> {
+        Self::builder().build(user_request).await
+    }
+
+    async fn with_context(context: AgentContext, user_request: String) -> Result<Self, AgentError> {
         let attributes = BasicAgent {
             objective: "Manage agents who are building excellent websites for the user".to_string(),
             position: "Project Manager".to_string(),
@@ -28,14 +143,15 @@ impl ManagingAgent {
         };
 
         let project_description = ai_task_request(
+            context.provider.as_ref(),
             user_request,
             &attributes.position,
             get_function_string!(convert_user_input_to_goal),
             convert_user_input_to_goal,
         )
-        .await;
+        .await?;
 
-        let agents: Vec<Box<dyn SpecialFunctions>> = vec![];
+        let agents: Vec<(Box<dyn SpecialFunctions>, FailurePolicy)> = vec![];
 
         let fact_sheet = FactSheet {
             project_description,
@@ -43,40 +159,372 @@ impl ManagingAgent {
             external_urls: vec![],
             backend_code: None,
             api_endpoint_schema: vec![],
+            frontend_code: None,
+            database_schema: None,
+            security_report: None,
+            code_review: None,
+            deployment_files: vec![],
         };
 
         Ok(Self {
             attributes,
             fact_sheet,
+            context,
+            checkpoint: None,
             agents,
         })
     }
 
-    fn add_agent(&mut self, agent: Box<dyn SpecialFunctions>) {
-        self.agents.push(agent);
+    // What the agents have built so far
+    pub fn fact_sheet(&self) -> &FactSheet {
+        &self.fact_sheet
+    }
+
+    // Fact sheet changes people made at the checkpoints, oldest first
+    pub fn transcript(&self) -> &[Message] {
+        &self.attributes.memory
+    }
+
+    fn add_agent(&mut self, agent: Box<dyn SpecialFunctions>, failure_policy: FailurePolicy) {
+        self.agents.push((agent, failure_policy));
     }
 
-    // consider adding more agents, e.g. FrontendDeveloper, Database etc
-    // as a future enhancement
+    // The order only matters between agents writing the same fact sheet fields,
+    // everything else is worked out from what the agents read and write
     fn create_agents(&mut self) {
-        self.add_agent(Box::new(AgentSolutionArchitect::new()));
-        self.add_agent(Box::new(AgentBackendDeveloper::new()));
+        let context = self.context.clone();
+        self.add_agent(
+            Box::new(AgentSolutionArchitect::new(context.clone())),
+            FailurePolicy::Retry(1),
+        );
+        // without a schema the backend keeps its data in memory
+        self.add_agent(
+            Box::new(AgentDatabaseEngineer::new(context.clone())),
+            FailurePolicy::Skip,
+        );
+        // out of bug fixing budget, start over from the code template
+        let fallback_context = context.clone();
+        self.add_agent(
+            Box::new(AgentBackendDeveloper::new(context.clone())),
+            FailurePolicy::Fallback(Box::new(move || {
+                Box::new(AgentBackendDeveloper::new(fallback_context.clone()))
+            })),
+        );
+        self.add_agent(
+            Box::new(AgentAuthEngineer::new(context.clone())),
+            FailurePolicy::Abort,
+        );
+        self.add_agent(
+            Box::new(AgentQaEngineer::new(context.clone())),
+            FailurePolicy::Skip,
+        );
+        self.add_agent(
+            Box::new(AgentFrontendDeveloper::new(context)),
+            FailurePolicy::Retry(1),
+        );
+        self.add_agent(Box::new(AgentDevOpsEngineer::new()), FailurePolicy::Skip);
+        self.add_agent(
+            Box::new(AgentDocumentationWriter::new()),
+            FailurePolicy::Skip,
+        );
+    }
+
+    fn agent_nodes(&self) -> Vec<AgentNode> {
+        self.agents
+            .iter()
+            .map(|(agent, _)| AgentNode {
+                position: agent.get_attributes_from_agent().position.clone(),
+                reads: agent.reads(),
+                writes: agent.writes(),
+            })
+            .collect()
+    }
+
+    // Let a person correct the fact sheet before the next wave starts from it
+    fn run_checkpoint(&mut self, finished_agents: &[String]) -> Result<(), AgentError> {
+        let Some(checkpoint) = &self.checkpoint else {
+            return Ok(());
+        };
+        let reviewed = checkpoint.review(finished_agents, &self.fact_sheet)?;
+        self.apply_human_edits(finished_agents, &reviewed);
+        Ok(())
     }
 
-    pub async fn execute_project(&mut self) {
+    // Commit the project before a wave runs, so the files of the agents that fail can be taken back
+    async fn snapshot_workspace(&self, wave_agents: &[String]) -> Option<Snapshot> {
+        let message = format!("Before the {}", wave_agents.join(", "));
+        run_blocking(move || WorkspaceHistory::workspace().snapshot(&message))
+            .await
+            .map_err(|e| {
+                PrintCommand::Issue.print_agent_message(
+                    self.attributes.position.as_str(),
+                    format!(
+                        "Could not record the project, files of failed agents stay in it: {}",
+                        e
+                    )
+                    .as_str(),
+                );
+            })
+            .ok()
+    }
+
+    // Rewrite the exported API files whenever the merged endpoints differ from the ones last exported
+    fn export_api_schema(&self, exported: &mut Vec<RouteObject>) -> Result<(), AgentError> {
+        if self.fact_sheet.api_endpoint_schema == *exported {
+            return Ok(());
+        }
+        export_api_schema(&self.fact_sheet)?;
+        exported.clone_from(&self.fact_sheet.api_endpoint_schema);
+        Ok(())
+    }
+
+    // Take over the fields a person changed, and keep a record of them in the transcript
+    fn apply_human_edits(&mut self, finished_agents: &[String], reviewed: &FactSheet) {
+        for field in self.fact_sheet.changed_fields(reviewed) {
+            let content = format!(
+                "After {}, changed {} to {}",
+                finished_agents.join(", "),
+                field,
+                reviewed.field_value(field)
+            );
+            PrintCommand::AICall
+                .print_agent_message(self.attributes.position.as_str(), content.as_str());
+            self.attributes.memory.push(Message {
+                role: "user".to_string(),
+                content,
+            });
+            self.fact_sheet.merge_field(reviewed, field);
+        }
+    }
+
+    // Agents only wait for the agents whose fact sheet fields they read, the others run concurrently
+    pub async fn execute_project(&mut self) -> Result<RunSummary, AgentError> {
         self.create_agents();
 
-        for agent in &mut self.agents {
-            let agent_response = agent.execute(&mut self.fact_sheet).await;
+        let nodes = self.agent_nodes();
+        let waves = plan_waves(&nodes, &self.fact_sheet.provided_fields())?;
+        let mut reports: Vec<Option<AgentRunReport>> = vec![None; nodes.len()];
+        // the exported files follow the endpoints of the merged fact sheet, whoever changed them
+        let mut exported_endpoints: Vec<RouteObject> = vec![];
+
+        let wave_count = waves.len();
+        for (wave_index, wave) in waves.into_iter().enumerate() {
+            let wave_agents: Vec<String> = wave
+                .iter()
+                .map(|&index| nodes[index].position.clone())
+                .collect();
+            let snapshot = self.snapshot_workspace(&wave_agents).await;
+            // every agent works on its own copy, only the fields it writes are merged back.
+            // The agents of a wave run as tasks of their own, the others wait in their slots
+            let mut agents: Vec<Option<(Box<dyn SpecialFunctions>, FailurePolicy)>> =
+                mem::take(&mut self.agents).into_iter().map(Some).collect();
+            let agent_runs: Vec<_> = agents
+                .iter_mut()
+                .enumerate()
+                .filter(|(index, _)| wave.contains(index))
+                .filter_map(|(index, slot)| slot.take().map(|agent| (index, agent)))
+                .map(|(index, (mut agent, failure_policy))| {
+                    let fact_sheet = self.fact_sheet.clone();
+                    let snapshot = snapshot.clone();
+                    tokio::spawn(async move {
+                        let (agent_fact_sheet, report) = run_agent(
+                            agent.as_mut(),
+                            &failure_policy,
+                            &fact_sheet,
+                            snapshot.as_ref(),
+                        )
+                        .await;
+                        (index, agent, failure_policy, agent_fact_sheet, report)
+                    })
+                })
+                .collect();
 
-            let agent_info = agent.get_attributes_from_agent();
+            let mut wave_runs = vec![];
+            let mut join_error = None;
+            for agent_run in join_all(agent_runs).await {
+                match agent_run {
+                    Ok((index, agent, failure_policy, agent_fact_sheet, report)) => {
+                        agents[index] = Some((agent, failure_policy));
+                        wave_runs.push((index, agent_fact_sheet, report));
+                    }
+                    Err(e) => join_error = Some(e),
+                }
+            }
+            self.agents = agents.into_iter().flatten().collect();
+            // run_agent catches the agents' panics, this is the task itself going away
+            if let Some(e) = join_error {
+                return Err(AgentError::Panicked(e.to_string()));
+            }
+
+            let mut is_aborted = false;
+            for (index, agent_fact_sheet, report) in wave_runs {
+                if report.result.is_usable() {
+                    for &field in &nodes[index].writes {
+                        self.fact_sheet.merge_field(&agent_fact_sheet, field);
+                    }
+                } else {
+                    PrintCommand::Issue.print_agent_message(
+                        self.attributes.position.as_str(),
+                        format!("{}: {}", report.position, report.result).as_str(),
+                    );
+                }
+                is_aborted |= matches!(report.result, AgentRunResult::Failed { .. });
+                reports[index] = Some(report);
+            }
+            self.export_api_schema(&mut exported_endpoints)?;
+            if is_aborted {
+                break;
+            }
+
+            if wave_index + 1 < wave_count {
+                self.run_checkpoint(&wave_agents)?;
+                self.export_api_schema(&mut exported_endpoints)?;
+            }
         }
+
+        let reports = reports
+            .into_iter()
+            .zip(&self.agents)
+            .map(|(report, (agent, _))| {
+                report.unwrap_or_else(|| AgentRunReport {
+                    position: agent.get_attributes_from_agent().position.clone(),
+                    state: agent.get_attributes_from_agent().state,
+                    result: AgentRunResult::NotRun,
+                    elapsed: Duration::ZERO,
+                })
+            })
+            .collect();
+        Ok(RunSummary { reports })
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use async_trait::async_trait;
+
     use super::*;
+    use crate::{
+        apis::call_requests::CallGptError,
+        models::{
+            agents::agent_traits::Severity,
+            general::{approval::InteractiveApproval, llm::Message},
+        },
+    };
+
+    // Answers every prompt with the same text
+    #[derive(Debug)]
+    struct CannedProvider(&'static str);
+
+    #[async_trait]
+    impl LlmProvider for CannedProvider {
+        async fn call(&self, _messages: Vec<Message>) -> Result<String, CallGptError> {
+            Ok(self.0.to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_builder_injects_provider() {
+        let managing_agent = ManagingAgent::builder()
+            .provider(CannedProvider("build a website that tracks crypto prices"))
+            .approval(InteractiveApproval)
+            .security_policy(SecurityPolicy {
+                block_at: Severity::Medium,
+            })
+            .build("crypto prices please".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            managing_agent.fact_sheet().project_description,
+            "build a website that tracks crypto prices"
+        );
+        assert_eq!(managing_agent.context.security.block_at, Severity::Medium);
+    }
+
+    // Drops the external URLs the architect picked
+    #[derive(Debug)]
+    struct DropExternalUrls;
+
+    impl FactSheetCheckpoint for DropExternalUrls {
+        fn review(
+            &self,
+            _finished_agents: &[String],
+            fact_sheet: &FactSheet,
+        ) -> Result<FactSheet, AgentError> {
+            let mut reviewed = fact_sheet.clone();
+            reviewed.external_urls = vec![];
+            Ok(reviewed)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_records_human_edits() {
+        let mut managing_agent = ManagingAgent::builder()
+            .provider(CannedProvider("build a website that tracks crypto prices"))
+            .checkpoint(DropExternalUrls)
+            .build("crypto prices please".to_string())
+            .await
+            .unwrap();
+        managing_agent.fact_sheet.external_urls = vec!["https://example.com/prices".to_string()];
+
+        let finished_agents = vec!["Solutions architect".to_string()];
+        managing_agent.run_checkpoint(&finished_agents).unwrap();
+        // nothing left to change the second time around
+        managing_agent.run_checkpoint(&finished_agents).unwrap();
+
+        assert!(managing_agent.fact_sheet().external_urls.is_empty());
+        let transcript: Vec<&str> = managing_agent
+            .transcript()
+            .iter()
+            .map(|message| message.content.as_str())
+            .collect();
+        assert_eq!(
+            transcript,
+            vec!["After Solutions architect, changed external_urls to []"]
+        );
+    }
+
+    #[test]
+    fn test_project_plan() {
+        let mut managing_agent = ManagingAgent {
+            attributes: BasicAgent {
+                objective: "".to_string(),
+                position: "Project Manager".to_string(),
+                state: AgentState::Discovering,
+                memory: vec![],
+            },
+            fact_sheet: serde_json::from_value(serde_json::json!({
+                "project_description": "build a website that tracks crypto prices",
+                "project_scope": null,
+                "external_urls": [],
+                "backend_code": null,
+                "api_endpoint_schema": []
+            }))
+            .unwrap(),
+            context: AgentContext::default(),
+            checkpoint: None,
+            agents: vec![],
+        };
+        managing_agent.create_agents();
+
+        let nodes = managing_agent.agent_nodes();
+        let waves = plan_waves(&nodes, &managing_agent.fact_sheet.provided_fields()).unwrap();
+        let positions: Vec<Vec<&str>> = waves
+            .iter()
+            .map(|wave| {
+                wave.iter()
+                    .map(|&index| nodes[index].position.as_str())
+                    .collect()
+            })
+            .collect();
+
+        // once the backend is tested, the frontend doesn't wait for the deployment files
+        assert!(positions.iter().any(|wave| wave.len() > 1));
+        assert!(positions
+            .iter()
+            .any(|wave| wave.contains(&"Frontend Developer") && wave.contains(&"DevOps Engineer")));
+        assert_eq!(positions.last(), Some(&vec!["Documentation Writer"]));
+    }
 
     #[tokio::test]
     #[cfg(feature = "openai-coverage")]
@@ -88,6 +536,10 @@ mod tests {
             .await
             .expect("Error creating Managing Agent");
 
-        managing_agent.execute_project().await;
+        let run_summary = managing_agent
+            .execute_project()
+            .await
+            .expect("Error executing project");
+        assert!(!run_summary.is_failed());
     }
 }
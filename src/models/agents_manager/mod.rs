@@ -1 +1,3 @@
+pub mod agent_run;
+pub mod execution_plan;
 pub mod managing_agent;
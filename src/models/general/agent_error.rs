@@ -0,0 +1,110 @@
+use std::io;
+
+use thiserror::Error;
+
+use crate::{
+    apis::call_requests::CallGptError,
+    helpers::{source_files::SourceFilesError, supervisor::Limit},
+    models::agents_manager::execution_plan::PlanError,
+};
+
+// Everything that can go wrong while agents build a project.
+// Agents return it from `execute`, so a failing agent never takes the whole process down.
+#[derive(Error, Debug)]
+pub enum AgentError {
+    #[error("Configuration error: {0}")]
+    Config(String),
+    #[error("Agents cannot be scheduled: {0}")]
+    Plan(#[from] PlanError),
+    #[error("LLM request failed: {0}")]
+    Llm(CallGptError),
+    #[error("Failed to decode {what}: {source}")]
+    Decode {
+        what: &'static str,
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error("Failed to encode {what}: {source}")]
+    Encode {
+        what: &'static str,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[error("Failed to {action}: {source}")]
+    Io {
+        action: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Generated files were rejected: {0}")]
+    Files(#[from] SourceFilesError),
+    #[error("Build failed: {0}")]
+    Build(String),
+    #[error("Validation failed: {0}")]
+    Validation(String),
+    #[error("Workspace history failed: {0}")]
+    History(String),
+    #[error("Sandboxed run failed: {0}")]
+    Sandbox(String),
+    #[error("The {step} was stopped at its {limit}")]
+    LimitExceeded { step: &'static str, limit: Limit },
+    #[error("{agent} was denied approval: {reason}")]
+    ApprovalDenied { agent: String, reason: String },
+    #[error("Agent panicked: {0}")]
+    Panicked(String),
+}
+
+impl AgentError {
+    // A person or an approval rule said no: neither retrying nor a fallback agent should get around it
+    pub fn is_denial(&self) -> bool {
+        matches!(self, Self::ApprovalDenied { .. })
+    }
+
+    // For map_err on I/O calls, e.g. `fs::write(..).map_err(AgentError::io("write backend code"))`
+    pub fn io(action: impl Into<String>) -> impl FnOnce(io::Error) -> Self {
+        let action = action.into();
+        move |source| Self::Io { action, source }
+    }
+
+    pub fn encode<E>(what: &'static str) -> impl FnOnce(E) -> Self
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        move |source| Self::Encode {
+            what,
+            source: Box::new(source),
+        }
+    }
+}
+
+// A missing API key is a configuration problem rather than a transport one
+impl From<CallGptError> for AgentError {
+    fn from(error: CallGptError) -> Self {
+        match error {
+            CallGptError::MissingEnvVar(name) => {
+                Self::Config(format!("{} not found among environment variables", name))
+            }
+            error => Self::Llm(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_agent_error_messages() {
+        assert_eq!(
+            AgentError::from(CallGptError::MissingEnvVar("OPEN_AI_KEY")).to_string(),
+            "Configuration error: OPEN_AI_KEY not found among environment variables"
+        );
+
+        let result: Result<(), AgentError> = Err(io::Error::from(io::ErrorKind::NotFound))
+            .map_err(AgentError::io("read code template"));
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Failed to read code template: entity not found"
+        );
+    }
+}
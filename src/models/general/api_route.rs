@@ -0,0 +1,363 @@
+use std::{collections::BTreeMap, fmt, ops::Range, str::FromStr};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use strum_macros::{AsRefStr, Display, EnumIter, EnumString};
+
+// value used for every {param} segment when a route has to be called
+const DUMMY_PATH_PARAM: &str = "1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumString, AsRefStr, EnumIter)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum HttpMethod {
+    Get,
+    Post,
+    Put,
+    Patch,
+    Delete,
+    Head,
+    Options,
+}
+
+impl Serialize for HttpMethod {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_ref())
+    }
+}
+
+impl From<HttpMethod> for reqwest::Method {
+    fn from(method: HttpMethod) -> Self {
+        match method {
+            HttpMethod::Get => Self::GET,
+            HttpMethod::Post => Self::POST,
+            HttpMethod::Put => Self::PUT,
+            HttpMethod::Patch => Self::PATCH,
+            HttpMethod::Delete => Self::DELETE,
+            HttpMethod::Head => Self::HEAD,
+            HttpMethod::Options => Self::OPTIONS,
+        }
+    }
+}
+
+// LLMs print methods as "get", "GET" or even " Get ", accept all of them
+impl<'de> Deserialize<'de> for HttpMethod {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let method = String::deserialize(deserializer)?;
+        HttpMethod::from_str(method.trim())
+            .map_err(|_| de::Error::custom(format!("unknown HTTP method '{}'", method)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScalarType {
+    String,
+    Number,
+    Bool,
+    // any type name we don't recognise is kept verbatim
+    Other(String),
+}
+
+// Describes the JSON shape of a request body or a response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShapeDescriptor {
+    // "None": the route has no body/response
+    None,
+    // "not_provided": the LLM could not work out the shape
+    NotProvided,
+    Scalar(ScalarType),
+    Array(Box<ShapeDescriptor>),
+    Object(BTreeMap<String, ShapeDescriptor>),
+}
+
+impl From<&Value> for ShapeDescriptor {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Null => ShapeDescriptor::None,
+            Value::Bool(_) => ShapeDescriptor::Scalar(ScalarType::Bool),
+            Value::Number(_) => ShapeDescriptor::Scalar(ScalarType::Number),
+            Value::String(type_name) => shape_from_type_name(type_name),
+            // the first element is taken as representative of the whole list
+            Value::Array(items) => match items.first() {
+                Some(item) => ShapeDescriptor::Array(Box::new(ShapeDescriptor::from(item))),
+                None => ShapeDescriptor::Array(Box::new(ShapeDescriptor::NotProvided)),
+            },
+            Value::Object(fields) => ShapeDescriptor::Object(
+                fields
+                    .iter()
+                    .map(|(name, field)| (name.clone(), ShapeDescriptor::from(field)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl From<&ShapeDescriptor> for Value {
+    fn from(shape: &ShapeDescriptor) -> Self {
+        match shape {
+            ShapeDescriptor::None => Value::String("None".to_string()),
+            ShapeDescriptor::NotProvided => Value::String("not_provided".to_string()),
+            ShapeDescriptor::Scalar(scalar) => Value::String(
+                match scalar {
+                    ScalarType::String => "string",
+                    ScalarType::Number => "number",
+                    ScalarType::Bool => "bool",
+                    ScalarType::Other(type_name) => type_name.as_str(),
+                }
+                .to_string(),
+            ),
+            ShapeDescriptor::Array(item) => Value::Array(vec![Value::from(item.as_ref())]),
+            ShapeDescriptor::Object(fields) => Value::Object(
+                fields
+                    .iter()
+                    .map(|(name, field)| (name.clone(), Value::from(field)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl Serialize for ShapeDescriptor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Value::from(self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ShapeDescriptor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        Ok(ShapeDescriptor::from(&value))
+    }
+}
+
+fn shape_from_type_name(type_name: &str) -> ShapeDescriptor {
+    match type_name.trim().to_lowercase().as_str() {
+        "" | "none" | "null" => ShapeDescriptor::None,
+        "not_provided" => ShapeDescriptor::NotProvided,
+        "string" | "str" => ShapeDescriptor::Scalar(ScalarType::String),
+        "number" | "integer" | "int" | "float" | "i32" | "i64" | "u32" | "u64" | "f32" | "f64" => {
+            ShapeDescriptor::Scalar(ScalarType::Number)
+        }
+        "bool" | "boolean" => ShapeDescriptor::Scalar(ScalarType::Bool),
+        _ => ShapeDescriptor::Scalar(ScalarType::Other(type_name.trim().to_string())),
+    }
+}
+
+// Accepts true, "true", "TRUE", false, "false" and friends
+pub fn deserialize_lenient_bool<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<bool, D::Error> {
+    struct LenientBoolVisitor;
+
+    impl de::Visitor<'_> for LenientBoolVisitor {
+        type Value = bool;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a boolean or a string holding a boolean")
+        }
+
+        fn visit_bool<E: de::Error>(self, value: bool) -> Result<bool, E> {
+            Ok(value)
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<bool, E> {
+            match value.trim().to_lowercase().as_str() {
+                "true" => Ok(true),
+                "false" | "" => Ok(false),
+                other => Err(E::custom(format!("expected a boolean, got '{}'", other))),
+            }
+        }
+    }
+
+    deserializer.deserialize_any(LenientBoolVisitor)
+}
+
+// Byte range and name of every `{param}` of an actix route, also inside a segment ("/{id}.json")
+// and with a custom regex after a colon, which may hold braces of its own: "{id:\\d{3}}"
+fn path_param_spans(route: &str) -> Vec<(Range<usize>, String)> {
+    let mut spans = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, character) in route.char_indices() {
+        match character {
+            '{' => {
+                if depth == 0 {
+                    start = index;
+                }
+                depth += 1;
+            }
+            '}' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    let inner = &route[start + 1..index];
+                    let name = inner.split(':').next().unwrap_or(inner).trim();
+                    spans.push((start..index + 1, name.to_string()));
+                }
+            }
+            _ => {}
+        }
+    }
+    spans
+}
+
+// Names of the `{param}`s of an actix route, e.g. "/item/{id}" -> ["id"]
+pub fn parse_path_params(route: &str) -> Vec<String> {
+    path_param_spans(route)
+        .into_iter()
+        .map(|(_, name)| name)
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+// Every `{param}` of a route replaced, e.g. "/item/{id}.json" -> "/item/1.json"
+fn replace_path_params(route: &str, replace: impl Fn(&str) -> String) -> String {
+    let mut replaced = String::new();
+    let mut copied_until = 0;
+    for (span, name) in path_param_spans(route) {
+        replaced.push_str(&route[copied_until..span.start]);
+        replaced.push_str(&replace(&name));
+        copied_until = span.end;
+    }
+    replaced.push_str(&route[copied_until..]);
+    replaced
+}
+
+// Whether a concrete path (or a JS template of one) hits a route: "/item/{id}" matches
+// "/item/${itemId}" and "/item/42"
+pub fn route_matches(route: &str, path: &str) -> bool {
+    let route_segments: Vec<&str> = route.trim_end_matches('/').split('/').collect();
+    let target_segments: Vec<&str> = path.trim_end_matches('/').split('/').collect();
+
+    route_segments.len() == target_segments.len()
+        && route_segments
+            .iter()
+            .zip(target_segments.iter())
+            .all(|(route_segment, target_segment)| segment_matches(route_segment, target_segment))
+}
+
+// A segment with params matches whatever sits between its fixed parts: "{id}.json" matches "42.json"
+fn segment_matches(route_segment: &str, target_segment: &str) -> bool {
+    let spans = path_param_spans(route_segment);
+    let (Some((first, _)), Some((last, _))) = (spans.first(), spans.last()) else {
+        return route_segment == target_segment;
+    };
+    if target_segment.contains("${") {
+        return true;
+    }
+    let (prefix, suffix) = (&route_segment[..first.start], &route_segment[last.end..]);
+    target_segment.len() > prefix.len() + suffix.len()
+        && target_segment.starts_with(prefix)
+        && target_segment.ends_with(suffix)
+}
+
+// The route without custom regexes, e.g. "/files/{tail:.*}" -> "/files/{tail}" as OpenAPI expects
+pub fn path_template(route: &str) -> String {
+    replace_path_params(route, |name| format!("{{{}}}", name))
+}
+
+// "/item/{id}" -> "/item/1", a path that can actually be requested
+pub fn concrete_path(route: &str) -> String {
+    replace_path_params(route, |_| DUMMY_PATH_PARAM.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_http_method_accepts_any_case() {
+        for method in ["get", "GET", " Get "] {
+            let parsed: HttpMethod = serde_json::from_value(json!(method)).unwrap();
+            assert_eq!(parsed, HttpMethod::Get);
+        }
+        assert!(serde_json::from_value::<HttpMethod>(json!("fetch")).is_err());
+        assert_eq!(
+            serde_json::to_value(HttpMethod::Delete).unwrap(),
+            json!("delete")
+        );
+    }
+
+    #[test]
+    fn test_lenient_bool() {
+        #[derive(Deserialize)]
+        struct Flag {
+            #[serde(deserialize_with = "deserialize_lenient_bool")]
+            value: bool,
+        }
+
+        for (raw, expected) in [
+            (json!(true), true),
+            (json!("true"), true),
+            (json!("TRUE"), true),
+            (json!(false), false),
+            (json!("false"), false),
+        ] {
+            let flag: Flag = serde_json::from_value(json!({ "value": raw })).unwrap();
+            assert_eq!(flag.value, expected);
+        }
+        assert!(serde_json::from_value::<Flag>(json!({ "value": "maybe" })).is_err());
+    }
+
+    #[test]
+    fn test_shape_descriptor_round_trip() {
+        let raw = json!({
+            "id": "number",
+            "name": "String",
+            "completed": "bool",
+            "tags": ["string"],
+            "owner": "User"
+        });
+
+        let shape: ShapeDescriptor = serde_json::from_value(raw).unwrap();
+        let ShapeDescriptor::Object(fields) = &shape else {
+            panic!("expected an object shape, got {:?}", shape);
+        };
+        assert_eq!(fields["id"], ShapeDescriptor::Scalar(ScalarType::Number));
+        assert_eq!(fields["name"], ShapeDescriptor::Scalar(ScalarType::String));
+        assert_eq!(
+            fields["tags"],
+            ShapeDescriptor::Array(Box::new(ShapeDescriptor::Scalar(ScalarType::String)))
+        );
+        assert_eq!(
+            fields["owner"],
+            ShapeDescriptor::Scalar(ScalarType::Other("User".to_string()))
+        );
+
+        assert_eq!(
+            serde_json::from_value::<ShapeDescriptor>(json!("None")).unwrap(),
+            ShapeDescriptor::None
+        );
+        assert_eq!(
+            serde_json::to_value(&shape).unwrap()["name"],
+            json!("string")
+        );
+    }
+
+    #[test]
+    fn test_parse_path_params() {
+        assert_eq!(parse_path_params("/item/{id}"), vec!["id"]);
+        assert_eq!(
+            parse_path_params("/users/{user_id}/files/{tail:.*}"),
+            vec!["user_id", "tail"]
+        );
+        assert_eq!(parse_path_params("/item/{id}.json"), vec!["id"]);
+        assert_eq!(parse_path_params("/code/{id:\\d{3}}"), vec!["id"]);
+        assert!(parse_path_params("/crypto").is_empty());
+        assert_eq!(concrete_path("/item/{id}.json"), "/item/1.json");
+        assert_eq!(
+            path_template("/users/{user_id}/files/{tail:.*}"),
+            "/users/{user_id}/files/{tail}"
+        );
+    }
+
+    #[test]
+    fn test_route_matches() {
+        assert!(route_matches("/item/{id}", "/item/${id}"));
+        assert!(route_matches("/item/{id}", "/item/42"));
+        assert!(route_matches("/items", "/items/"));
+        assert!(!route_matches("/items", "/item"));
+        assert!(!route_matches("/item/{id}", "/item"));
+        assert!(route_matches("/item/{id}.json", "/item/42.json"));
+        assert!(!route_matches("/item/{id}.json", "/item/42"));
+    }
+}
@@ -0,0 +1,248 @@
+use std::{fmt, sync::Arc};
+
+use crate::{
+    helpers::command_line::review_generated_code,
+    models::{
+        agents::agent_traits::{SecurityReport, Severity},
+        general::agent_error::AgentError,
+    },
+};
+
+// What an agent asks approval for before running AI generated code on this machine
+#[derive(Debug)]
+pub struct ApprovalRequest<'a> {
+    pub agent_position: &'a str,
+    // what the policy approved last time, None when this agent hasn't had code approved yet
+    pub previous_code: Option<&'a str>,
+    pub code: &'a str,
+    pub security_report: &'a SecurityReport,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Approval {
+    Approved,
+    // approved after a person changed the code by hand, the edited code is what gets built
+    Edited { code: String },
+    Denied { reason: String },
+}
+
+// Decides whether AI generated code may be built and run
+pub trait ApprovalPolicy: fmt::Debug + Send + Sync {
+    fn approve(&self, request: &ApprovalRequest) -> Result<Approval, AgentError>;
+}
+
+// Stops the agent unless the policy approves, agents call this right before building generated code.
+// Returns the hand edited code when it should replace the generated one
+pub fn require_approval(
+    policy: &dyn ApprovalPolicy,
+    request: &ApprovalRequest,
+) -> Result<Option<String>, AgentError> {
+    match policy.approve(request)? {
+        Approval::Approved => Ok(None),
+        Approval::Edited { code } => Ok(Some(code)),
+        Approval::Denied { reason } => Err(AgentError::ApprovalDenied {
+            agent: request.agent_position.to_string(),
+            reason,
+        }),
+    }
+}
+
+// Asks the person at the terminal, after showing them the security report and what changed
+// in the code, they can also fix the code in their $EDITOR before it's built
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InteractiveApproval;
+
+impl ApprovalPolicy for InteractiveApproval {
+    fn approve(&self, request: &ApprovalRequest) -> Result<Approval, AgentError> {
+        review_generated_code(request)
+    }
+}
+
+// For sandboxes and CI, where nothing of value can be damaged
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AlwaysAllow;
+
+impl ApprovalPolicy for AlwaysAllow {
+    fn approve(&self, _request: &ApprovalRequest) -> Result<Approval, AgentError> {
+        Ok(Approval::Approved)
+    }
+}
+
+// Generates code without ever running it
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AlwaysDeny;
+
+impl ApprovalPolicy for AlwaysDeny {
+    fn approve(&self, _request: &ApprovalRequest) -> Result<Approval, AgentError> {
+        Ok(Approval::Denied {
+            reason: "running generated code is not allowed".to_string(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApprovalRule {
+    // the security review found nothing at all
+    CleanScan,
+    // every finding is below this severity
+    BelowSeverity(Severity),
+    // no finding of this security rule, e.g. "process-command"
+    WithoutFinding(String),
+}
+
+impl ApprovalRule {
+    // Why the report breaks the rule, None when it doesn't
+    fn violation(&self, security_report: &SecurityReport) -> Option<String> {
+        match self {
+            Self::CleanScan => (!security_report.findings.is_empty()).then(|| {
+                format!(
+                    "the security review has {} finding(s)",
+                    security_report.findings.len()
+                )
+            }),
+            Self::BelowSeverity(severity) => security_report
+                .highest_severity()
+                .filter(|highest| highest >= severity)
+                .map(|highest| format!("the security review has a {} finding", highest)),
+            Self::WithoutFinding(rule) => security_report
+                .findings
+                .iter()
+                .find(|finding| &finding.rule == rule)
+                .map(|finding| format!("the security review found {}", finding)),
+        }
+    }
+}
+
+// Approves when the code passes every rule, otherwise denies or hands over to another policy,
+// e.g. allow clean code and ask at the terminal about the rest
+#[derive(Debug, Clone)]
+pub struct RuleBasedApproval {
+    rules: Vec<ApprovalRule>,
+    otherwise: Option<Arc<dyn ApprovalPolicy>>,
+}
+
+impl RuleBasedApproval {
+    pub fn new(rules: Vec<ApprovalRule>) -> Self {
+        Self {
+            rules,
+            otherwise: None,
+        }
+    }
+
+    pub fn otherwise(mut self, policy: impl ApprovalPolicy + 'static) -> Self {
+        self.otherwise = Some(Arc::new(policy));
+        self
+    }
+}
+
+impl ApprovalPolicy for RuleBasedApproval {
+    fn approve(&self, request: &ApprovalRequest) -> Result<Approval, AgentError> {
+        let violation = self
+            .rules
+            .iter()
+            .find_map(|rule| rule.violation(request.security_report));
+
+        match (violation, &self.otherwise) {
+            (None, _) => Ok(Approval::Approved),
+            (Some(_), Some(otherwise)) => otherwise.approve(request),
+            (Some(reason), None) => Ok(Approval::Denied { reason }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::agents::agent_traits::SecurityFinding;
+
+    fn security_report(findings: &[(Severity, &str)]) -> SecurityReport {
+        SecurityReport {
+            findings: findings
+                .iter()
+                .map(|(severity, rule)| SecurityFinding {
+                    severity: *severity,
+                    rule: rule.to_string(),
+                    file: "src/main.rs".to_string(),
+                    line: 3,
+                    snippet: "Command::new(\"rm\")".to_string(),
+                    message: "runs a command".to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    fn approve(policy: &dyn ApprovalPolicy, security_report: &SecurityReport) -> Approval {
+        let request = ApprovalRequest {
+            agent_position: "Backend Developer",
+            previous_code: None,
+            code: "fn main() {}",
+            security_report,
+        };
+        policy.approve(&request).unwrap()
+    }
+
+    #[test]
+    fn test_rule_based_approval() {
+        let clean = security_report(&[]);
+        let medium = security_report(&[(Severity::Medium, "outbound-url")]);
+        let critical = security_report(&[(Severity::Critical, "process-command")]);
+
+        let clean_only = RuleBasedApproval::new(vec![ApprovalRule::CleanScan]);
+        assert_eq!(approve(&clean_only, &clean), Approval::Approved);
+        assert_eq!(
+            approve(&clean_only, &medium),
+            Approval::Denied {
+                reason: "the security review has 1 finding(s)".to_string()
+            }
+        );
+
+        let below_high = RuleBasedApproval::new(vec![
+            ApprovalRule::BelowSeverity(Severity::High),
+            ApprovalRule::WithoutFinding("process-command".to_string()),
+        ]);
+        assert_eq!(approve(&below_high, &medium), Approval::Approved);
+        assert_eq!(
+            approve(&below_high, &critical),
+            Approval::Denied {
+                reason: "the security review has a CRITICAL finding".to_string()
+            }
+        );
+
+        let clean_or_allow = clean_only.otherwise(AlwaysAllow);
+        assert_eq!(approve(&clean_or_allow, &critical), Approval::Approved);
+    }
+
+    #[derive(Debug)]
+    struct HandEdited;
+
+    impl ApprovalPolicy for HandEdited {
+        fn approve(&self, _request: &ApprovalRequest) -> Result<Approval, AgentError> {
+            Ok(Approval::Edited {
+                code: "fn main() { println!(\"edited\"); }".to_string(),
+            })
+        }
+    }
+
+    #[test]
+    fn test_require_approval() {
+        let request = ApprovalRequest {
+            agent_position: "Auth Engineer",
+            previous_code: Some("fn main() {}"),
+            code: "fn main() {}",
+            security_report: &security_report(&[]),
+        };
+
+        assert_eq!(require_approval(&AlwaysAllow, &request).unwrap(), None);
+        assert_eq!(
+            require_approval(&AlwaysDeny, &request)
+                .unwrap_err()
+                .to_string(),
+            "Auth Engineer was denied approval: running generated code is not allowed"
+        );
+
+        assert_eq!(
+            require_approval(&HandEdited, &request).unwrap().as_deref(),
+            Some("fn main() { println!(\"edited\"); }")
+        );
+    }
+}
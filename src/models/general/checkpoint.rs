@@ -0,0 +1,32 @@
+use std::fmt;
+
+use crate::{
+    helpers::command_line::review_fact_sheet,
+    models::{agents::agent_traits::FactSheet, general::agent_error::AgentError},
+};
+
+// Lets a person correct the fact sheet before the next agents build on it,
+// e.g. a wrong project scope spotted right after the architect ran
+pub trait FactSheetCheckpoint: fmt::Debug + Send + Sync {
+    // The fact sheet the next agents start from, the same one when nothing needs fixing
+    fn review(
+        &self,
+        finished_agents: &[String],
+        fact_sheet: &FactSheet,
+    ) -> Result<FactSheet, AgentError>;
+}
+
+// Shows the description, scope and external URLs at the terminal,
+// they can be edited one at a time or all together in $EDITOR
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InteractiveCheckpoint;
+
+impl FactSheetCheckpoint for InteractiveCheckpoint {
+    fn review(
+        &self,
+        finished_agents: &[String],
+        fact_sheet: &FactSheet,
+    ) -> Result<FactSheet, AgentError> {
+        review_fact_sheet(finished_agents, fact_sheet)
+    }
+}
@@ -1 +1,5 @@
+pub mod agent_error;
+pub mod api_route;
+pub mod approval;
+pub mod checkpoint;
 pub mod llm;